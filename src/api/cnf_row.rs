@@ -1,9 +1,7 @@
 
 use std::ops::{Add, AddAssign};
 
-use super::{Plant, OrderData, Wbs};
-
-use super::cnf_serde::three_digit_f64;
+use super::{DensityTable, Mark, Material, Plant, OrderData, Qty, Uom, Wbs};
 
 /// Confirmation file row (SAP Confirmation Files)
 /// 
@@ -11,6 +9,7 @@ use super::cnf_serde::three_digit_f64;
 /// ```tsv
 /// {mark}	S-{job}	{part wbs}	{part location: PROD}	{part qty}	{part UoM: EA}	{material master}	{material wbs}	{material qty}	{material UoM: IN2}	{material location}	{plant}	{program}	
 /// ```
+#[allow(clippy::tabs_in_doc_comments)]
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all="PascalCase")]
 pub struct CnfFileRow {
@@ -27,10 +26,12 @@ pub struct CnfFileRow {
     /// Part quantity
     pub part_qty: u64,
     /// Part unit of measure (EA)
-    pub part_uom: String,
+    #[serde(deserialize_with="Uom::deserialize")]
+    pub part_uom: Uom,
 
     /// Material master
-    pub matl:     String,
+    #[serde(deserialize_with="Material::deserialize")]
+    pub matl:     Material,
     /// Material WBS Element
     #[serde(deserialize_with="Wbs::deserialize")]
     // pub matl_wbs: Option<Wbs>,
@@ -40,10 +41,10 @@ pub struct CnfFileRow {
     /// This is the amount consumed for all parts.
     /// 
     /// `{qty per part} * {part_qty} = {matl_qty}`
-    #[serde(serialize_with="three_digit_f64")]
-    pub matl_qty: f64,
+    pub matl_qty: Qty,
     /// Material unit of measure (IN2, usually)
-    pub matl_uom: String,
+    #[serde(deserialize_with="Uom::deserialize")]
+    pub matl_uom: Uom,
     /// Material storage location
     pub matl_loc: Option<String>,
 
@@ -63,7 +64,25 @@ pub struct CnfFileRow {
 impl CnfFileRow {
 
     pub fn area_per_ea(&self) -> f64 {
-        self.matl_qty / self.part_qty as f64
+        self.matl_qty.to_f64() / self.part_qty as f64
+    }
+
+    /// Plate weight (lb) of the material consumed, if it is raw material in `IN2`
+    pub fn weight(&self, densities: &DensityTable) -> Option<f64> {
+        match self.matl_uom {
+            Uom::In2 => self.matl.weight(self.matl_qty, densities),
+            _ => None
+        }
+    }
+
+    /// Check that the mark's job matches the part WBS job
+    pub fn check_mark_job(&self) -> Result<(), String> {
+        Mark::from(self.mark.as_str()).check_wbs(&self.part_wbs)
+    }
+
+    /// Material qty consumed by `qty` parts, rounded to the thousandth
+    pub fn matl_qty_for(&self, qty: u64) -> Qty {
+        self.matl_qty.scale(qty, self.part_qty)
     }
 
     pub fn modify_with(&self, order: &OrderData) -> Self {
@@ -71,11 +90,27 @@ impl CnfFileRow {
 
         result.part_wbs = order.wbs.clone();
         result.part_qty = order.qty as u64;
-        result.matl_qty = self.area_per_ea() * order.qty as f64;
-        result.plant = order.plant.clone();
+        result.matl_qty = self.matl_qty_for(order.qty as u64);
+        result.plant = order.plant;
 
         result
     }
+
+    /// Split the row across orders, as with [`modify_with`](Self::modify_with)
+    ///
+    /// The material qty for all orders is split between the rows, so that they add up
+    /// to exactly the material qty of this row (if the orders are for all of `part_qty`).
+    pub fn split_across<'a>(&self, orders: impl IntoIterator<Item = &'a OrderData>) -> Vec<Self> {
+        let orders: Vec<&OrderData> = orders.into_iter().collect();
+        let qtys: Vec<u64> = orders.iter().map(|o| o.qty as u64).collect();
+
+        let total = self.matl_qty_for(qtys.iter().sum());
+        orders
+            .into_iter()
+            .zip(total.split(&qtys))
+            .map(|(order, matl_qty)| Self { matl_qty, ..self.modify_with(order) })
+            .collect()
+    }
 }
 
 impl Add<CnfFileRow> for CnfFileRow {
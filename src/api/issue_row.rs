@@ -1,8 +1,9 @@
 
+use std::ops::{Add, AddAssign};
+
 use regex::{Regex, RegexSetBuilder, RegexSet};
 
-use super::{CnfFileRow, Plant, Wbs};
-use super::cnf_serde::three_digit_f64;
+use super::{CnfFileRow, DensityTable, Material, Plant, Qty, Uom, UomRegistry, Wbs};
 
 lazy_static! {
     // Production job number match
@@ -10,7 +11,7 @@ lazy_static! {
 
     // machine name pattern matching
     static ref MACHINES: RegexSet = {
-        let names = vec!["gemini", "titan" , "mg", "farley", "ficep"];
+        let names = ["gemini", "titan" , "mg", "farley", "ficep"];
 
         // each name will must be begin and end with '-', '_', or string start/end
         RegexSetBuilder::new(
@@ -60,7 +61,8 @@ lazy_static! {
 /// |---|---|
 /// | Machine Parts (i.e. CNC table parts) | `634124` |
 /// | Shop Supplies (default) | `637118` |
-#[derive(Debug, Deserialize, Serialize)]
+#[allow(clippy::tabs_in_doc_comments)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all="PascalCase")]
 pub struct IssueFileRow {
     /// [Transaction code](#transaction-codes)
@@ -72,15 +74,17 @@ pub struct IssueFileRow {
 
 
     /// Material master
-    pub matl:     String,
+    #[serde(deserialize_with="Material::deserialize")]
+    pub matl:     Material,
     /// Material WBS Element
     // pub matl_wbs: Option<Wbs>,
+    #[serde(deserialize_with="Wbs::deserialize")]
     pub matl_wbs: Wbs,
     /// Material quantity
-    #[serde(serialize_with="three_digit_f64")]
-    pub matl_qty: f64,
+    pub matl_qty: Qty,
     /// Material unit of measure
-    pub matl_uom: String,
+    #[serde(deserialize_with="Uom::deserialize")]
+    pub matl_uom: Uom,
     /// Material storage location
     pub matl_loc: Option<String>,
 
@@ -91,7 +95,7 @@ pub struct IssueFileRow {
 }
 
 /// Issue codes
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 pub enum IssueCode {
     /// Issue material to the same project
     #[serde(rename = "PR01")]
@@ -110,34 +114,100 @@ pub enum IssueCode {
     CostCenterFromProject,
 }
 
-impl Into<IssueFileRow> for CnfFileRow {
-    /// Convert a [`CnfFileRow`] into an [`IssueFileRow`]
-    fn into(self) -> IssueFileRow {
-        let (code, user1, user2) = infer_codes(&self);
+impl std::fmt::Display for IssueCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = match self {
+            Self::ProjectFromProject      => "PR01",
+            Self::ProjectFromStock        => "PR02",
+            Self::ProjectFromOtherProject => "PR03",
+            Self::CostCenterFromStock     => "CC01",
+            Self::CostCenterFromProject   => "CC02",
+        };
 
-        IssueFileRow {
-            code, user1, user2,
+        write!(f, "{}", code)
+    }
+}
 
-            matl:     self.matl,
-            matl_wbs: self.matl_wbs,
-            matl_qty: self.matl_qty,
-            matl_uom: self.matl_uom,
-            matl_loc: self.matl_loc,
-            plant:    self.plant,
-            program:  self.program
+impl IssueFileRow {
+    /// Plate weight (lb) of the material issued, if it is raw material in `IN2`
+    pub fn weight(&self, densities: &DensityTable) -> Option<f64> {
+        match self.matl_uom {
+            Uom::In2 => self.matl.weight(self.matl_qty, densities),
+            _ => None
         }
     }
+
+    /// Convert the material qty to the unit SAP stocks the material in, if set in `registry`
+    pub fn into_stock_uom(mut self, registry: &UomRegistry) -> Result<Self, String> {
+        if let Some(uom) = registry.stock_uom(&self.matl) {
+            self.matl_qty = registry.convert(self.matl_qty, &self.matl_uom, uom, &self.matl)?;
+            self.matl_uom = uom.clone();
+        }
+
+        registry.validate(self.matl_qty, &self.matl_uom, &self.matl)?;
+
+        Ok(self)
+    }
+}
+
+impl Add<IssueFileRow> for IssueFileRow {
+    type Output = Self;
+
+    fn add(mut self, rhs: IssueFileRow) -> Self::Output {
+        self += rhs;
+
+        self
+    }
+}
+
+impl AddAssign<IssueFileRow> for IssueFileRow {
+    fn add_assign(&mut self, rhs: IssueFileRow) {
+        self.matl_qty += rhs.matl_qty;
+    }
 }
 
-impl Into<IssueFileRow> for &CnfFileRow {
+impl IssueFileRow {
+    /// Convert a [`CnfFileRow`], inferring the code and user columns from its part WBS
+    ///
+    /// fails if the part WBS is not a production job or cost center
+    pub fn try_from_cnf(row: &CnfFileRow) -> Result<Self, String> {
+        let (code, user1, user2) = try_infer_codes(row)?;
+
+        Ok(Self {
+            code, user1, user2,
+
+            matl:     row.matl.clone(),
+            matl_wbs: row.matl_wbs.clone(),
+            matl_qty: row.matl_qty,
+            matl_uom: row.matl_uom.clone(),
+            matl_loc: row.matl_loc.clone(),
+            plant:    row.plant,
+            program:  row.program.clone()
+        })
+    }
+}
+
+impl From<CnfFileRow> for IssueFileRow {
+    /// Convert a [`CnfFileRow`] into an [`IssueFileRow`]
+    fn from(row: CnfFileRow) -> Self {
+        (&row).into()
+    }
+}
+
+impl From<&CnfFileRow> for IssueFileRow {
     /// Convert a [`CnfFileRow`] into an [`IssueFileRow`]
-    fn into(self) -> IssueFileRow {
-        self.clone().into()
+    ///
+    /// panics if the codes cannot be inferred (see [`IssueFileRow::try_from_cnf`])
+    fn from(row: &CnfFileRow) -> Self {
+        Self::try_from_cnf(row).unwrap_or_else(|e| panic!("cnf -> issue conversion failed: {}", e))
     }
 }
 
-fn infer_codes(row: &CnfFileRow) -> (IssueCode, String, String) {
+fn try_infer_codes(row: &CnfFileRow) -> Result<(IssueCode, String, String), String> {
     let (user1, user2) = match &row.part_wbs {
+        Wbs::CostCenter { cc } if !Wbs::COST_CENTERS.contains(cc) => {
+            return Err( format!("{} cost center {} is not a 2xxx cost center", row.mark, cc) )
+        },
         Wbs::CostCenter { cc } => {
             // cost center issuing
             // let code = match &row.matl_wbs {
@@ -155,7 +225,7 @@ fn infer_codes(row: &CnfFileRow) -> (IssueCode, String, String) {
             // infer G/L account
             let user2 = infer_gl_acct(&row.mark);
     
-            return (code, format!("{}", user1), user2)
+            return Ok( (code, format!("{}", user1), user2) )
         },
         Wbs::Hd { job, id: _ } => {
             (format!("D-{}", job), "01".into())
@@ -163,7 +233,7 @@ fn infer_codes(row: &CnfFileRow) -> (IssueCode, String, String) {
         Wbs::Legacy { job, shipment } => {
             (format!("D-{}", job), format!("{:02}", shipment))
         },
-        Wbs::None => unreachable!()
+        Wbs::None => return Err( format!("{} has no part WBS element", row.mark) )
     };
 
     if PROD_JOB_WBS.is_match(&row.part_wbs.to_string()) {
@@ -194,15 +264,14 @@ fn infer_codes(row: &CnfFileRow) -> (IssueCode, String, String) {
             },
         };
 
-        return (code, user1, user2)
+        return Ok( (code, user1, user2) )
     }
 
     // unmatched data
-    panic!("cnf -> issue conversion failed");
-    // TODO: default result
+    Err( format!("{} part WBS {} is not a production job", row.mark, row.part_wbs) )
 }
 
-fn infer_gl_acct(mark: &String) -> String {
+fn infer_gl_acct(mark: &str) -> String {
     match MACHINES.is_match(mark) {
         true  => "634124".into(),   // machine parts
         false => "637118".into()    // all others
@@ -224,7 +293,7 @@ mod tests {
             
             matl: "50W-0008".into(),
             matl_wbs: Wbs::None,
-            matl_qty: 1_001.569f64,
+            matl_qty: Qty::from_milli(1_001_569),
             matl_uom: "IN2".into(),
             matl_loc: Some("K2".into()),
 
@@ -236,9 +305,9 @@ mod tests {
     #[test]
     fn machines_regex() {
         assert!(MACHINES.is_match("GEMINI_TABLE-A"));
-        assert_eq!(false, MACHINES.is_match("geminitest"));
+        assert!(!MACHINES.is_match("geminitest"));
         assert!(MACHINES.is_match("for_titan"));
-        assert_eq!(false, MACHINES.is_match("an_img"));
+        assert!(!MACHINES.is_match("an_img"));
         assert!(MACHINES.is_match("mg-test"));
         assert!(MACHINES.is_match("for_mg"));
         assert!(MACHINES.is_match("farley-a"));
@@ -247,7 +316,7 @@ mod tests {
     #[test]
     fn infer_job_shipment() {
         let row = get_test_row();
-        let (_, u1, u2) = infer_codes(&row);
+        let (_, u1, u2) = try_infer_codes(&row).unwrap();
 
         assert_eq!(&u1, "D-1210123");
         assert_eq!(&u2, "10");
@@ -256,7 +325,7 @@ mod tests {
     #[test]
     fn infer_project_from_stock() {
         let row = get_test_row();
-        let (c, ..) = infer_codes(&row);
+        let (c, ..) = try_infer_codes(&row).unwrap();
 
         assert_eq!(c, IssueCode::ProjectFromStock);
     }
@@ -267,7 +336,7 @@ mod tests {
         // row.matl_wbs = Some("D-1210123-10004".into());
         row.matl_wbs = "D-1210123-10004".try_into().unwrap();
 
-        let (c, ..) = infer_codes(&row);
+        let (c, ..) = try_infer_codes(&row).unwrap();
         assert_eq!(c, IssueCode::ProjectFromProject);
     }
 
@@ -275,9 +344,9 @@ mod tests {
     fn infer_project_from_other_project() {
         let mut row = get_test_row();
         // row.matl_wbs = Some("D-1200248-10004".into());
-        row.matl_wbs = "D-1210123-10004".try_into().unwrap();
+        row.matl_wbs = "D-1200248-10004".try_into().unwrap();
 
-        let (c, ..) = infer_codes(&row);
+        let (c, ..) = try_infer_codes(&row).unwrap();
 
         assert_eq!(c, IssueCode::ProjectFromOtherProject);
     }
@@ -288,7 +357,7 @@ mod tests {
         // row.job = "D-HSU".into();
         row.part_wbs = "S-HSU-2-2062".try_into().unwrap();
 
-        let (c, ..) = infer_codes(&row);
+        let (c, ..) = try_infer_codes(&row).unwrap();
 
         assert_eq!(c, IssueCode::CostCenterFromStock);
     }
@@ -300,18 +369,27 @@ mod tests {
         row.part_wbs = "S-HSU-2-2062".try_into().unwrap();
         row.matl_wbs = "D-1200248-10004".try_into().unwrap();
 
-        let (c, ..) = infer_codes(&row);
+        let (c, ..) = try_infer_codes(&row).unwrap();
 
         assert_eq!(c, IssueCode::CostCenterFromProject);
     }
 
+    #[test]
+    fn infer_cost_center_out_of_range() {
+        let mut row = get_test_row();
+        row.part_wbs = "S-HSU-2-5062".try_into().unwrap();
+
+        assert!(try_infer_codes(&row).is_err());
+    }
+
     #[test]
     #[should_panic]
     fn infer_fallout() {
         let mut row = get_test_row();
         row.part_wbs = "D-HSU-10004".try_into().unwrap();
+        assert!(IssueFileRow::try_from_cnf(&row).is_err());
 
-        let _ = infer_codes(&row);
+        let _ = IssueFileRow::from(&row);
     }
 }
 
@@ -0,0 +1,118 @@
+//! Piecemark parsing
+
+use std::fmt::{self, Display};
+
+use regex::Regex;
+
+use super::Wbs;
+
+lazy_static! {
+    // {job}{structure}-{piece}, e.g. 1210123A-X1A
+    static ref STANDARD_MARK: Regex = Regex::new(r"^(\d{7})([a-zA-Z])-([\w-]+)$").expect("Failed to build STANDARD_MARK regex");
+}
+
+/// Part mark (piecemark)
+///
+/// Marks such as `1210123A-X1A` are parsed into their job, structure and piece id.
+/// Other marks (machine parts, etc.) are kept as [`Mark::Other`], and every variant
+/// displays as the original text.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mark {
+    /// `{job}{structure}-{piece}`
+    Standard { job: String, structure: char, piece: String },
+    /// Non-standard mark
+    Other(String),
+}
+
+impl Mark {
+    pub fn job(&self) -> Option<&str> {
+        match self {
+            Self::Standard { job, .. } => Some(job),
+            Self::Other(_) => None
+        }
+    }
+
+    pub fn structure(&self) -> Option<char> {
+        match self {
+            Self::Standard { structure, .. } => Some(*structure),
+            Self::Other(_) => None
+        }
+    }
+
+    pub fn piece(&self) -> Option<&str> {
+        match self {
+            Self::Standard { piece, .. } => Some(piece),
+            Self::Other(_) => None
+        }
+    }
+
+    /// Check that the mark's job is the job of a project WBS element
+    ///
+    /// Non-standard marks and cost center (or no) WBS elements are not checked.
+    pub fn check_wbs(&self, wbs: &Wbs) -> Result<(), String> {
+        let wbs_job = match wbs {
+            Wbs::Hd { job, .. } | Wbs::Legacy { job, .. } => job,
+            Wbs::CostCenter { .. } | Wbs::None => return Ok(())
+        };
+
+        match self.job() {
+            Some(job) if job != wbs_job => Err( format!("{}: mark job {} does not match WBS {}", self, job, wbs) ),
+            _ => Ok(())
+        }
+    }
+}
+
+impl From<&str> for Mark {
+    fn from(value: &str) -> Self {
+        match STANDARD_MARK.captures(value) {
+            Some(caps) => Self::Standard {
+                job: caps[1].into(),
+                // safe to unwrap: regex matched 1 letter
+                structure: caps[2].chars().next().unwrap(),
+                piece: caps[3].into(),
+            },
+            None => Self::Other(value.into())
+        }
+    }
+}
+
+impl From<String> for Mark {
+    fn from(value: String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl Display for Mark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Standard { job, structure, piece } => write!(f, "{}{}-{}", job, structure, piece),
+            Self::Other(mark) => write!(f, "{}", mark),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_marks() {
+        let mark = Mark::from("1210123A-X1A");
+        assert_eq!(mark, Mark::Standard { job: "1210123".into(), structure: 'A', piece: "X1A".into() });
+        assert_eq!(mark.to_string(), "1210123A-X1A");
+
+        let mark = Mark::from("GEMINI_TABLE-A");
+        assert_eq!(mark.job(), None);
+        assert_eq!(mark.to_string(), "GEMINI_TABLE-A");
+    }
+
+    #[test]
+    fn mark_wbs_job() {
+        let mark = Mark::from("1210123A-X1A");
+
+        assert!(mark.check_wbs(&"D-1210123-10004".try_into().unwrap()).is_ok());
+        assert!(mark.check_wbs(&"S-1210123-2-10".try_into().unwrap()).is_ok());
+        assert!(mark.check_wbs(&"D-1210124-10004".try_into().unwrap()).is_err());
+        assert!(Mark::from("FICEP_PART").check_wbs(&"D-1210124-10004".try_into().unwrap()).is_ok());
+    }
+}
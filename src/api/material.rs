@@ -0,0 +1,225 @@
+//! Material master model and plate weights
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::Path;
+
+use regex::Regex;
+use serde::{Deserializer, Serialize};
+
+use crate::paths::{read_pairs, MATERIAL_DENSITIES};
+use super::Qty;
+
+lazy_static! {
+    // raw material: {grade}-{thickness code}
+    static ref RAW_MATERIAL: Regex = Regex::new(r"^([0-9A-Z]+)-(\d{4})$").expect("Failed to build RAW_MATERIAL regex");
+
+    // purchased part: SAP material number
+    static ref PURCHASED_PART: Regex = Regex::new(r"^\d+$").expect("Failed to build PURCHASED_PART regex");
+}
+
+/// Density of carbon steel, in lb/in³
+pub const STEEL_DENSITY: f64 = 0.2836;
+
+/// Material master
+///
+/// Parsed from text such as `50W-0008`. Formats that are not recognized are kept
+/// as [`Material::Other`], and every variant displays as the original text.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Material {
+    /// Raw plate material, `{grade}-{thickness}`
+    Raw { grade: String, thickness: Thickness },
+    /// Purchased part, by SAP material number
+    Purchased(String),
+    /// Unrecognized material master
+    Other(String),
+}
+
+impl Material {
+    pub fn is_raw(&self) -> bool {
+        matches!(self, Self::Raw { .. })
+    }
+
+    pub fn grade(&self) -> Option<&str> {
+        match self {
+            Self::Raw { grade, .. } => Some(grade),
+            _ => None
+        }
+    }
+
+    pub fn thickness(&self) -> Option<Thickness> {
+        match self {
+            Self::Raw { thickness, .. } => Some(*thickness),
+            _ => None
+        }
+    }
+
+    /// Weight (lb) of `area` (in²) of raw material
+    ///
+    /// returns `None` for purchased parts and unrecognized materials
+    pub fn weight(&self, area: Qty, densities: &DensityTable) -> Option<f64> {
+        match self {
+            Self::Raw { grade, thickness } => Some( area.to_f64() * thickness.inches() * densities.density(grade) ),
+            _ => None
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Material, D::Error>
+        where D: Deserializer<'de>
+    {
+        let s: String = serde::de::Deserialize::deserialize(deserializer)?;
+        Ok( Material::from(s.as_str()) )
+    }
+}
+
+impl From<&str> for Material {
+    fn from(value: &str) -> Self {
+        if let Some(caps) = RAW_MATERIAL.captures(value) {
+            return Self::Raw {
+                grade: caps[1].into(),
+                // safe to unwrap: regex matched 4 digits
+                thickness: Thickness(caps[2].parse().unwrap())
+            };
+        }
+
+        if PURCHASED_PART.is_match(value) {
+            return Self::Purchased(value.into());
+        }
+
+        Self::Other(value.into())
+    }
+}
+
+impl From<String> for Material {
+    fn from(value: String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Raw { grade, thickness } => write!(f, "{}-{}", grade, thickness),
+            Self::Purchased(matl) | Self::Other(matl) => write!(f, "{}", matl),
+        }
+    }
+}
+
+impl Serialize for Material {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Plate thickness code, in sixteenths of an inch (`0008` is 1/2")
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Thickness(u16);
+
+impl Thickness {
+    pub fn from_sixteenths(sixteenths: u16) -> Self {
+        Self(sixteenths)
+    }
+
+    pub fn sixteenths(&self) -> u16 {
+        self.0
+    }
+
+    pub fn inches(&self) -> f64 {
+        self.0 as f64 / 16.
+    }
+}
+
+impl Display for Thickness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}", self.0)
+    }
+}
+
+/// Material density (lb/in³) by grade
+///
+/// Grades not in the table are steel ([`STEEL_DENSITY`]).
+/// Densities can be added in [`MATERIAL_DENSITIES`], one `grade<TAB>density` pair per line:
+///
+/// ```text
+/// # aluminum
+/// 6061	0.0975
+/// ```
+#[allow(clippy::tabs_in_doc_comments)]
+#[derive(Debug, Clone)]
+pub struct DensityTable {
+    default: f64,
+    densities: HashMap<String, f64>,
+}
+
+impl Default for DensityTable {
+    fn default() -> Self {
+        Self { default: STEEL_DENSITY, densities: HashMap::new() }
+    }
+}
+
+impl DensityTable {
+    /// Steel for all grades, plus those in [`MATERIAL_DENSITIES`] if it exists
+    pub fn load_default() -> io::Result<Self> {
+        let mut result = Self::default();
+        if MATERIAL_DENSITIES.exists() {
+            result.load(*MATERIAL_DENSITIES)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Add densities from a tab delimited file of `grade<TAB>density` lines
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        for (grade, density) in read_pairs(path)? {
+            let density = density.trim().parse()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid density `{}` for grade `{}`", density, grade)))?;
+            self.add(&grade, density);
+        }
+
+        Ok(())
+    }
+
+    pub fn add(&mut self, grade: &str, density: f64) {
+        self.densities.insert(grade.trim().to_uppercase(), density);
+    }
+
+    /// Density of a grade, in lb/in³
+    pub fn density(&self, grade: &str) -> f64 {
+        self.densities.get(&grade.to_uppercase()).copied().unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_materials() {
+        let matl = Material::from("HPS70W-0024");
+        assert_eq!(matl, Material::Raw { grade: "HPS70W".into(), thickness: Thickness::from_sixteenths(24) });
+        assert_eq!(matl.thickness().unwrap().inches(), 1.5);
+        assert_eq!(matl.to_string(), "HPS70W-0024");
+
+        assert_eq!(Material::from("1001"), Material::Purchased("1001".into()));
+        assert_eq!(Material::from("50w-8"), Material::Other("50w-8".into()));
+        assert_eq!(Material::from("50w-8").to_string(), "50w-8");
+    }
+
+    #[test]
+    fn plate_weight() {
+        let mut densities = DensityTable::default();
+        densities.add("6061", 0.0975);
+
+        // 1/2" steel, 100 in²
+        let steel = Material::from("50W-0008").weight(Qty::from(100), &densities).unwrap();
+        assert!((steel - 14.18).abs() < 1e-9);
+
+        let aluminum = Material::from("6061-0016").weight(Qty::from(100), &densities).unwrap();
+        assert!((aluminum - 9.75).abs() < 1e-9);
+
+        assert_eq!(Material::from("1001").weight(Qty::from(100), &densities), None);
+    }
+}
@@ -8,24 +8,22 @@
 
 mod cnf_row;
 mod issue_row;
+mod mark;
+mod material;
 mod order;
 mod plant;
+mod qty;
+mod transfer_row;
+mod uom;
 mod wbs;
 
 pub use cnf_row::CnfFileRow;
-pub use issue_row::IssueFileRow;
+pub use issue_row::{IssueCode, IssueFileRow};
+pub use mark::Mark;
+pub use material::{DensityTable, Material, Thickness, STEEL_DENSITY};
 pub use order::{Order, OrderData};
-pub use plant::Plant;
+pub use plant::{Plant, StorageLocations};
+pub use qty::Qty;
+pub use transfer_row::TransferFileRow;
+pub use uom::{Dimension, Uom, UomRegistry};
 pub use wbs::Wbs;
-
-mod cnf_serde {
-    use serde::{self, Serializer};
-
-    pub fn three_digit_f64<S>(val: &f64, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        serializer.serialize_str(&format!("{:.3}", val))
-    }
-}
-
@@ -1,7 +1,7 @@
 
 use super::{Wbs, Plant};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Order {
     PlannedOrder(OrderData),
     ProductionOrder(OrderData)
@@ -17,11 +17,12 @@ impl Order {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OrderData {
     pub id: u32,
     pub mark: String,
     pub qty: u32,
+    #[serde(deserialize_with="Wbs::deserialize")]
     pub wbs: Wbs,
     pub plant: Plant
 }
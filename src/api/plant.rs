@@ -1,5 +1,9 @@
+use std::io;
+use std::path::Path;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+use crate::paths::{read_pairs, STORAGE_LOCATIONS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub enum Plant {
     /// Lancaster (HS01)
     #[serde(rename = "HS01")]
@@ -9,18 +13,89 @@ pub enum Plant {
     Williamsport
 }
 
-impl From<String> for Plant {
-    fn from(value: String) -> Self {
-        Self::from(value.as_str())
+impl std::fmt::Display for Plant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lancaster    => write!(f, "HS01"),
+            Self::Williamsport => write!(f, "HS02"),
+        }
+    }
+}
+
+impl TryFrom<&str> for Plant {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim() {
+            "HS01" => Ok( Self::Lancaster ),
+            "HS02" => Ok( Self::Williamsport ),
+            _ => Err( anyhow!("unexpected plant `{}`", value) )
+        }
     }
 }
 
-impl From<&str> for Plant {
-    fn from(value: &str) -> Self {
-        match value {
-            "HS01" => Self::Lancaster,
-            "HS02" => Self::Williamsport,
-            _ => panic!("Unexpected plant string <{}>", value)
+/// Plant of each material storage location
+///
+/// Locations are read from [`STORAGE_LOCATIONS`], one `location<TAB>plant` pair per line.
+/// The first location listed for a plant is where material transferred to it is received:
+///
+/// ```text
+/// K2	HS01
+/// W1	HS02
+/// ```
+#[allow(clippy::tabs_in_doc_comments)]
+#[derive(Debug, Clone, Default)]
+pub struct StorageLocations {
+    /// (location, plant), in the order added
+    plants: Vec<(String, Plant)>,
+}
+
+impl StorageLocations {
+    /// Locations in [`STORAGE_LOCATIONS`], if it exists
+    pub fn load_default() -> io::Result<Self> {
+        let mut result = Self::default();
+        if STORAGE_LOCATIONS.exists() {
+            result.load(*STORAGE_LOCATIONS)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Add locations from a tab delimited file of `location<TAB>plant` lines
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        for (loc, plant) in read_pairs(path)? {
+            let plant = Plant::try_from(plant.as_str())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Unknown plant `{}` for location `{}`", plant, loc)))?;
+            self.add(&loc, plant);
+        }
+
+        Ok(())
+    }
+
+    pub fn add(&mut self, loc: &str, plant: Plant) {
+        let loc = loc.trim().to_uppercase();
+
+        match self.plants.iter_mut().find(|(l, _)| *l == loc) {
+            Some((_, p)) => *p = plant,
+            None => self.plants.push( (loc, plant) ),
         }
     }
-}
\ No newline at end of file
+
+    /// Plant of a storage location, if known
+    pub fn plant(&self, loc: &str) -> Option<Plant> {
+        let loc = loc.trim().to_uppercase();
+
+        self.plants
+            .iter()
+            .find(|(l, _)| *l == loc)
+            .map(|(_, p)| *p)
+    }
+
+    /// Location material transferred to a plant is received in, if any are listed for it
+    pub fn receiving_location(&self, plant: Plant) -> Option<&str> {
+        self.plants
+            .iter()
+            .find(|(_, p)| *p == plant)
+            .map(|(l, _)| l.as_str())
+    }
+}
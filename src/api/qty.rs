@@ -0,0 +1,349 @@
+//! Exact decimal quantities
+
+use std::fmt::{self, Display};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+/// Thousandths in one unit
+const SCALE: i64 = 1_000;
+
+/// Exact decimal quantity, to the thousandth
+///
+/// `.ready` files are written with 3 decimal places, so quantities are kept
+/// as whole thousandths. Arithmetic is exact and splitting a quantity
+/// ([`Qty::split`]) always adds back up to the original.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Qty(i64);
+
+impl Qty {
+    pub const ZERO: Self = Self(0);
+
+    /// Quantity from a number of thousandths
+    pub const fn from_milli(milli: i64) -> Self {
+        Self(milli)
+    }
+
+    /// Number of thousandths
+    pub const fn milli(&self) -> i64 {
+        self.0
+    }
+
+    /// Quantity rounded to the nearest thousandth
+    pub fn from_f64(val: f64) -> Self {
+        Self( (val * SCALE as f64).round() as i64 )
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.0 as f64 / SCALE as f64
+    }
+
+    /// Parse a quantity as SAP GUI writes it in exports
+    ///
+    /// `.` and `,` are thousand separators or decimal points, depending on user settings.
+    /// The last separator is the decimal point if it is used once (`1.234`, `1.234,5`, `2,5`),
+    /// otherwise all separators group thousands (`1.234.567`).
+    /// Negative quantities may have a trailing sign (`12.5-`).
+    pub fn parse_sap(text: &str) -> Result<Self, String> {
+        let value: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let (negative, value) = match value.strip_suffix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value.as_str()),
+        };
+
+        let separators: Vec<(usize, char)> = value.char_indices().filter(|(_, c)| matches!(c, '.' | ',')).collect();
+        let decimal = match separators.as_slice() {
+            [] => None,
+            [.., (i, sep)] if separators.iter().filter(|(_, s)| s == sep).count() == 1 => Some(*i),
+            _ => None
+        };
+
+        let normalized: String = value
+            .char_indices()
+            .filter_map(|(i, c)| match c {
+                '.' | ',' if Some(i) == decimal => Some('.'),
+                '.' | ',' => None,
+                c => Some(c),
+            })
+            .collect();
+
+        let qty: Self = normalized.parse().map_err(|_| format!("invalid quantity `{}`", text))?;
+
+        Ok( if negative { -qty } else { qty } )
+    }
+
+    /// Deserialize a quantity written by SAP GUI (see [`Qty::parse_sap`]), or a number
+    ///
+    /// for use with `#[serde(deserialize_with)]` on export columns
+    pub fn deserialize_sap<'de, D>(deserializer: D) -> Result<Self, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        deserializer.deserialize_any(QtyVisitor(Self::parse_sap))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// `self * num / den`, rounded half away from zero to the thousandth
+    ///
+    /// returns zero if `den` is zero
+    pub fn scale(&self, num: u64, den: u64) -> Self {
+        if den == 0 {
+            return Self::ZERO;
+        }
+
+        let product = self.0 as i128 * num as i128;
+        let den = den as i128;
+        let rounded = (product.abs() * 2 + den) / (den * 2);
+
+        Self( (rounded * product.signum()) as i64 )
+    }
+
+    /// Split proportionally to `weights`, so that the parts add up to exactly `self`
+    ///
+    /// each part is rounded down to the thousandth, and the remainder goes to the part
+    /// with the largest weight (the first, if tied). All weights zero splits evenly.
+    pub fn split(&self, weights: &[u64]) -> Vec<Self> {
+        if weights.is_empty() {
+            return Vec::new();
+        }
+
+        let total: u64 = weights.iter().sum();
+        let weights: Vec<u64> = match total {
+            0 => vec![1; weights.len()],
+            _ => weights.to_vec(),
+        };
+        let total = weights.iter().sum::<u64>() as i128;
+
+        let mut parts: Vec<Self> = weights
+            .iter()
+            .map(|&w| Self( (self.0 as i128 * w as i128 / total) as i64 ))
+            .collect();
+
+        let remainder = *self - parts.iter().copied().sum();
+        let largest = weights
+            .iter()
+            .enumerate()
+            .max_by(|(i, a), (j, b)| a.cmp(b).then(j.cmp(i)))
+            .map_or(0, |(i, _)| i);
+        parts[largest] += remainder;
+
+        parts
+    }
+}
+
+impl Display for Qty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+
+        write!(f, "{}{}.{:03}", sign, abs / SCALE as u64, abs % SCALE as u64)
+    }
+}
+
+impl FromStr for Qty {
+    type Err = String;
+
+    /// Parse a decimal number, such as `1001.569` or `-12`
+    ///
+    /// more than 3 decimal places are rounded half away from zero
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        let invalid = || format!("invalid quantity `{}`", s);
+
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+
+        let (whole, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        let is_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && frac.is_empty()) || !is_digits(whole) || !is_digits(frac) {
+            return Err(invalid());
+        }
+
+        let whole: i64 = match whole {
+            "" => 0,
+            _ => whole.parse().map_err(|_| invalid())?,
+        };
+
+        let mut milli = 0;
+        for (i, b) in frac.bytes().take(3).enumerate() {
+            milli += (b - b'0') as i64 * 10i64.pow(2 - i as u32);
+        }
+        if frac.as_bytes().get(3).is_some_and(|&b| b >= b'5') {
+            milli += 1;
+        }
+
+        let val = whole
+            .checked_mul(SCALE)
+            .and_then(|w| w.checked_add(milli))
+            .ok_or_else(invalid)?;
+
+        Ok( Self(if negative { -val } else { val }) )
+    }
+}
+
+impl From<u32> for Qty {
+    fn from(val: u32) -> Self {
+        Self(val as i64 * SCALE)
+    }
+}
+
+impl From<Qty> for f64 {
+    fn from(qty: Qty) -> Self {
+        qty.to_f64()
+    }
+}
+
+impl Add for Qty {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Qty {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Qty {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Qty {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Neg for Qty {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self(-self.0)
+    }
+}
+
+impl Sum for Qty {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl serde::Serialize for Qty {
+    /// serialized as text with 3 decimal places
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Visits text with a parser, and numbers as is
+struct QtyVisitor(fn(&str) -> Result<Qty, String>);
+
+impl<'de> serde::de::Visitor<'de> for QtyVisitor {
+    type Value = Qty;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a quantity")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Qty, E> {
+        (self.0)(v).map_err(E::custom)
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Qty, E> {
+        v.checked_mul(SCALE).map(Qty).ok_or_else(|| E::custom(format!("quantity {} is too large", v)))
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Qty, E> {
+        i64::try_from(v)
+            .map_err(|_| E::custom(format!("quantity {} is too large", v)))
+            .and_then(|v| self.visit_i64(v))
+    }
+
+    fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Qty, E> {
+        Ok( Qty::from_f64(v) )
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Qty {
+    /// deserialized from text (see [`Qty::from_str`]), or a number
+    ///
+    /// text is asked for first, so text formats (.ready files) keep every digit
+    /// instead of going through a float. Numbers are accepted from formats that only have a number.
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: serde::Deserializer<'de>
+    {
+        deserializer.deserialize_str(QtyVisitor(|s| s.parse()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display() {
+        let qty: Qty = "1001.569".parse().unwrap();
+        assert_eq!(qty, Qty::from_milli(1_001_569));
+        assert_eq!(qty.to_string(), "1001.569");
+
+        assert_eq!("-0.5".parse::<Qty>().unwrap().to_string(), "-0.500");
+        assert_eq!("12".parse::<Qty>().unwrap(), Qty::from(12));
+        assert_eq!("2.0005".parse::<Qty>().unwrap(), Qty::from_milli(2_001));
+        assert!("1.2.3".parse::<Qty>().is_err());
+        assert!("".parse::<Qty>().is_err());
+    }
+
+    #[test]
+    fn deserialize_csv_exactly() {
+        #[derive(Deserialize)]
+        struct Row {
+            qty: Qty,
+        }
+
+        let mut reader = csv::ReaderBuilder::new().delimiter(b'\t').from_reader("qty\n1001.569\n2.0005\n12\n".as_bytes());
+        let qtys: Vec<Qty> = reader.deserialize::<Row>().map(|r| r.unwrap().qty).collect();
+
+        assert_eq!(qtys, vec![Qty::from_milli(1_001_569), Qty::from_milli(2_001), Qty::from(12)]);
+    }
+
+    #[test]
+    fn parse_sap_exports() {
+        assert_eq!(Qty::parse_sap("1,234.5").unwrap(), Qty::from_milli(1_234_500));
+        assert_eq!(Qty::parse_sap("1.234,5").unwrap(), Qty::from_milli(1_234_500));
+        assert_eq!(Qty::parse_sap("1.234").unwrap(), Qty::from_milli(1234));
+        assert_eq!(Qty::parse_sap("30,000").unwrap(), Qty::from(30));
+        assert_eq!(Qty::parse_sap("1.234.567").unwrap(), Qty::from(1_234_567));
+        assert_eq!(Qty::parse_sap("1.234,000").unwrap(), Qty::from(1234));
+        assert_eq!(Qty::parse_sap(" 12.5-").unwrap(), Qty::from_milli(-12_500));
+        assert!(Qty::parse_sap("abc").is_err());
+        assert!(Qty::parse_sap("").is_err());
+    }
+
+    #[test]
+    fn split_adds_up() {
+        let total: Qty = "1001.569".parse().unwrap();
+
+        let parts = total.split(&[1, 1, 1]);
+        assert_eq!(parts.iter().copied().sum::<Qty>(), total);
+        assert_eq!(parts, vec![Qty::from_milli(333_857), Qty::from_milli(333_856), Qty::from_milli(333_856)]);
+
+        let parts = total.split(&[2, 5]);
+        assert_eq!(parts.iter().copied().sum::<Qty>(), total);
+        assert_eq!(parts[0], Qty::from_milli(286_162));
+
+        assert_eq!(total.scale(2, 7), Qty::from_milli(286_163));
+    }
+}
@@ -0,0 +1,47 @@
+use std::ops::AddAssign;
+
+use super::{Material, Plant, Qty, Uom, Wbs};
+
+/// Transfer file row (plant to plant stock transfer, SAP MIGO 301)
+///
+/// Material consumed in one plant from a storage location in another plant is
+/// transferred first, so that the consumption does not fail into COGI.
+///
+/// tab delimited row in the format
+/// ```tsv
+/// {material master}	{material wbs}	{material qty}	{material UoM}	{from plant}	{from location}	{to plant}	{to location}	{program}
+/// ```
+#[allow(clippy::tabs_in_doc_comments)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all="PascalCase")]
+pub struct TransferFileRow {
+    /// Material master
+    #[serde(deserialize_with="Material::deserialize")]
+    pub matl:      Material,
+    /// Material WBS Element
+    #[serde(deserialize_with="Wbs::deserialize")]
+    pub matl_wbs:  Wbs,
+    /// Material quantity
+    pub matl_qty:  Qty,
+    /// Material unit of measure
+    #[serde(deserialize_with="Uom::deserialize")]
+    pub matl_uom:  Uom,
+
+    /// Plant the material is stored in
+    pub from_plant: Plant,
+    /// Storage location the material is taken from
+    pub from_loc:  Option<String>,
+    /// Plant the material is consumed in
+    pub to_plant:  Plant,
+    /// Storage location the material is received in
+    pub to_loc:    Option<String>,
+
+    /// Program number
+    pub program:   String
+}
+
+impl AddAssign for TransferFileRow {
+    fn add_assign(&mut self, rhs: Self) {
+        self.matl_qty += rhs.matl_qty;
+    }
+}
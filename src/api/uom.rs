@@ -0,0 +1,263 @@
+//! Units of measure and conversion between them
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::io;
+use std::path::Path;
+
+use serde::{Deserializer, Serialize};
+
+use crate::paths::{read_pairs, MATERIAL_UOMS};
+use super::{DensityTable, Material, Qty};
+
+/// Unit of measure
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Uom {
+    /// Each
+    Ea,
+    /// Square inches
+    In2,
+    /// Square feet
+    Ft2,
+    /// Inches
+    In,
+    /// Feet
+    Ft,
+    /// Pounds
+    Lb,
+    /// Unrecognized unit
+    Other(String),
+}
+
+/// What a [`Uom`] measures
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dimension {
+    Count,
+    Area,
+    Length,
+    Weight,
+    Unknown,
+}
+
+impl Uom {
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            Self::Ea              => Dimension::Count,
+            Self::In2 | Self::Ft2 => Dimension::Area,
+            Self::In | Self::Ft   => Dimension::Length,
+            Self::Lb              => Dimension::Weight,
+            Self::Other(_)        => Dimension::Unknown,
+        }
+    }
+
+    /// Size of the unit, in the base unit of its dimension (EA, IN2, IN or LB)
+    fn base_factor(&self) -> Option<f64> {
+        match self {
+            Self::Ea | Self::In2 | Self::In | Self::Lb => Some(1.),
+            Self::Ft2 => Some(144.),
+            Self::Ft  => Some(12.),
+            Self::Other(_) => None,
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Uom, D::Error>
+        where D: Deserializer<'de>
+    {
+        let s: String = serde::de::Deserialize::deserialize(deserializer)?;
+        Ok( Uom::from(s.as_str()) )
+    }
+}
+
+impl From<&str> for Uom {
+    fn from(value: &str) -> Self {
+        match value.trim().to_uppercase().as_str() {
+            "EA"  => Self::Ea,
+            "IN2" => Self::In2,
+            "FT2" => Self::Ft2,
+            "IN"  => Self::In,
+            "FT"  => Self::Ft,
+            "LB"  => Self::Lb,
+            _ => Self::Other(value.into())
+        }
+    }
+}
+
+impl Display for Uom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ea       => write!(f, "EA"),
+            Self::In2      => write!(f, "IN2"),
+            Self::Ft2      => write!(f, "FT2"),
+            Self::In       => write!(f, "IN"),
+            Self::Ft       => write!(f, "FT"),
+            Self::Lb       => write!(f, "LB"),
+            Self::Other(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl Serialize for Uom {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// Unit conversions, and the unit SAP stocks each material in
+///
+/// Units of the same [`Dimension`] convert by fixed factors. Area and weight
+/// convert through the plate weight of a raw [`Material`] (see [`DensityTable`]).
+///
+/// Stocking units can be set in [`MATERIAL_UOMS`], one `material<TAB>uom` pair per line.
+/// The material can be a full material master or a grade:
+///
+/// ```text
+/// # stocked by weight
+/// HPS70W	LB
+/// 1001	EA
+/// ```
+#[allow(clippy::tabs_in_doc_comments)]
+#[derive(Debug, Clone, Default)]
+pub struct UomRegistry {
+    densities: DensityTable,
+    stock_uoms: HashMap<String, Uom>,
+}
+
+impl UomRegistry {
+    pub fn new(densities: DensityTable) -> Self {
+        Self { densities, stock_uoms: HashMap::new() }
+    }
+
+    /// Default densities and stocking units, plus those in [`MATERIAL_UOMS`] if it exists
+    pub fn load_default() -> io::Result<Self> {
+        let mut result = Self::new( DensityTable::load_default()? );
+        if MATERIAL_UOMS.exists() {
+            result.load(*MATERIAL_UOMS)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Add stocking units from a tab delimited file of `material<TAB>uom` lines
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        for (matl, uom) in read_pairs(path)? {
+            let uom = Uom::from(uom.as_str());
+            if let Uom::Other(uom) = uom {
+                return Err( io::Error::new(io::ErrorKind::InvalidData, format!("Unknown unit `{}` for material `{}`", uom, matl)) );
+            }
+            self.set_stock_uom(&matl, uom);
+        }
+
+        Ok(())
+    }
+
+    pub fn set_stock_uom(&mut self, matl: &str, uom: Uom) {
+        self.stock_uoms.insert(matl.trim().to_uppercase(), uom);
+    }
+
+    /// Unit SAP stocks a material in, by material master and then by grade
+    ///
+    /// returns `None` if not set, in which case rows keep their unit
+    pub fn stock_uom(&self, matl: &Material) -> Option<&Uom> {
+        self.stock_uoms
+            .get(&matl.to_string().to_uppercase())
+            .or_else(|| matl.grade().and_then(|grade| self.stock_uoms.get(&grade.to_uppercase())))
+    }
+
+    pub fn densities(&self) -> &DensityTable {
+        &self.densities
+    }
+
+    /// Convert `qty` of `matl` between units
+    pub fn convert(&self, qty: Qty, from: &Uom, to: &Uom, matl: &Material) -> Result<Qty, String> {
+        if from == to {
+            return Ok(qty);
+        }
+
+        let cannot_convert = || format!("Cannot convert {} from {} to {}", matl, from, to);
+        let (from_factor, to_factor) = match (from.base_factor(), to.base_factor()) {
+            (Some(f), Some(t)) => (f, t),
+            _ => return Err(cannot_convert())
+        };
+
+        // weight of 1 IN2 of the material
+        let lb_per_in2 = || matl
+            .weight(Qty::from(1), &self.densities)
+            .filter(|w| *w > 0.)
+            .ok_or_else(cannot_convert);
+
+        let base = qty.to_f64() * from_factor;
+        let base = match (from.dimension(), to.dimension()) {
+            (a, b) if a == b => base,
+            (Dimension::Area, Dimension::Weight) => base * lb_per_in2()?,
+            (Dimension::Weight, Dimension::Area) => base / lb_per_in2()?,
+            _ => return Err(cannot_convert())
+        };
+
+        Ok( Qty::from_f64(base / to_factor) )
+    }
+
+    /// Check that a quantity and unit belong together for a material
+    ///
+    /// - the unit must be known
+    /// - counts must be whole
+    /// - raw material must be measured by area or weight, and purchased parts by count
+    pub fn validate(&self, qty: Qty, uom: &Uom, matl: &Material) -> Result<(), String> {
+        let dimension = uom.dimension();
+
+        if dimension == Dimension::Unknown {
+            return Err( format!("Unknown unit of measure `{}` for {}", uom, matl) );
+        }
+
+        if dimension == Dimension::Count && qty.milli() % Qty::from(1).milli() != 0 {
+            return Err( format!("{} {} of {} is not a whole count", qty, uom, matl) );
+        }
+
+        match matl {
+            Material::Raw { .. } if !matches!(dimension, Dimension::Area | Dimension::Weight) =>
+                Err( format!("Raw material {} cannot be measured in {}", matl, uom) ),
+            Material::Purchased(_) if dimension != Dimension::Count =>
+                Err( format!("Purchased part {} must be measured in EA, not {}", matl, uom) ),
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_units() {
+        let registry = UomRegistry::default();
+        let plate = Material::from("50W-0008");
+
+        let ft2 = registry.convert(Qty::from(288), &Uom::In2, &Uom::Ft2, &plate).unwrap();
+        assert_eq!(ft2, Qty::from(2));
+
+        // 1/2" steel, 100 in²
+        let lb = registry.convert(Qty::from(100), &Uom::In2, &Uom::Lb, &plate).unwrap();
+        assert_eq!(lb.to_string(), "14.180");
+        assert_eq!(registry.convert(lb, &Uom::Lb, &Uom::In2, &plate).unwrap(), Qty::from(100));
+
+        assert!(registry.convert(Qty::from(1), &Uom::Ea, &Uom::In2, &plate).is_err());
+        assert!(registry.convert(Qty::from(1), &Uom::In2, &Uom::Lb, &Material::from("1001")).is_err());
+    }
+
+    #[test]
+    fn validate_units() {
+        let mut registry = UomRegistry::default();
+        registry.set_stock_uom("hps70w", Uom::Lb);
+
+        let plate = Material::from("50W-0008");
+        assert!(registry.validate(Qty::from(10), &Uom::In2, &plate).is_ok());
+        assert!(registry.validate(Qty::from(10), &Uom::Ea, &plate).is_err());
+        assert!(registry.validate(Qty::from_milli(1_500), &Uom::Ea, &Material::from("1001")).is_err());
+        assert!(registry.validate(Qty::from(1), &Uom::from("BOX"), &plate).is_err());
+
+        assert_eq!(Uom::from("in2"), Uom::In2);
+        assert_eq!(registry.stock_uom(&Material::from("HPS70W-0024")), Some(&Uom::Lb));
+        assert_eq!(registry.stock_uom(&plate), None);
+    }
+}
@@ -4,7 +4,8 @@ use regex::Regex;
 use serde::{Deserializer, de::Error, Serialize};
 
 lazy_static! {
-    static ref COST_CENTER_WBS: Regex = Regex::new(r"S-.*-2-(2\d{3})").expect("Failed to build COST_CENTER_WBS regex");
+    // any 4 digit cost center parses, so ones outside Wbs::COST_CENTERS can be reported
+    static ref COST_CENTER_WBS: Regex = Regex::new(r"S-.*-2-(\d{4})").expect("Failed to build COST_CENTER_WBS regex");
     static ref HD_WBS: Regex = Regex::new(r"D-(\d{7})-(\d{5})").expect("Failed to build HD_WBS regex");
     static ref LEGACY_WBS: Regex = Regex::new(r"S-(\d{7})-2-(\d{2})").expect("Failed to build LEGACY_WBS regex");
 }
@@ -18,6 +19,9 @@ pub enum Wbs {
 }
 
 impl Wbs {
+    /// Cost centers material can be issued to
+    pub const COST_CENTERS: std::ops::Range<u32> = 2000..3000;
+
     pub fn set_id(mut self, new_id: u32) {
         match self {
             Self::Hd { job: _, ref mut id } => *id = new_id,
@@ -42,8 +46,8 @@ impl Wbs {
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Wbs, D::Error>
         where D: Deserializer<'de>
     {
-        let s: &str = serde::de::Deserialize::deserialize(deserializer)?;
-        Wbs::try_from(s).map_err(D::Error::custom)
+        let s: String = serde::de::Deserialize::deserialize(deserializer)?;
+        Wbs::try_from(s.as_str()).map_err(D::Error::custom)
     }
 }
 
@@ -91,7 +95,7 @@ impl TryFrom<&str> for Wbs {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if value == "" {
+        if value.is_empty() {
             return Ok(Self::None);
         }
 
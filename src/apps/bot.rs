@@ -0,0 +1,664 @@
+//! Unattended inbox-processing daemon
+//!
+//! Watches a drop folder for inbox error exports (`*.txt`) and COHV exports (workbooks, `|` list downloads, tab delimited text or CSV).
+//! Each poll, all pending inbox error exports are compared against the newest COHV
+//! export and the generated files are written to a staging folder.
+//! Inputs are then moved to `processed` (or `failed`, if the run errored) before any generated file is moved to outbound.
+//!
+//! Inbox error exports whose name starts with `issue` are issued instead of compared.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local};
+
+use crate::excel::SPREADSHEET_EXTENSIONS;
+use crate::inbox::{Failure, FailureMatchStatus};
+use crate::inbox::consolidate::Consolidation;
+use crate::inbox::validation::Findings;
+use crate::inbox::parsers::{is_cohv_text, COHV_EXPORT_EXTENSIONS};
+use crate::inbox::workflow::{ArchiveSource, CohvSource, Generated, InboxWorkflow, WorkflowOptions};
+use crate::history::RunFile;
+use crate::paths::timestamped_file;
+use crate::report::RunReport;
+
+const LOCK_FILE: &str = "bot.lock";
+const PROCESSED_FOLDER: &str = "processed";
+const FAILED_FOLDER: &str = "failed";
+
+/// Minimum age of a file before it is picked up, so files still being exported are skipped
+const SETTLE_TIME: Duration = Duration::from_secs(5);
+/// Granularity of checking for shutdown while waiting for the next poll
+const SHUTDOWN_CHECK: Duration = Duration::from_millis(250);
+
+fn is_spreadsheet_ext(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| SPREADSHEET_EXTENSIONS.iter().any(|ext| e.eq_ignore_ascii_case(ext)))
+}
+
+fn log(msg: impl AsRef<str>) {
+    println!("[{}] {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), msg.as_ref());
+}
+
+/// Bot configuration
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    /// Folder watched for inbox error and COHV exports
+    pub drop_folder: PathBuf,
+    /// Folder generated files and reports are written to
+    pub staging: PathBuf,
+    /// Time between polls of the drop folder
+    pub poll_interval: Duration,
+    /// Number of archived production files to search for confirmation rows
+    pub files_to_parse: usize,
+    /// Move generated files to outbound if every failure matched with no ambiguity
+    pub auto_move_files: bool,
+    /// Run history database
+    pub history: PathBuf,
+    /// How generated rows are merged and split into files
+    pub consolidation: Consolidation,
+}
+
+/// Lock file held for the lifetime of the bot, to prevent multiple instances
+///
+/// lock file is removed when dropped
+#[derive(Debug)]
+struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    fn acquire(path: PathBuf) -> anyhow::Result<Self> {
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err( anyhow!("Another instance is running (lock file `{}` exists). If not, delete the lock file.", path.display()) );
+            },
+            Err(e) => return Err( anyhow!("Failed to create lock file `{}`: {}", path.display(), e) )
+        };
+
+        writeln!(file, "{}", std::process::id())?;
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Results of a single poll of the drop folder
+#[derive(Debug, Default)]
+struct RunSummary {
+    inbox_files: Vec<PathBuf>,
+    cohv_file: Option<PathBuf>,
+
+    complete: usize,
+    no_cnf_row: usize,
+    not_enough_orders: usize,
+    parse_errors: Vec<String>,
+    ambiguous_marks: Vec<String>,
+    not_matched: Vec<String>,
+    /// Mark/WBS job mismatches and COHV export freshness warnings
+    warnings: Vec<String>,
+    /// Validation of the generated production rows
+    findings: Findings,
+    /// Plant to plant transfer rows generated
+    transfers: usize,
+    /// Failures output could not be generated for
+    errors: Vec<String>,
+    /// Error that stopped the run, its inputs are moved to the failed folder
+    failed: Option<String>,
+
+    generated: Vec<PathBuf>,
+    /// Generated files moved to outbound, at their destination
+    moved: Vec<PathBuf>,
+}
+
+impl RunSummary {
+    fn tally(&mut self, inbox: &[Failure]) {
+        for f in inbox {
+            match f.status() {
+                FailureMatchStatus::MatchComplete => self.complete += 1,
+                FailureMatchStatus::NoConfirmationRow => self.no_cnf_row += 1,
+                FailureMatchStatus::NotEnoughOrdersApplied(_) => self.not_enough_orders += 1,
+            }
+        }
+
+        self.not_matched.extend( inbox.iter().filter_map(Failure::new_inbox_text) );
+    }
+
+    /// Add the files written and failures not generated by a run
+    fn add_generated(&mut self, generated: &Generated) {
+        generated.errors.iter().for_each(|e| log( format!("Output not generated: {}", e) ));
+        self.errors.extend(generated.errors.iter().cloned());
+
+        self.generated.extend(generated.new_inbox_file.clone());
+        self.generated.extend(generated.files.iter().cloned());
+        self.generated.push(generated.report_file.clone());
+    }
+
+    /// every failure matched and no orders could have been allocated differently
+    fn is_unambiguous(&self) -> bool {
+        self.no_cnf_row == 0
+            && self.not_enough_orders == 0
+            && self.parse_errors.is_empty()
+            && self.errors.is_empty()
+            && self.ambiguous_marks.is_empty()
+            && self.warnings.is_empty()
+            && !self.findings.has_errors()
+    }
+
+    /// every failure is issued, so only missing confirmation rows and errors make an issue run ambiguous
+    fn is_issue_unambiguous(&self) -> bool {
+        self.no_cnf_row == 0
+            && self.parse_errors.is_empty()
+            && self.errors.is_empty()
+    }
+
+    fn report(&self) -> String {
+        let mut lines = vec![
+            format!("Inbox files: {}", self.inbox_files.len()),
+        ];
+        lines.extend( self.inbox_files.iter().map(|f| format!("\t{}", f.display())) );
+        if let Some(cohv) = &self.cohv_file {
+            lines.push( format!("COHV file: {}", cohv.display()) );
+        }
+        if let Some(e) = &self.failed {
+            lines.push( format!("Run failed, inputs moved to {}: {}", FAILED_FOLDER, e) );
+        }
+
+        lines.push( String::new() );
+        lines.push( format!("Matched:                 {}", self.complete) );
+        lines.push( format!("No confirmation row:     {}", self.no_cnf_row) );
+        lines.push( format!("Not enough orders:       {}", self.not_enough_orders) );
+        lines.push( format!("Lines failed to parse:   {}", self.parse_errors.len()) );
+        if self.transfers > 0 {
+            lines.push( format!("Plant transfers:         {}", self.transfers) );
+        }
+
+        if !self.warnings.is_empty() {
+            lines.push( String::new() );
+            lines.push( "Warnings:".into() );
+            lines.extend( self.warnings.iter().map(|w| format!("\t{}", w)) );
+        }
+
+        for (severity, findings) in self.findings.by_severity() {
+            lines.push( String::new() );
+            lines.push( format!("Validation {}s:", severity.to_string().to_lowercase()) );
+            lines.extend( findings.iter().map(|f| format!("\t[{}] {}", f.rule, f)) );
+        }
+        if self.findings.has_errors() {
+            lines.push( "\tProduction and transfer files not written".into() );
+        }
+
+        if !self.ambiguous_marks.is_empty() {
+            lines.push( String::new() );
+            lines.push( "Marks with multiple failures (orders allocated in sorted order):".into() );
+            lines.extend( self.ambiguous_marks.iter().map(|m| format!("\t{}", m)) );
+        }
+
+        if !self.parse_errors.is_empty() {
+            lines.push( String::new() );
+            lines.push( "Parse errors:".into() );
+            lines.extend( self.parse_errors.iter().map(|e| format!("\t{}", e)) );
+        }
+
+        if !self.errors.is_empty() {
+            lines.push( String::new() );
+            lines.push( "Output not generated:".into() );
+            lines.extend( self.errors.iter().map(|e| format!("\t{}", e)) );
+        }
+
+        if !self.not_matched.is_empty() {
+            lines.push( String::new() );
+            lines.push( "Not matched:".into() );
+            lines.extend( self.not_matched.iter().map(|e| format!("\t{}", e)) );
+        }
+
+        lines.push( String::new() );
+        lines.push( "Generated files:".into() );
+        lines.extend( self.generated.iter().map(|f| format!("\t{}", f.display())) );
+        lines.push( format!("Moved to outbound: {}", if self.moved.is_empty() { "no" } else { "yes" }) );
+
+        lines.join("\r\n")
+    }
+}
+
+/// Unattended inbox-processing daemon
+#[derive(Debug)]
+pub struct InboxBot {
+    config: BotConfig,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl InboxBot {
+    pub fn new(config: BotConfig) -> Self {
+        Self {
+            config,
+            shutdown: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Flag that, when set, stops the bot after the current poll finishes
+    pub fn shutdown_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.shutdown)
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Run the bot until shutdown is requested
+    pub fn run(&self) -> anyhow::Result<()> {
+        if !self.config.drop_folder.is_dir() {
+            return Err( anyhow!("Drop folder `{}` does not exist", self.config.drop_folder.display()) );
+        }
+
+        let _lock = LockFile::acquire(self.config.drop_folder.join(LOCK_FILE))?;
+
+        fs::create_dir_all(&self.config.staging)?;
+        fs::create_dir_all(self.config.drop_folder.join(PROCESSED_FOLDER))?;
+        fs::create_dir_all(self.config.drop_folder.join(FAILED_FOLDER))?;
+
+        log( format!("Watching {}", self.config.drop_folder.display()) );
+
+        while !self.is_shutdown() {
+            if let Err(e) = self.poll() {
+                log( format!("Error: {}", e) );
+            }
+
+            self.wait();
+        }
+
+        log("Shutting down");
+
+        Ok(())
+    }
+
+    fn wait(&self) {
+        let start = SystemTime::now();
+
+        while !self.is_shutdown() {
+            match start.elapsed() {
+                Ok(elapsed) if elapsed >= self.config.poll_interval => break,
+                _ => std::thread::sleep(SHUTDOWN_CHECK)
+            }
+        }
+    }
+
+    /// Files in the drop folder with the given extension, that are not still being written
+    fn settled_files(&self, exts: &[&str]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(&self.config.drop_folder)?.filter_map(Result::ok) {
+            let path = entry.path();
+            let is_ext = path.extension()
+                .and_then(|e| e.to_str())
+                .map(|e| exts.iter().any(|ext| e.eq_ignore_ascii_case(ext)))
+                .unwrap_or(false);
+
+            if !is_ext || !path.is_file() {
+                continue;
+            }
+
+            let settled = entry.metadata()
+                .and_then(|m| m.modified())
+                .map(|t| t.elapsed().unwrap_or_default() >= SETTLE_TIME)
+                .unwrap_or(false);
+
+            if settled {
+                files.push(path);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Settled COHV exports in the drop folder, newest first
+    fn cohv_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files: Vec<(SystemTime, PathBuf)> = self.settled_files(&COHV_EXPORT_EXTENSIONS)?
+            .into_iter()
+            .filter(|f| is_spreadsheet_ext(f) || is_cohv_text(f))
+            .filter_map(|f| {
+                fs::metadata(&f)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .map(|t| (t, f))
+            })
+            .collect();
+
+        files.sort_by_key(|(t, _)| std::cmp::Reverse(*t));
+
+        Ok( files.into_iter().map(|(_, f)| f).collect() )
+    }
+
+    /// Process the settled inbox files, comparisons first
+    ///
+    /// a failed comparison is logged, so issue files are still processed
+    fn poll(&self) -> anyhow::Result<()> {
+        let (issue_files, cnf_files): (Vec<_>, Vec<_>) = self.settled_files(&["txt"])?
+            .into_iter()
+            .filter(|f| !is_cohv_text(f))
+            .partition(|f| {
+                f.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|n| n.to_lowercase().starts_with("issue"))
+                    .unwrap_or(false)
+            });
+
+        if !cnf_files.is_empty() {
+            let mut exports = self.cohv_files()?;
+            match exports.is_empty() {
+                true  => log( format!("{} inbox file(s) waiting for a COHV export", cnf_files.len()) ),
+                false => {
+                    let cohv = exports.remove(0);
+                    if let Err(e) = self.process_comparison(cnf_files, cohv, exports) {
+                        log( format!("Comparison failed: {}", e) );
+                    }
+                },
+            }
+        }
+
+        if !issue_files.is_empty() {
+            self.process_issue(issue_files)?;
+        }
+
+        Ok(())
+    }
+
+    /// Workflow for the inbox errors in `files`, with the lines parsed and confirmation rows matched
+    fn start_workflow(&self, files: &[PathBuf], cohv: CohvSource, summary: &mut RunSummary) -> anyhow::Result<InboxWorkflow> {
+        let mut text = String::new();
+        for file in files {
+            text.push_str( &fs::read_to_string(file)? );
+            text.push('\n');
+        }
+
+        // inbox files are exported after the inbox is current, so the newest one dates the list
+        let inbox_updated = files.iter()
+            .filter_map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+            .max()
+            .map(DateTime::<Local>::from);
+
+        let mut workflow = InboxWorkflow::new(text)
+            .archive( ArchiveSource::SapArchive(self.config.files_to_parse) )
+            .cohv(cohv)
+            .options(WorkflowOptions {
+                output_dir: self.config.staging.clone(),
+                inbox_updated,
+                history: Some( self.config.history.clone() ),
+                consolidation: self.config.consolidation.clone(),
+            });
+        files.iter().for_each(|f| workflow.add_file( RunFile::input(f) ));
+
+        summary.inbox_files = files.to_vec();
+        let parsed = workflow.parse()?;
+        parsed.warnings.iter().for_each(|w| log( format!("Warning: {}", w) ));
+        summary.parse_errors = parsed.errors;
+        summary.warnings = parsed.warnings;
+        workflow.match_confirmations(&mut ())?;
+
+        Ok(workflow)
+    }
+
+    /// Compare inbox files against the newest COHV export
+    ///
+    /// older exports are moved to processed, so a later inbox drop is not compared against them
+    fn process_comparison(&self, files: Vec<PathBuf>, cohv: PathBuf, superseded: Vec<PathBuf>) -> anyhow::Result<()> {
+        log( format!("Comparing {} inbox file(s) against {}", files.len(), cohv.display()) );
+
+        self.move_inputs(&superseded, PROCESSED_FOLDER)?;
+        superseded.iter().for_each(|f| log( format!("Moved superseded COHV export {} to {}", f.display(), PROCESSED_FOLDER) ));
+
+        let mut summary = RunSummary::default();
+        let run = self.compare(&files, cohv.clone(), &mut summary);
+
+        let inputs: Vec<PathBuf> = files.into_iter().chain(Some(cohv)).collect();
+        self.finish(summary, &inputs, run)
+    }
+
+    /// Compare inbox errors against a COHV export, returning the workflow and whether its files can be moved to outbound
+    fn compare(&self, files: &[PathBuf], cohv: PathBuf, summary: &mut RunSummary) -> anyhow::Result<(InboxWorkflow, bool)> {
+        summary.cohv_file = Some(cohv.clone());
+        let mut workflow = self.start_workflow(files, CohvSource::File(cohv), summary)?;
+
+        let loaded = workflow.load_orders()?;
+        if let Some(info) = &loaded.export {
+            log( format!("Read COHV export {}", info) );
+        }
+        loaded.warnings.iter().for_each(|w| log( format!("Warning: {}", w) ));
+        summary.warnings.extend(loaded.warnings);
+
+        workflow.apply_orders(&mut ())?;
+        summary.tally(workflow.failures());
+
+        let mut marks: Vec<&String> = workflow.failures().iter().map(|f| &f.mark).collect();
+        marks.sort();
+        summary.ambiguous_marks = marks
+            .windows(2)
+            .filter(|w| w[0] == w[1])
+            .map(|w| w[0].clone())
+            .collect();
+        summary.ambiguous_marks.dedup();
+
+        let generated = workflow.write_production()?;
+        generated.findings.lines().into_iter().for_each(log);
+        summary.findings = generated.findings.clone();
+        // includes matched failures, if validation errors stopped their rows being written
+        summary.not_matched = generated.new_inbox.clone();
+        summary.transfers = generated.report.transfers.len();
+        summary.add_generated(&generated);
+
+        summary.generated.push( self.write_report(&generated.report, &mut workflow)? );
+
+        Ok(( workflow, summary.is_unambiguous() ))
+    }
+
+    fn process_issue(&self, files: Vec<PathBuf>) -> anyhow::Result<()> {
+        log( format!("Issuing {} inbox file(s)", files.len()) );
+
+        let mut summary = RunSummary::default();
+        let run = self.issue(&files, &mut summary);
+
+        self.finish(summary, &files, run)
+    }
+
+    /// Issue inbox errors, returning the workflow and whether its files can be moved to outbound
+    fn issue(&self, files: &[PathBuf], summary: &mut RunSummary) -> anyhow::Result<(InboxWorkflow, bool)> {
+        let mut workflow = self.start_workflow(files, CohvSource::Orders(Vec::new()), summary)?;
+        summary.tally(workflow.failures());
+
+        let generated = workflow.write_issue()?;
+        summary.add_generated(&generated);
+
+        summary.generated.push( self.write_report(&generated.report, &mut workflow)? );
+
+        Ok(( workflow, summary.is_issue_unambiguous() ))
+    }
+
+    /// Write the Excel report of a run to staging
+    fn write_report(&self, report: &RunReport, workflow: &mut InboxWorkflow) -> anyhow::Result<PathBuf> {
+        let path = self.config.staging.join( timestamped_file("Report", "xlsx") );
+        report.write_xlsx(&path)?;
+        workflow.add_file( RunFile::output(&path) );
+
+        Ok(path)
+    }
+
+    /// Record a run in the history database
+    ///
+    /// a failure is logged, since the generated files are already written
+    fn record_history(&self, workflow: &InboxWorkflow) {
+        if let Err(e) = workflow.record_history() {
+            log( format!("Failed to record run history: {}", e) );
+        }
+    }
+
+    /// Move a run's inputs out of the drop folder, so they are not picked up again
+    fn move_inputs(&self, inputs: &[PathBuf], folder: &str) -> anyhow::Result<()> {
+        let to = self.config.drop_folder.join(folder);
+        for input in inputs {
+            // safe to unwrap: inputs are files read from the drop folder
+            fs::rename(input, to.join(input.file_name().unwrap()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the inputs to processed (or failed), then move generated files to outbound and write the summary report
+    ///
+    /// generated files are moved last, so nothing reaches outbound unless its inputs are out of the drop folder
+    fn finish(&self, mut summary: RunSummary, inputs: &[PathBuf], run: anyhow::Result<(InboxWorkflow, bool)>) -> anyhow::Result<()> {
+        let report = self.config.staging.join( timestamped_file("Summary", "txt") );
+
+        let (mut workflow, move_files) = match run {
+            Ok(run) => run,
+            Err(e) => {
+                summary.failed = Some(e.to_string());
+                fs::write(&report, summary.report())?;
+                self.move_inputs(inputs, FAILED_FOLDER)?;
+
+                return Err(e);
+            }
+        };
+
+        self.move_inputs(inputs, PROCESSED_FOLDER)?;
+
+        if self.config.auto_move_files && move_files {
+            match workflow.move_generated() {
+                Ok(moved) => {
+                    moved.iter().for_each(|to| log( format!("Moved file {}", to.display()) ));
+                    summary.moved = moved;
+                },
+                Err(e) => log( format!("Failed to move generated files to outbound: {}", e) )
+            }
+        }
+
+        self.record_history(&workflow);
+        fs::write(&report, summary.report())?;
+
+        log( format!(
+            "{} matched, {} not matched, report written to {}",
+            summary.complete,
+            summary.not_matched.len(),
+            report.display()
+        ) );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn lock_file_prevents_second_instance() {
+        let path = std::env::temp_dir().join( format!("sap-error-utils-{}.lock", std::process::id()) );
+
+        let lock = LockFile::acquire(path.clone()).unwrap();
+        assert!(LockFile::acquire(path.clone()).is_err());
+
+        drop(lock);
+        assert!(!path.exists());
+
+        let _ = File::create(&path);
+        assert!(LockFile::acquire(path.clone()).is_err());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn failed_run_moves_inputs_out_of_drop_folder() {
+        let root = std::env::temp_dir().join( format!("sap-error-utils-bot-{}", std::process::id()) );
+        let drop_folder = root.join("drop");
+        let staging = root.join("staging");
+        for dir in [drop_folder.join(FAILED_FOLDER), drop_folder.join(PROCESSED_FOLDER), staging.clone()] {
+            fs::create_dir_all(dir).unwrap();
+        }
+
+        // an empty inbox export fails to parse
+        let input = drop_folder.join("inbox.txt");
+        File::create(&input).unwrap();
+
+        let bot = InboxBot::new(BotConfig {
+            drop_folder: drop_folder.clone(),
+            staging: staging.clone(),
+            poll_interval: Duration::from_secs(60),
+            files_to_parse: 0,
+            auto_move_files: true,
+            history: root.join("history.db"),
+            consolidation: Consolidation::default(),
+        });
+
+        let mut summary = RunSummary::default();
+        let inputs = vec![input.clone()];
+        let run = bot.issue(&inputs, &mut summary);
+        assert!(run.is_err());
+        assert!(bot.finish(summary, &inputs, run).is_err());
+
+        assert!(!input.exists());
+        assert!(drop_folder.join(FAILED_FOLDER).join("inbox.txt").exists());
+        let report = fs::read_dir(&staging).unwrap().next().unwrap().unwrap().path();
+        assert!(fs::read_to_string(report).unwrap().contains("Run failed"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn poll_moves_superseded_exports_and_continues_after_failed_comparison() {
+        let root = std::env::temp_dir().join( format!("sap-error-utils-bot-poll-{}", std::process::id()) );
+        let drop_folder = root.join("drop");
+        let staging = root.join("staging");
+        for dir in [drop_folder.join(FAILED_FOLDER), drop_folder.join(PROCESSED_FOLDER), staging.clone()] {
+            fs::create_dir_all(dir).unwrap();
+        }
+
+        // empty inbox and issue files fail to parse
+        let header = "Order Type\tOrder\tMaterial\tTarget qty\tWBS Element\tPlant\n";
+        let files = [("old_cohv.txt", header, 7200), ("cohv.txt", header, 3600), ("inbox.txt", "", 60), ("issue.txt", "", 60)];
+        for (name, text, age) in files {
+            let path = drop_folder.join(name);
+            fs::write(&path, text).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(age);
+            File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        }
+
+        let bot = InboxBot::new(BotConfig {
+            drop_folder: drop_folder.clone(),
+            staging,
+            poll_interval: Duration::from_secs(60),
+            files_to_parse: 0,
+            auto_move_files: true,
+            history: root.join("history.db"),
+            consolidation: Consolidation::default(),
+        });
+
+        assert!(bot.poll().is_err());
+        assert!(drop_folder.join(PROCESSED_FOLDER).join("old_cohv.txt").exists());
+        for name in ["cohv.txt", "inbox.txt", "issue.txt"] {
+            assert!(drop_folder.join(FAILED_FOLDER).join(name).exists(), "{} not moved to failed", name);
+        }
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn generation_errors_block_auto_move() {
+        let mut summary = RunSummary::default();
+        assert!(summary.is_issue_unambiguous());
+
+        summary.errors.push("Failed to convert 1210123A-X1A to stock unit".into());
+        assert!(!summary.is_issue_unambiguous());
+        assert!(!summary.is_unambiguous());
+        assert!(summary.report().contains("Output not generated:"));
+    }
+}
@@ -0,0 +1,687 @@
+//! Sortable, filterable table of parsed failures
+
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+use eframe::egui::{self, Color32, RichText};
+use egui_extras::{Column, TableBuilder};
+
+use crate::api::{OrderData, Plant, Wbs};
+use crate::inbox::{Failure, FailureMatchStatus};
+
+const ROW_HEIGHT: f32 = 18.;
+
+/// Table column, used for sorting
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum GridColumn {
+    #[default]
+    Mark,
+    Wbs,
+    Program,
+    Qty,
+    Remaining,
+    Status,
+    File,
+    Orders,
+}
+
+impl GridColumn {
+    const ALL: [Self; 8] = [
+        Self::Mark, Self::Wbs, Self::Program, Self::Qty,
+        Self::Remaining, Self::Status, Self::File, Self::Orders,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Mark      => "Mark",
+            Self::Wbs       => "WBS",
+            Self::Program   => "Program",
+            Self::Qty       => "Qty",
+            Self::Remaining => "Remaining",
+            Self::Status    => "Status",
+            Self::File      => "Archive File",
+            Self::Orders    => "Orders",
+        }
+    }
+
+    fn compare(&self, a: &Failure, b: &Failure) -> Ordering {
+        match self {
+            Self::Mark      => a.mark.cmp(&b.mark),
+            Self::Wbs       => a.wbs.partial_cmp(&b.wbs).unwrap_or(Ordering::Equal),
+            Self::Program   => a.program.cmp(&b.program),
+            Self::Qty       => a.qty.cmp(&b.qty),
+            Self::Remaining => a.qty().cmp(&b.qty()),
+            Self::Status    => status_rank(a.status()).cmp(&status_rank(b.status())),
+            Self::File      => a.confirmation_file().cmp(&b.confirmation_file()),
+            Self::Orders    => order_ids(a).cmp(&order_ids(b)),
+        }
+    }
+}
+
+/// Status filter for the grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum StatusFilter {
+    #[default]
+    All,
+    Matched,
+    NoConfirmationRow,
+    MissingOrders,
+}
+
+impl StatusFilter {
+    const ALL: [Self; 4] = [Self::All, Self::Matched, Self::NoConfirmationRow, Self::MissingOrders];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::All               => "All",
+            Self::Matched           => "Matched",
+            Self::NoConfirmationRow => "No confirmation row",
+            Self::MissingOrders     => "Missing orders",
+        }
+    }
+
+    fn is_match(&self, status: FailureMatchStatus) -> bool {
+        matches!(
+            (self, status),
+            (Self::All, _)
+                | (Self::Matched, FailureMatchStatus::MatchComplete)
+                | (Self::NoConfirmationRow, FailureMatchStatus::NoConfirmationRow)
+                | (Self::MissingOrders, FailureMatchStatus::NotEnoughOrdersApplied(_))
+        )
+    }
+}
+
+fn status_rank(status: FailureMatchStatus) -> u8 {
+    match status {
+        FailureMatchStatus::NoConfirmationRow => 0,
+        FailureMatchStatus::NotEnoughOrdersApplied(_) => 1,
+        FailureMatchStatus::MatchComplete => 2,
+    }
+}
+
+pub fn status_color(status: FailureMatchStatus) -> Color32 {
+    match status {
+        FailureMatchStatus::MatchComplete => Color32::from_rgb(0, 128, 0),
+        FailureMatchStatus::NoConfirmationRow => Color32::from_rgb(192, 0, 0),
+        FailureMatchStatus::NotEnoughOrdersApplied(_) => Color32::from_rgb(208, 112, 0),
+    }
+}
+
+fn order_ids(failure: &Failure) -> String {
+    failure.applied
+        .iter()
+        .map(|o| o.id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn file_name(failure: &Failure) -> String {
+    failure.confirmation_file()
+        .and_then(|f| f.file_name())
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Table of failures from the last comparison, with a detail pane for the selected failure
+#[derive(Debug, Default)]
+pub struct FailureGrid {
+    failures: Vec<Failure>,
+
+    sort_by: GridColumn,
+    descending: bool,
+    filter: String,
+    status_filter: StatusFilter,
+
+    selected: Option<usize>,
+
+    /// Planned orders (or remainder of) not applied to any failure
+    orders: Vec<OrderData>,
+    /// Failure the override inputs were last loaded from
+    editing: Option<usize>,
+    wbs_text: String,
+}
+
+impl FailureGrid {
+    pub fn set_failures(&mut self, failures: Vec<Failure>) {
+        self.failures = failures;
+        self.selected = None;
+        self.editing = None;
+        self.sort();
+    }
+
+    pub fn failures(&self) -> &[Failure] {
+        &self.failures
+    }
+
+    pub fn set_orders(&mut self, orders: Vec<OrderData>) {
+        self.orders = orders;
+    }
+
+    pub fn orders(&self) -> &[OrderData] {
+        &self.orders
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn selected(&self) -> Option<&Failure> {
+        self.selected.and_then(|i| self.failures.get(i))
+    }
+
+    fn sort(&mut self) {
+        let col = self.sort_by;
+        let selected = self.selected.map(|i| self.failures[i].clone());
+
+        self.failures.sort_by(|a, b| col.compare(a, b));
+        if self.descending {
+            self.failures.reverse();
+        }
+
+        // keep selection on the same failure
+        self.selected = selected.and_then(|s| self.failures.iter().position(|f| *f == s));
+        self.editing = self.selected;
+    }
+
+    fn set_sort(&mut self, col: GridColumn) {
+        if self.sort_by == col {
+            self.descending = !self.descending;
+        }
+        else {
+            self.sort_by = col;
+            self.descending = false;
+        }
+
+        self.sort();
+    }
+
+    /// indices of failures that pass the current filters
+    fn visible(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+
+        self.failures
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| self.status_filter.is_match(f.status()))
+            .filter(|(_, f)| {
+                filter.is_empty()
+                    || f.mark.to_lowercase().contains(&filter)
+                    || f.wbs.to_string().to_lowercase().contains(&filter)
+                    || f.program.contains(&filter)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Filter controls and failure table
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            ui.text_edit_singleline(&mut self.filter);
+
+            egui::ComboBox::from_id_source("status-filter")
+                .selected_text(self.status_filter.name())
+                .show_ui(ui, |ui| {
+                    for filter in StatusFilter::ALL {
+                        ui.selectable_value(&mut self.status_filter, filter, filter.name());
+                    }
+                });
+        });
+
+        let visible = self.visible();
+        let mut sort_by = None;
+        let mut clicked = None;
+
+        TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .columns(Column::auto().at_least(60.), GridColumn::ALL.len() - 1)
+            .column(Column::remainder())
+            .header(ROW_HEIGHT + 2., |mut header| {
+                for col in GridColumn::ALL {
+                    header.col(|ui| {
+                        let mut text = col.name().to_string();
+                        if col == self.sort_by {
+                            text.push_str(if self.descending { " v" } else { " ^" });
+                        }
+
+                        if ui.button(RichText::new(text).strong()).clicked() {
+                            sort_by = Some(col);
+                        }
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(ROW_HEIGHT, visible.len(), |row_index, mut row| {
+                    let index = visible[row_index];
+                    let failure = &self.failures[index];
+                    let status = failure.status();
+
+                    row.col(|ui| {
+                        if ui.selectable_label(self.selected == Some(index), &failure.mark).clicked() {
+                            clicked = Some(index);
+                        }
+                    });
+                    row.col(|ui| { ui.label(failure.wbs.to_string()); });
+                    row.col(|ui| { ui.label(&failure.program); });
+                    row.col(|ui| { ui.label(failure.qty.to_string()); });
+                    row.col(|ui| { ui.label(failure.qty().to_string()); });
+                    row.col(|ui| { ui.colored_label(status_color(status), status.to_string()); });
+                    row.col(|ui| { ui.label(file_name(failure)); });
+                    row.col(|ui| { ui.label(order_ids(failure)); });
+                });
+            });
+
+        if let Some(col) = sort_by {
+            self.set_sort(col);
+        }
+
+        if let Some(index) = clicked {
+            self.selected = match self.selected {
+                Some(i) if i == index => None,
+                _ => Some(index)
+            };
+        }
+    }
+
+    /// Detail pane for the selected failure, with manual order assignment and overrides
+    pub fn show_detail(&mut self, ui: &mut egui::Ui) {
+        let index = match self.selected {
+            Some(i) if i < self.failures.len() => i,
+            _ => {
+                ui.label("Select a failure to see its details");
+                return;
+            }
+        };
+
+        if self.editing != Some(index) {
+            self.editing = Some(index);
+            self.wbs_text = self.failures[index].wbs_override
+                .as_ref()
+                .map(|w| w.to_string())
+                .unwrap_or_default();
+        }
+
+        let failure = &self.failures[index];
+        let status = failure.status();
+        ui.heading(&failure.mark);
+        ui.colored_label(status_color(status), status.to_string());
+
+        ui.separator();
+        ui.strong("Confirmation row");
+        match failure.confirmation_row() {
+            Some(row) => {
+                egui::Grid::new("cnf-row-detail")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        let fields = [
+                            ("Archive file", file_name(failure)),
+                            ("Id",           row.id.clone()),
+                            ("Part WBS",     row.part_wbs.to_string()),
+                            ("Part qty",     format!("{} {}", row.part_qty, row.part_uom)),
+                            ("Material",     row.matl.to_string()),
+                            ("Material WBS", row.matl_wbs.to_string()),
+                            ("Material qty", format!("{} {}", row.matl_qty, row.matl_uom)),
+                            ("Location",     row.matl_loc.clone().unwrap_or_default()),
+                            ("Plant",        format!("{:?}", row.plant)),
+                            ("Program",      row.program.clone()),
+                        ];
+
+                        for (name, value) in fields {
+                            ui.label(name);
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+            },
+            None => { ui.label("None found in archived production files"); }
+        }
+
+        ui.separator();
+        self.show_overrides(ui, index);
+
+        ui.separator();
+        self.show_applied(ui, index);
+
+        ui.separator();
+        self.show_available(ui, index);
+    }
+
+    fn show_overrides(&mut self, ui: &mut egui::Ui, index: usize) {
+        ui.strong("Overrides");
+
+        let failure = &mut self.failures[index];
+        ui.checkbox(&mut failure.exclude, "Exclude from output");
+
+        ui.horizontal(|ui| {
+            ui.label("WBS");
+            let res = ui.text_edit_singleline(&mut self.wbs_text);
+
+            if res.lost_focus() {
+                let text = self.wbs_text.trim();
+                failure.wbs_override = match Wbs::try_from(text) {
+                    Ok(Wbs::None) => None,
+                    Ok(wbs) => Some(wbs),
+                    Err(_) => failure.wbs_override.clone(),
+                };
+            }
+        });
+        if !self.wbs_text.trim().is_empty() && Wbs::try_from(self.wbs_text.trim()).is_err() {
+            ui.colored_label(Color32::from_rgb(192, 0, 0), "Invalid WBS element");
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Plant");
+            egui::ComboBox::from_id_source("plant-override")
+                .selected_text(failure.plant_override.map(|p| format!("{:?}", p)).unwrap_or("Order plant".into()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut failure.plant_override, None, "Order plant");
+                    ui.selectable_value(&mut failure.plant_override, Some(Plant::Lancaster), "Lancaster");
+                    ui.selectable_value(&mut failure.plant_override, Some(Plant::Williamsport), "Williamsport");
+                });
+        });
+    }
+
+    fn show_applied(&mut self, ui: &mut egui::Ui, index: usize) {
+        ui.strong("Applied orders");
+
+        let failure = &mut self.failures[index];
+        if failure.applied.is_empty() {
+            ui.label("None");
+            return;
+        }
+
+        let mut remove = None;
+        egui::Grid::new("applied-orders-detail")
+            .num_columns(5)
+            .striped(true)
+            .show(ui, |ui| {
+                for name in ["Order", "Qty", "WBS", "Plant", ""] {
+                    ui.strong(name);
+                }
+                ui.end_row();
+
+                for i in 0..failure.applied.len() {
+                    let range = applied_qty_range(&self.orders, failure, i);
+                    let order = &failure.applied[i];
+                    let mut qty = order.qty;
+
+                    ui.label(order.id.to_string());
+                    ui.add(egui::DragValue::new(&mut qty).clamp_range(range));
+                    ui.label(order.wbs.to_string());
+                    ui.label(format!("{:?}", order.plant));
+                    if ui.small_button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+
+                    if qty != order.qty {
+                        set_applied_qty(&mut self.orders, failure, i, qty);
+                    }
+                }
+            });
+
+        if let Some(i) = remove {
+            let order = failure.remove_applied(i);
+            return_to_pool(&mut self.orders, order);
+        }
+    }
+
+    fn show_available(&mut self, ui: &mut egui::Ui, index: usize) {
+        ui.strong("Available planned orders");
+
+        let failure = &mut self.failures[index];
+        let available: Vec<usize> = self.orders.iter()
+            .enumerate()
+            .filter(|(_, o)| o.mark == failure.mark && o.qty > 0)
+            .map(|(i, _)| i)
+            .collect();
+
+        if available.is_empty() {
+            ui.label("None");
+            return;
+        }
+
+        let mut assign = None;
+        egui::Grid::new("available-orders")
+            .num_columns(5)
+            .striped(true)
+            .show(ui, |ui| {
+                for name in ["Order", "Qty", "WBS", "Plant", ""] {
+                    ui.strong(name);
+                }
+                ui.end_row();
+
+                for i in available {
+                    let order = &self.orders[i];
+
+                    ui.label(order.id.to_string());
+                    ui.label(order.qty.to_string());
+                    ui.label(order.wbs.to_string());
+                    ui.label(format!("{:?}", order.plant));
+                    if ui.add_enabled(failure.qty() > 0, egui::Button::new("Assign").small()).clicked() {
+                        assign = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some(i) = assign {
+            assign_from_pool(&mut self.orders, failure, i);
+        }
+    }
+}
+
+/// Qty an applied order can be set to
+///
+/// it can be raised by what is left of the order in the pool, but not past the failure's remaining qty
+fn applied_qty_range(pool: &[OrderData], failure: &Failure, index: usize) -> RangeInclusive<u32> {
+    let order = &failure.applied[index];
+    let available = pool.iter()
+        .find(|o| o.id == order.id)
+        .map_or(0, |o| o.qty);
+
+    1..=order.qty + available.min(failure.qty())
+}
+
+/// Change the qty of an applied order, moving the difference from or to the pool
+fn set_applied_qty(pool: &mut Vec<OrderData>, failure: &mut Failure, index: usize, qty: u32) {
+    let range = applied_qty_range(pool, failure, index);
+    let qty = qty.clamp(*range.start(), *range.end());
+
+    let order = &mut failure.applied[index];
+    let mut change = order.clone();
+    change.qty = order.qty.abs_diff(qty);
+
+    match qty.cmp(&order.qty) {
+        Ordering::Less    => return_to_pool(pool, change),
+        Ordering::Greater => take_from_pool(pool, &change),
+        Ordering::Equal   => (),
+    }
+
+    order.qty = qty;
+}
+
+/// Apply as much of a pooled order to the failure as it still needs
+fn assign_from_pool(pool: &mut Vec<OrderData>, failure: &mut Failure, pool_index: usize) {
+    let mut order = pool[pool_index].clone();
+    order.qty = order.qty.min(failure.qty());
+    if order.qty == 0 {
+        return;
+    }
+
+    take_from_pool(pool, &order);
+    match failure.applied.iter_mut().find(|o| o.id == order.id) {
+        Some(applied) => applied.qty += order.qty,
+        None => failure.applied.push(order),
+    }
+}
+
+/// Return qty of an order to the pool of unapplied orders
+fn return_to_pool(pool: &mut Vec<OrderData>, order: OrderData) {
+    match pool.iter_mut().find(|o| o.id == order.id) {
+        Some(o) => o.qty += order.qty,
+        None => pool.push(order),
+    }
+}
+
+/// Take qty of an order from the pool of unapplied orders
+fn take_from_pool(pool: &mut Vec<OrderData>, order: &OrderData) {
+    if let Some(o) = pool.iter_mut().find(|o| o.id == order.id) {
+        o.qty = o.qty.saturating_sub(order.qty);
+    }
+
+    pool.retain(|o| o.qty > 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{CnfFileRow, Qty};
+
+    fn order(id: u32, qty: u32) -> OrderData {
+        OrderData {
+            id,
+            mark: "1210123A-X1A".into(),
+            qty,
+            wbs: "D-1210123-10005".try_into().unwrap(),
+            plant: Plant::Lancaster,
+        }
+    }
+
+    fn failure(qty: u32) -> Failure {
+        Failure::try_from(
+            format!("Planned order not found for 1210123A-X1A, D-1210123-10004, {}.000, Sigmanest Program:54091", qty)
+        ).unwrap()
+    }
+
+    fn failure_for(mark: &str, program: &str, qty: u32) -> Failure {
+        Failure::try_from(
+            format!("Planned order not found for {}, D-1210123-10004, {}.000, Sigmanest Program:{}", mark, qty, program)
+        ).unwrap()
+    }
+
+    fn with_cnf_row(mut failure: Failure) -> Failure {
+        let row = CnfFileRow {
+            mark: failure.mark.clone(),
+            id: "S-1210123".into(),
+            part_wbs: failure.wbs.clone(),
+            part_loc: "PROD".into(),
+            part_qty: failure.qty as u64,
+            part_uom: "EA".into(),
+
+            matl: "50W-0008".into(),
+            matl_wbs: Wbs::None,
+            matl_qty: Qty::from(1_000),
+            matl_uom: "IN2".into(),
+            matl_loc: Some("K2".into()),
+
+            plant: Plant::Lancaster,
+            program: failure.program.clone(),
+        };
+
+        failure.set_confirmation_row_from(row, "Production_1.ready".into());
+        failure
+    }
+
+    fn grid() -> FailureGrid {
+        let mut matched = with_cnf_row(failure_for("1210123A-X1B", "54091", 2));
+        matched.apply_order_unchecked(order(1, 2));
+
+        let mut grid = FailureGrid::default();
+        grid.set_failures(vec![
+            failure_for("1210123A-X1C", "54092", 3),
+            matched,
+            with_cnf_row(failure_for("1210123A-X1A", "54093", 1)),
+        ]);
+
+        grid
+    }
+
+    fn marks(grid: &FailureGrid) -> Vec<&str> {
+        grid.failures().iter().map(|f| f.mark.as_str()).collect()
+    }
+
+    #[test]
+    fn sort_toggles_and_keeps_selection() {
+        let mut grid = grid();
+        assert_eq!(marks(&grid), ["1210123A-X1A", "1210123A-X1B", "1210123A-X1C"]);
+
+        grid.selected = Some(2);
+        grid.set_sort(GridColumn::Status);
+        assert_eq!(marks(&grid), ["1210123A-X1C", "1210123A-X1A", "1210123A-X1B"]);
+        assert_eq!(grid.selected().unwrap().mark, "1210123A-X1C");
+
+        // same column again reverses
+        grid.set_sort(GridColumn::Status);
+        assert_eq!(marks(&grid), ["1210123A-X1B", "1210123A-X1A", "1210123A-X1C"]);
+        assert_eq!(grid.selected().unwrap().mark, "1210123A-X1C");
+
+        grid.set_sort(GridColumn::Qty);
+        assert!(!grid.descending);
+        assert_eq!(marks(&grid), ["1210123A-X1A", "1210123A-X1B", "1210123A-X1C"]);
+    }
+
+    #[test]
+    fn filters_by_text_and_status() {
+        let mut grid = grid();
+        assert_eq!(grid.visible(), [0, 1, 2]);
+
+        grid.filter = "x1b".into();
+        assert_eq!(grid.visible(), [1]);
+
+        grid.filter = "54093".into();
+        assert_eq!(grid.visible(), [0]);
+
+        grid.filter.clear();
+        grid.status_filter = StatusFilter::MissingOrders;
+        assert_eq!(grid.visible(), [0]);
+        grid.status_filter = StatusFilter::NoConfirmationRow;
+        assert_eq!(grid.visible(), [2]);
+        grid.status_filter = StatusFilter::Matched;
+        assert_eq!(grid.visible(), [1]);
+    }
+
+    #[test]
+    fn applied_qty_capped_at_failure_qty() {
+        let mut pool = vec![order(1, 10)];
+        let mut failure = failure(5);
+
+        assign_from_pool(&mut pool, &mut failure, 0);
+        assert_eq!(failure.applied[0].qty, 5);
+        assert_eq!(pool[0].qty, 5);
+        assert_eq!(failure.qty(), 0);
+
+        // order still has qty in the pool, but the failure needs no more
+        assert_eq!(applied_qty_range(&pool, &failure, 0), 1..=5);
+        set_applied_qty(&mut pool, &mut failure, 0, 8);
+        assert_eq!(failure.applied[0].qty, 5);
+        assert_eq!(pool[0].qty, 5);
+        assert_eq!(failure.qty(), 0);
+    }
+
+    #[test]
+    fn applied_qty_moves_through_pool() {
+        let mut pool = vec![order(1, 2), order(2, 10)];
+        let mut failure = failure(5);
+
+        assign_from_pool(&mut pool, &mut failure, 0);
+        assign_from_pool(&mut pool, &mut failure, 0);
+        assert_eq!(failure.applied.iter().map(|o| o.qty).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool[0].qty, 7);
+
+        set_applied_qty(&mut pool, &mut failure, 0, 1);
+        assert_eq!(failure.qty(), 1);
+        assert_eq!(pool.iter().map(|o| (o.id, o.qty)).collect::<Vec<_>>(), vec![(2, 7), (1, 1)]);
+
+        // nothing left to assign once the failure is satisfied
+        set_applied_qty(&mut pool, &mut failure, 1, 10);
+        assert_eq!(failure.applied[1].qty, 4);
+        assign_from_pool(&mut pool, &mut failure, 0);
+        assert_eq!(failure.applied.len(), 2);
+        assert_eq!(failure.qty(), 0);
+    }
+}
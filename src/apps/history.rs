@@ -0,0 +1,248 @@
+//! Browse the run history database
+
+use eframe::egui::{self, Color32};
+use egui_extras::{Column, TableBuilder};
+
+use crate::history::{FailureRecord, History, RunFile, RunRecord};
+
+const ROW_HEIGHT: f32 = 18.;
+const RUNS_SHOWN: usize = 200;
+const MATCHED: Color32 = Color32::from_rgb(0, 128, 0);
+const NOT_MATCHED: Color32 = Color32::from_rgb(192, 0, 0);
+
+/// What the central panel shows
+#[derive(Debug)]
+enum HistoryView {
+    /// Nothing selected
+    Empty,
+    /// A run, with its files and failures
+    Run { run: RunRecord, files: Vec<RunFile>, failures: Vec<FailureRecord> },
+    /// Outcomes of a mark across runs
+    Mark { mark: String, failures: Vec<FailureRecord> },
+}
+
+/// Window listing recorded runs, with search by mark or generated file name
+#[derive(Debug)]
+pub struct HistoryBrowser {
+    pub visible: bool,
+
+    history: Option<History>,
+    runs: Vec<RunRecord>,
+    search: String,
+    view: HistoryView,
+}
+
+impl Default for HistoryBrowser {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            history: None,
+            runs: Vec::new(),
+            search: String::new(),
+            view: HistoryView::Empty,
+        }
+    }
+}
+
+impl HistoryBrowser {
+    /// Open the history database (if not open) and reload the list of runs
+    pub fn refresh(&mut self) -> anyhow::Result<()> {
+        if self.history.is_none() {
+            self.history = Some( History::open_default()? );
+        }
+
+        // safe to unwrap: opened above
+        self.runs = self.history.as_ref().unwrap().runs(RUNS_SHOWN)?;
+
+        Ok(())
+    }
+
+    /// Show the history window, returning messages to log
+    pub fn show(&mut self, ctx: &egui::Context) -> Vec<String> {
+        let mut log = Vec::new();
+        let mut visible = self.visible;
+
+        egui::Window::new("Run history")
+            .open(&mut visible)
+            .resizable(true)
+            .default_width(900.)
+            .show(ctx, |ui| {
+                egui::SidePanel::left("history-runs")
+                    .resizable(true)
+                    .show_inside(ui, |ui| self.show_runs(ui, &mut log));
+
+                egui::CentralPanel::default()
+                    .show_inside(ui, |ui| self.show_view(ui));
+            });
+
+        self.visible = visible;
+
+        log
+    }
+
+    fn show_runs(&mut self, ui: &mut egui::Ui, log: &mut Vec<String>) {
+        ui.horizontal(|ui| {
+            let search = ui.add(
+                egui::TextEdit::singleline(&mut self.search)
+                    .hint_text("Mark or file name")
+            );
+            let enter = search.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+            if (ui.button("Search").clicked() || enter) && !self.search.trim().is_empty() {
+                if let Err(e) = self.search() {
+                    log.push( format!("History search failed: {}", e) );
+                }
+            }
+
+            if ui.button("Refresh").clicked() {
+                if let Err(e) = self.refresh() {
+                    log.push( format!("Failed to read run history: {}", e) );
+                }
+            }
+        });
+
+        ui.separator();
+        if self.runs.is_empty() {
+            ui.label("No recorded runs");
+        }
+
+        let mut open = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for run in &self.runs {
+                let selected = matches!(&self.view, HistoryView::Run { run: r, .. } if r.id == run.id);
+                let text = format!(
+                    "{} {}  {}/{} matched",
+                    run.params.started.format("%Y-%m-%d %H:%M"),
+                    run.params.kind,
+                    run.matched,
+                    run.failures
+                );
+
+                if ui.selectable_label(selected, text).clicked() {
+                    open = Some(run.id);
+                }
+            }
+        });
+
+        if let Some(id) = open {
+            if let Err(e) = self.open_run(id) {
+                log.push( format!("Failed to read run {}: {}", id, e) );
+            }
+        }
+    }
+
+    /// Search for a generated file name, or else a mark
+    fn search(&mut self) -> anyhow::Result<()> {
+        let history = match &self.history {
+            Some(history) => history,
+            None => return Err( anyhow!("History is not open") )
+        };
+
+        let search = self.search.trim();
+        if let Some(run) = history.find_file(std::path::Path::new(search))? {
+            return self.open_run(run.id);
+        }
+
+        let failures = history.mark_history(search)?;
+        self.view = HistoryView::Mark { mark: search.to_string(), failures };
+
+        Ok(())
+    }
+
+    fn open_run(&mut self, id: i64) -> anyhow::Result<()> {
+        let history = match &self.history {
+            Some(history) => history,
+            None => return Err( anyhow!("History is not open") )
+        };
+
+        self.view = match history.run(id)? {
+            Some(run) => HistoryView::Run { files: history.files(id)?, failures: history.failures(id)?, run },
+            None => HistoryView::Empty,
+        };
+
+        Ok(())
+    }
+
+    fn show_view(&mut self, ui: &mut egui::Ui) {
+        match &self.view {
+            HistoryView::Empty => {
+                ui.label("Select a run, or search for a mark or file name");
+            },
+            HistoryView::Run { run, files, failures } => {
+                ui.heading( format!("{} run {}", run.params.kind, run.params.started.format("%Y-%m-%d %H:%M:%S")) );
+
+                egui::Grid::new("history-run-params").num_columns(2).show(ui, |ui| {
+                    ui.label("Archive files searched");
+                    ui.label(run.params.files_searched.to_string());
+                    ui.end_row();
+
+                    ui.label("COHV export");
+                    ui.label(run.params.cohv_file.as_ref().map_or("-".into(), |p| p.display().to_string()));
+                    ui.end_row();
+
+                    ui.label("Strategy");
+                    ui.label(&run.params.strategy);
+                    ui.end_row();
+
+                    ui.label("Rows written");
+                    ui.label( format!("{} production, {} issue", run.production_rows, run.issue_rows) );
+                    ui.end_row();
+                });
+
+                ui.separator();
+                for file in files {
+                    ui.label( format!("{}: {}", file.role, file.path.display()) );
+                }
+
+                ui.separator();
+                show_failures(ui, failures, false);
+            },
+            HistoryView::Mark { mark, failures } => {
+                let matched = failures.iter().filter(|f| f.matched).count();
+                ui.heading( format!("{}: {} run(s), matched in {}", mark, failures.len(), matched) );
+
+                ui.separator();
+                show_failures(ui, failures, true);
+            },
+        }
+    }
+}
+
+fn show_failures(ui: &mut egui::Ui, failures: &[FailureRecord], with_run: bool) {
+    let headers: &[&str] = match with_run {
+        true  => &["Run", "Mark", "WBS", "Program", "Qty", "Outcome", "Archive file", "Orders"],
+        false => &["Mark", "WBS", "Program", "Qty", "Outcome", "Archive file", "Orders"],
+    };
+
+    egui::ScrollArea::horizontal().show(ui, |ui| {
+        TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .columns(Column::auto().at_least(60.), headers.len() - 1)
+            .column(Column::remainder())
+            .header(ROW_HEIGHT, |mut header| {
+                for name in headers {
+                    header.col(|ui| { ui.strong(*name); });
+                }
+            })
+            .body(|body| {
+                body.rows(ROW_HEIGHT, failures.len(), |i, mut row| {
+                    let f = &failures[i];
+
+                    if with_run {
+                        row.col(|ui| { ui.label( format!("{} {}", f.started.format("%Y-%m-%d %H:%M"), f.kind) ); });
+                    }
+                    row.col(|ui| { ui.label(&f.mark); });
+                    row.col(|ui| { ui.label(&f.wbs); });
+                    row.col(|ui| { ui.label(&f.program); });
+                    row.col(|ui| { ui.label(f.qty.to_string()); });
+                    row.col(|ui| {
+                        let color = if f.matched { MATCHED } else { NOT_MATCHED };
+                        ui.colored_label(color, &f.outcome);
+                    });
+                    row.col(|ui| { ui.label(f.cnf_file.as_ref().map_or("-".into(), |p| p.display().to_string())); });
+                    row.col(|ui| { ui.label(&f.orders); });
+                });
+            });
+    });
+}
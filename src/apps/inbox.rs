@@ -2,18 +2,31 @@
 use std::{fs, io};
 use std::path::PathBuf;
 
+use chrono::{DateTime, Local};
 use eframe::{self, egui};
 
-use crate::api::{Order, CnfFileRow, IssueFileRow};
-use crate::inbox::{FailureMatchStatus, Failure};
-use crate::inbox::parsers::{parse_failures, parse_cohv_xl};
-use crate::inbox::cnf_files::{self, get_last_n_files, parse_file, write_file};
-use crate::paths::{self, timestamped_file};
+use crate::api::OrderData;
+use crate::inbox::Failure;
+use crate::inbox::discovery::{default_export_folders, ExportInfo, ExportSearch};
+use crate::inbox::cnf_files;
+use crate::inbox::consolidate::Consolidation;
+use crate::inbox::validation::{Findings, Severity};
+use crate::inbox::workflow::{CohvSource, InboxWorkflow, WorkflowOptions};
+use crate::paths::timestamped_file;
+use crate::report::RunReport;
+use super::grid::FailureGrid;
+use super::history::HistoryBrowser;
+use super::ready_files::ReadyFileBrowser;
+use super::worker::{Event, Job, JobInput, JobProgress, Worker, move_files};
 
 const MAX_FILES: usize = 2000;
 
+const CNF_ERROR_POPUP: &str = "gen-cnf-error-popup";
+const ISSUE_ERROR_POPUP: &str = "issue-error-popup";
+const COGI_ERROR_POPUP: &str = "cogi-error-popup";
+
 fn push_str_ls(ls: &mut String, value: impl AsRef<str>) {
-    if ls.len() > 0 { ls.push('\n'); }
+    if !ls.is_empty() { ls.push('\n'); }
 
     ls.push_str(value.as_ref());
 }
@@ -25,16 +38,52 @@ pub struct SapInboxApp {
     auto_move_files: bool,
 
     inbox_errors: String,
+    /// When the inbox errors were last edited
+    inbox_updated: Option<DateTime<Local>>,
     parts_list: String,
     new_inbox: String,
     log: String,
 
     popup_error: String,
+
+    /// Folders searched for COHV exports, one per line
+    cohv_folders: String,
+    /// COGI export to generate corrective issue rows for
+    cogi_file: String,
+    /// COHV export used by the last comparison (or check), with freshness warnings
+    cohv_export: Option<(ExportInfo, Vec<String>)>,
+
+    worker: Option<Worker>,
+    progress: JobProgress,
+
+    grid: FailureGrid,
+    ready_files: ReadyFileBrowser,
+    history: HistoryBrowser,
+    /// How generated rows are merged and split into files
+    consolidation: Consolidation,
+    /// Production files written by the last comparison
+    prodfiles: Vec<PathBuf>,
+    /// Validation of the last generated (or regenerated) rows
+    findings: Findings,
+    /// Results of the last job
+    report: Option<RunReport>,
+    /// Workflow of the last comparison, to regenerate its output
+    workflow: Option<Box<InboxWorkflow>>,
+}
+
+/// Failures and orders from the last comparison, including manual edits
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Session {
+    failures: Vec<Failure>,
+    orders: Vec<OrderData>,
+    #[serde(default)]
+    prodfiles: Vec<PathBuf>,
 }
 
 impl SapInboxApp {
     const NAME: &'static str = "SAP Inbox Errors";
 
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> eframe::AppCreator {
         Box::new(|cc| Box::new(Self::init(cc)))
     }
@@ -64,21 +113,50 @@ impl SapInboxApp {
             None => (false, "".into(), "".into())
         };
 
-        Self {
+        let inbox_updated = cc.storage
+            .and_then(|storage| storage.get_string("inbox_updated"))
+            .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&Local));
+        let cohv_folders = cc.storage
+            .and_then(|storage| storage.get_string("cohv_folders"))
+            .unwrap_or_else(|| {
+                default_export_folders()
+                    .iter()
+                    .map(|f| f.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+
+        let cogi_file = cc.storage
+            .and_then(|storage| storage.get_string("cogi_file"))
+            .unwrap_or_default();
+
+        let consolidation: Consolidation = cc.storage
+            .and_then(|storage| eframe::get_value(storage, "consolidation"))
+            .unwrap_or_default();
+
+        let session: Session = cc.storage
+            .and_then(|storage| eframe::get_value(storage, "session"))
+            .unwrap_or_default();
+
+        let mut app = Self {
             files_to_parse: 200,
             max_files: cnf_files::get_num_files().unwrap_or(MAX_FILES),
             auto_move_files,
             inbox_errors,
+            inbox_updated,
             new_inbox,
+            cohv_folders,
+            cogi_file,
+            consolidation,
+            prodfiles: session.prodfiles,
 
             ..Default::default()
-        }
-    }
+        };
+        app.grid.set_failures(session.failures);
+        app.grid.set_orders(session.orders);
 
-    fn inbox_errors(&self) -> std::str::Split<&str> {
-        self.inbox_errors
-            .trim_end_matches("\n")
-            .split("\n")
+        app
     }
 
     fn log(&mut self, val: impl AsRef<str>) {
@@ -86,210 +164,220 @@ impl SapInboxApp {
     }
 
     pub fn generate_parts(&mut self) -> anyhow::Result<()> {
-        if self.inbox_errors.is_empty() {
-            return Err( anyhow!("No inbox errors to parse") );
-        }
-
-        let inbox = parse_failures(self.inbox_errors());
-
-        // get marks only from failures
-        let (parsed, errors): (Vec<_>, Vec<_>) = inbox
-            .into_iter()
-            .partition(Result::is_ok);
+        let mut workflow = InboxWorkflow::new(self.inbox_errors.as_str());
+        let parsed = workflow.parse()?;
 
         // log errors
-        errors
-            .into_iter()
-            .for_each(|e| self.log( e.unwrap_err().to_string() ) );
-
-        let mut marks: Vec<String> = parsed 
+        parsed.errors
             .into_iter()
-            .map(|f| f.unwrap().mark)
-            .collect();
-
-        // remove duplicates
-        marks.sort();
-        marks.dedup();
+            .for_each(|e| self.log(e));
 
         // cannot delimit on '\n' because applications like SAP and Excel don't read this as multiple lines
-        self.parts_list = marks.join("\r\n");
+        self.parts_list = workflow.parts_list().join("\r\n");
 
         Ok(())
     }
 
-    fn issue_all(&mut self) -> anyhow::Result<()> {
-        // parse inbox
-        let mut inbox: Vec<Failure> = parse_failures(self.inbox_errors())
-            .into_iter()
-            .filter(|f| f.is_ok())
-            .map(Result::unwrap)
-            .collect();
-        inbox.sort_by( |a, b| a.partial_cmp(b).unwrap() );
-
-        // get confirmation file data
-        for f in get_last_n_files(self.files_to_parse)? {
-            for cnf_row in parse_file(f.path())? {
-                inbox
-                    .iter_mut()
-                    .filter(|f| **f == cnf_row)
-                    .for_each(|f| f.set_confirmation_row_data(cnf_row.clone()));
-            }
+    fn job_input(&self) -> JobInput {
+        JobInput {
+            inbox_errors: self.inbox_errors.clone(),
+            files_to_parse: self.files_to_parse,
+            auto_move_files: self.auto_move_files,
+            cohv_folders: self.cohv_folders(),
+            inbox_updated: self.inbox_updated,
+            consolidation: self.consolidation.clone(),
+            cogi_file: (!self.cogi_file.trim().is_empty()).then(|| PathBuf::from(self.cogi_file.trim())),
+        }
+    }
 
-            let has_confirmation_row = inbox
-                .iter()
-                .filter(|f| !f.has_confirmation_row())
-                .count();
+    fn cohv_folders(&self) -> Vec<PathBuf> {
+        self.cohv_folders
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
 
-            if has_confirmation_row == 0 {
-                break;
-            }
-        }
+    /// Find the newest COHV export and check it against the inbox errors
+    fn check_cohv_export(&mut self) -> anyhow::Result<()> {
+        let mut workflow = InboxWorkflow::new(self.inbox_errors.as_str())
+            .cohv( CohvSource::Search(ExportSearch::new(self.cohv_folders())) )
+            .options( WorkflowOptions { inbox_updated: self.inbox_updated, ..Default::default() } );
 
-        let issuefile = paths::timestamped_file("Issue", "ready");
-        let mut records: Vec<IssueFileRow> = Vec::new();
-        inbox.iter_mut()
-            .map(|f| f.generate_issue_output())
-            .for_each(|r| {
-                match r {
-                    Ok(result) => records.push(result),
-                    Err(e) => eprintln!("{}", e),
-                }
-            });
+        // an empty inbox only skips the missing marks check
+        let _ = workflow.parse();
+        let loaded = workflow.load_orders()?;
 
-        write_file(records, issuefile.into())?;
-        if self.auto_move_files {
-            self.move_issuefiles()?;
+        if let Some(info) = loaded.export {
+            self.log( format!("Found COHV export {}", info) );
+            self.cohv_export = Some( (info, loaded.warnings) );
         }
 
         Ok(())
     }
 
-    pub fn generate_comparison(&mut self) -> anyhow::Result<()> {
-        if self.inbox_errors.is_empty() {
-            return Err( anyhow!("No inbox errors to parse") );
-        }
+    fn is_busy(&self) -> bool {
+        self.worker.is_some()
+    }
 
-        // parse inbox
-        let mut inbox: Vec<Failure> = parse_failures(self.inbox_errors())
-            .into_iter()
-            .filter(|f| f.is_ok())
-            .map(Result::unwrap)
-            .collect();
-        inbox.sort_by( |a, b| a.partial_cmp(b).unwrap() );
-
-        // get confirmation file data
-        for f in get_last_n_files(self.files_to_parse)? {
-            for cnf_row in parse_file(f.path())? {
-                inbox
-                    .iter_mut()
-                    .filter(|f| **f == cnf_row)
-                    .for_each(|f| f.set_confirmation_row_data(cnf_row.clone()));
-            }
+    /// Start a job on a background worker
+    fn start_job(&mut self, job: Job, ctx: &egui::Context) {
+        self.log( format!("{}...", job.name()) );
+        self.progress = JobProgress::default();
+        self.worker = Some( Worker::spawn(job, self.job_input(), ctx.clone()) );
+    }
 
-            let has_confirmation_row = inbox
-                .iter()
-                .filter(|f| !f.has_confirmation_row())
-                .count();
+    /// Handle events from the background worker, returning the error if the job failed
+    fn poll_worker(&mut self) -> Option<String> {
+        let events = match &mut self.worker {
+            Some(worker) => worker.poll(),
+            None => return None
+        };
 
-            if has_confirmation_row == 0 {
-                break;
+        let job = self.worker.as_ref().map(Worker::job);
+        let cancelled = self.worker.as_ref().is_some_and(Worker::is_cancelled);
+        let mut error = None;
+        for event in events {
+            if let Some(err) = self.handle_event(event, job) {
+                error = Some(err);
             }
         }
 
-        // get orders from cohv
-        let userprofile = match std::env::var_os("USERPROFILE") {
-            Some(path) => path,
-            None => return Err( anyhow!("Could not locate environment variable `USERPROFILE`") )
-        };
-    
-        let path = PathBuf::from(format!("{}/Documents/SAP/SAP GUI/export.xlsx", userprofile.to_str().unwrap()));
+        if self.worker.as_ref().is_some_and(Worker::is_finished) {
+            self.worker = None;
+        }
 
-        if !path.exists() {
-            return Err( anyhow!("Could not locate export file: {}", path.display()) );
+        // cancelling is not an error
+        if cancelled {
+            return None;
         }
-    
-        for order in parse_cohv_xl(PathBuf::from(path))? {
-            match order {
-                Order::PlannedOrder(mut data) => {
-                    'inbox: for failure in &mut inbox {
-                        if failure.mark == data.mark {
-                            let order = failure.apply_order_unchecked(data);
-    
-                            match order {
-                                Some(d) => data = d,
 
-                                // break loop if order is 100% applied
-                                None => break 'inbox
-                            }
-                        }
-                    }
-                },
-                _ => ()
-            }
+        error
+    }
+
+    fn handle_event(&mut self, event: Event, job: Option<Job>) -> Option<String> {
+        if self.progress.update(&event) {
+            return None;
+        }
+
+        match event {
+            Event::Log(msg) => self.log(msg),
+            Event::NewInbox(new_inbox) => self.new_inbox = new_inbox,
+            Event::Failures(failures) => self.grid.set_failures(failures),
+            Event::Orders(orders) => self.grid.set_orders(orders),
+            Event::CohvExport { info, warnings } => self.cohv_export = Some( (info, warnings) ),
+            Event::Generated(files) => self.prodfiles = files,
+            Event::Findings(findings) => self.findings = findings,
+            Event::Report(report) => self.report = Some(report),
+            Event::Workflow(workflow) => self.workflow = Some(workflow),
+            Event::Finished(Ok(())) => match job {
+                Some(Job::Comparison) => self.log("Confirmation file generated"),
+                Some(Job::IssueAll) => self.log("Issue file generated"),
+                Some(Job::IssueCogi) => self.log("COGI issue file generated"),
+                None => ()
+            },
+            Event::Finished(Err(e)) => {
+                self.log(&e);
+
+                return Some(e);
+            },
+            _ => ()
         }
 
-        for f in &inbox {
-            match f.status() {
-                FailureMatchStatus::NoConfirmationRow => {
-                    self.log( format!("{}\t<{}, {}> has no confirmation row", f.mark, f.wbs, f.program) );
-                },
-                FailureMatchStatus::NotEnoughOrdersApplied(qty) => {
-                    self.log( format!("{}\t<{}, {}> missing orders for qty of {}/{}", f.mark, f.wbs, f.program, qty, f.qty) );
-                },
-                _ => ()
+        None
+    }
+
+    /// Generate a confirmation file on the current thread
+    pub fn generate_comparison(&mut self) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for event in Worker::run_blocking(Job::Comparison, self.job_input()) {
+            if let Some(e) = self.handle_event(event, Some(Job::Comparison)) {
+                result = Err( anyhow!(e) );
             }
         }
 
-        let new_inbox: Vec<String> = inbox.iter()
-            .map(|f| f.new_inbox_text())
-            .filter(Option::is_some)
-            .map(Option::unwrap)
-            .collect();
+        result
+    }
 
-        self.new_inbox = new_inbox.join("\n");
-        if new_inbox.len() > 0 {
-            fs::write(timestamped_file("new_inbox", "txt"), new_inbox.join("\n"))?;
+    /// Rewrite the transfer and production files and not matched list from the (edited) failures
+    ///
+    /// written by the workflow of the last comparison, so rows are validated against its
+    /// archived rows. Replaces the files from the last comparison, if they have not been moved yet;
+    /// the old files are only removed once the new ones are written.
+    fn regenerate_output(&mut self) -> anyhow::Result<Vec<PathBuf>> {
+        // the workflow is not kept between sessions, so a new one has no archived rows
+        let mut workflow = self.workflow.take().map_or_else(|| self.job_input().workflow(), |w| *w);
+        workflow.set_consolidation(self.consolidation.clone());
+        workflow.edit(self.grid.failures().to_vec(), self.grid.orders().to_vec());
+
+        let result = workflow.write_production();
+        let generated = match result {
+            Ok(generated) => generated,
+            Err(e) => {
+                self.workflow = Some(Box::new(workflow));
+                return Err(e);
+            }
+        };
+
+        generated.errors.iter().for_each(|e| self.log(e));
+        self.findings = generated.findings.clone();
+        for line in self.findings.lines() {
+            self.log(line);
         }
 
-        let prodfile = paths::timestamped_file("Production", "ready");
-        let mut records: Vec<CnfFileRow> = Vec::new();
-        inbox.iter_mut()
-            .map(|f| f.generate_output())
-            .for_each(|r| {
-                match r {
-                    Ok(results) => records.extend(results),
-                    Err(e) => eprintln!("{}", e),
-                }
-            });
+        if self.findings.has_errors() {
+            // the last files are kept, so the failures in them are not back in the inbox
+            if let Some(file) = &generated.new_inbox_file {
+                fs::remove_file(file)?;
+            }
+            self.workflow = Some(Box::new(workflow));
 
-        write_file(records, prodfile.into())?;
-        if self.auto_move_files {
-            self.move_prodfiles()?;
+            return Err( anyhow!("Confirmation and transfer files not written: rows have validation errors") );
         }
 
-        Ok(())
-    }
+        // a new file may have the same name as an old one, if written in the same second
+        for file in self.prodfiles.iter().filter(|f| f.exists() && !generated.files.contains(f)) {
+            fs::remove_file(file)?;
+        }
+        self.prodfiles = generated.files.clone();
+        self.new_inbox = generated.new_inbox.join("\n");
+        self.log( format!("Report written to {}", generated.report_file.display()) );
+        self.report = Some(generated.report);
 
-    fn move_prodfiles(&mut self) -> io::Result<()> {
-        self.move_files("Production_*.ready")
-    }
+        if let Err(e) = workflow.record_history() {
+            self.log( format!("Failed to record run history: {}", e) );
+        }
+        self.workflow = Some(Box::new(workflow));
 
-    fn move_issuefiles(&mut self) -> io::Result<()> {
-        self.move_files("Issue_*.ready")
+        Ok(self.prodfiles.clone())
     }
 
-    fn move_files(&mut self, pattern: &str) -> io::Result<()> {
+    /// Write an Excel report of the last job
+    ///
+    /// comparison results are taken from the failures grid, so manual edits are included
+    fn export_report(&mut self) -> anyhow::Result<PathBuf> {
+        let report = match &self.report {
+            Some(report) if !report.issue.is_empty() => report.clone(),
+            _ => RunReport {
+                params: self.report.as_ref().map(|r| r.params.clone()).unwrap_or_default(),
+                ..RunReport::from_failures(self.grid.failures().to_vec(), self.grid.orders().to_vec())
+            },
+        };
+
+        let path = PathBuf::from( timestamped_file("Report", "xlsx") );
+        report.write_xlsx(&path)?;
 
-        for entry in glob::glob(pattern).unwrap() {
-            if let Ok(file) = entry {
-                let mut to = paths::SAP_OUTBOUND.to_path_buf();
-                to.push(&file);
+        Ok(path)
+    }
 
-                fs::copy(&file, to)?;
-                fs::remove_file(&file)?;
+    /// Move the transfer and production files, transfers first so they are posted before production
+    fn move_prodfiles(&mut self) -> io::Result<()> {
+        let mut moved = move_files("Transfer_*.ready")?;
+        moved.extend( move_files("Production_*.ready")? );
 
-                self.log(format!("Moved file {}", &file.display()))
-            }
+        for file in moved {
+            self.log(format!("Moved file {}", &file.display()));
         }
 
         Ok(())
@@ -301,9 +389,48 @@ impl eframe::App for SapInboxApp {
         storage.set_string("auto_move", self.auto_move_files.to_string());
         storage.set_string("inbox", self.inbox_errors.to_string());
         storage.set_string("new_inbox", self.new_inbox.to_string());
+        storage.set_string("cohv_folders", self.cohv_folders.to_string());
+        storage.set_string("cogi_file", self.cogi_file.to_string());
+        if let Some(updated) = self.inbox_updated {
+            storage.set_string("inbox_updated", updated.to_rfc3339());
+        }
+
+        let session = Session {
+            failures: self.grid.failures().to_vec(),
+            orders: self.grid.orders().to_vec(),
+            prodfiles: self.prodfiles.clone(),
+        };
+        eframe::set_value(storage, "session", &session);
+        eframe::set_value(storage, "consolidation", &self.consolidation);
     }
 
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let job = self.worker.as_ref().map(Worker::job);
+        if let Some(e) = self.poll_worker() {
+            self.popup_error = e;
+
+            let popup = match job {
+                Some(Job::IssueAll) => ISSUE_ERROR_POPUP,
+                Some(Job::IssueCogi) => COGI_ERROR_POPUP,
+                _ => CNF_ERROR_POPUP,
+            };
+            ctx.memory_mut(|mem| mem.open_popup(egui::Id::new(popup)));
+        }
+
+        let busy = self.is_busy();
+
+        if self.ready_files.visible {
+            for msg in self.ready_files.show(ctx) {
+                self.log(msg);
+            }
+        }
+
+        if self.history.visible {
+            for msg in self.history.show(ctx) {
+                self.log(msg);
+            }
+        }
+
         egui::TopBottomPanel::top("action-area")
             .show(ctx, |ui| {
                 ui.vertical_centered(|ui| {
@@ -322,27 +449,46 @@ impl eframe::App for SapInboxApp {
                         }
                     }
 
-                    let res_cnf = ui.button("Generate confirmation file");
-                    let err_cnf = ui.make_persistent_id("gen-cnf-error-popup");
+                    let res_cnf = ui.add_enabled(!busy, egui::Button::new("Generate confirmation file"));
+                    let err_cnf = egui::Id::new(CNF_ERROR_POPUP);
                     egui::popup_below_widget(ui, err_cnf, &res_cnf, |ui| {
                         ui.style_mut().wrap = Some(false);
                         ui.label(&self.popup_error);
                     });
                     if res_cnf.clicked() {
-                        self.log("Generating confirmation file...");
-
-                        // TODO: move this to another thread because it takes a while
-                        match self.generate_comparison() {
-                            Ok(_) => self.log("Confirmation file generated"),
-                            Err(e) => {
-                                self.popup_error = e.to_string();
-                                ui.memory_mut(|mem| mem.open_popup(err_cnf));
-                            }
+                        self.start_job(Job::Comparison, ctx);
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.label("COGI export");
+                        ui.text_edit_singleline(&mut self.cogi_file);
+
+                        let res_cogi = ui.add_enabled(!busy && !self.cogi_file.trim().is_empty(), egui::Button::new("Issue COGI errors"));
+                        let err_cogi = egui::Id::new(COGI_ERROR_POPUP);
+                        egui::popup_below_widget(ui, err_cogi, &res_cogi, |ui| {
+                            ui.style_mut().wrap = Some(false);
+                            ui.label(&self.popup_error);
+                        });
+
+                        if res_cogi.clicked() {
+                            self.start_job(Job::IssueCogi, ctx);
                         }
+                    });
+
+
+                    if ui.button("Review pending files").clicked() {
+                        self.ready_files.visible = true;
+                        self.ready_files.refresh();
                     }
 
+                    if ui.button("Run history").clicked() {
+                        match self.history.refresh() {
+                            Ok(()) => self.history.visible = true,
+                            Err(e) => self.log( format!("Failed to read run history: {}", e) ),
+                        }
+                    }
 
-                    if ui.button("Move confirmation file(s)").clicked() {
+                    if ui.add_enabled(!busy, egui::Button::new("Move confirmation file(s)")).clicked() {
                         match self.move_prodfiles() {
                             Ok(_) => self.log("File(s) moved"),
                             Err(e) => self.log( e.to_string() )
@@ -362,34 +508,32 @@ impl eframe::App for SapInboxApp {
                     .max_height(200.)
                     .show(ui, |ui| {
                         ui.style_mut().wrap = Some(false);
-                        egui::TextEdit::multiline(&mut self.inbox_errors)
+                        let edit = egui::TextEdit::multiline(&mut self.inbox_errors)
                             .desired_width(f32::INFINITY)
                             .show(ui);
+
+                        if edit.response.changed() {
+                            self.inbox_updated = Some(Local::now());
+                        }
                     });
 
                 ui.horizontal(|ui| {
                     if ui.button("Clear inbox errors").clicked() {
                         self.inbox_errors.clear();
+                        self.inbox_updated = None;
                     }
     
                     if !self.inbox_errors.is_empty() {
                         // TODO: refactor this "button with a popup for errors"
-                        let res_issue = ui.button("Issue all errors");
-                        let err_issue = ui.make_persistent_id("issue-error-popup");
+                        let res_issue = ui.add_enabled(!busy, egui::Button::new("Issue all errors"));
+                        let err_issue = egui::Id::new(ISSUE_ERROR_POPUP);
                         egui::popup_below_widget(ui, err_issue, &res_issue, |ui| {
                             ui.style_mut().wrap = Some(false);
                             ui.label(&self.popup_error);
                         });
     
                         if res_issue.clicked() {
-                            // TODO: move this to another thread because it takes a while
-                            match self.issue_all() {
-                                Ok(_) => self.log("Issue file generated"),
-                                Err(e) => {
-                                    self.popup_error = e.to_string();
-                                    ui.memory_mut(|mem| mem.open_popup(err_issue));
-                                }
-                            }
+                            self.start_job(Job::IssueAll, ctx);
                         }
                     }
                 });
@@ -417,6 +561,29 @@ impl eframe::App for SapInboxApp {
                                 })
                         );
                     });
+
+                    ui.checkbox(&mut self.consolidation.merge, "Merge rows with the same mark, WBS, material, plant and program");
+                    ui.checkbox(&mut self.consolidation.split_by_plant, "Write a separate file for each plant");
+                    ui.horizontal(|ui| {
+                        let mut capped = self.consolidation.max_rows.is_some();
+                        ui.checkbox(&mut capped, "Maximum rows per file");
+
+                        let mut max_rows = self.consolidation.max_rows.unwrap_or(500);
+                        ui.add_enabled(capped, egui::DragValue::new(&mut max_rows).speed(10.0).clamp_range(1..=100_000));
+                        self.consolidation.max_rows = capped.then_some(max_rows);
+                    });
+
+                    ui.label("COHV export folders (one per line)");
+                    ui.add(
+                        egui::TextEdit::multiline(&mut self.cohv_folders)
+                            .desired_rows(3)
+                            .desired_width(f32::INFINITY)
+                    );
+                    if ui.add_enabled(!busy, egui::Button::new("Find COHV export")).clicked() {
+                        if let Err(e) = self.check_cohv_export() {
+                            self.log( e.to_string() );
+                        }
+                    }
                 });
             });
         egui::TopBottomPanel::bottom("log")
@@ -445,7 +612,6 @@ impl eframe::App for SapInboxApp {
                         // .max_height(100.)
                         .show_rows(ui, ui.text_style_height(&egui::TextStyle::Body), 10, |ui, rng| {
                             let display = self.parts_list.split('\n')
-                                .into_iter()
                                 .skip(rng.start)
                                 .take(rng.end - rng.start)
                                 .collect::<Vec<_>>()
@@ -467,6 +633,17 @@ impl eframe::App for SapInboxApp {
                         });
             });
 
+        if !self.grid.is_empty() {
+            egui::SidePanel::right("failure-detail")
+                .resizable(true)
+                .min_width(200.)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_source("detail scroll area")
+                        .show(ui, |ui| self.grid.show_detail(ui));
+                });
+        }
+
         egui::CentralPanel::default()
         .show(ctx, |ui| {
                 egui::ScrollArea::both().show(ui, |ui| {
@@ -484,16 +661,73 @@ impl eframe::App for SapInboxApp {
                             self.new_inbox.clear();
                         }
                                 
-    
-                    // TODO: progress bar
-                    // let progress = 0f64;
-                    // let progress_bar = egui::ProgressBar::new(progress)
-                    //     .show_percentage()
-                    //     .animate(*animate_progress_bar);
-                    // *animate_progress_bar = ui
-                    //     .add(progress_bar)
-                    //     .on_hover_text("The progress bar can be animated!")
-                    //     .hovered();
+                    if let Some((info, warnings)) = &self.cohv_export {
+                        ui.separator();
+                        ui.label( format!("COHV export: {}", info) );
+                        for w in warnings {
+                            ui.colored_label(egui::Color32::from_rgb(192, 96, 0), w);
+                        }
+                    }
+
+                    if !self.findings.is_empty() {
+                        ui.separator();
+                        ui.heading("Validation");
+                        for (severity, findings) in self.findings.by_severity() {
+                            let color = match severity {
+                                Severity::Error => egui::Color32::from_rgb(192, 0, 0),
+                                Severity::Warning => egui::Color32::from_rgb(192, 96, 0),
+                            };
+
+                            ui.collapsing(format!("{} {}(s)", findings.len(), severity), |ui| {
+                                for f in findings {
+                                    ui.colored_label(color, format!("[{}] {}", f.rule, f));
+                                }
+                            });
+                        }
+                    }
+
+                    if let Some(worker) = &self.worker {
+                        ui.separator();
+                        ui.heading(worker.job().name());
+
+                        let progress_bar = egui::ProgressBar::new(self.progress.fraction())
+                            .text(format!("{}/{} files scanned", self.progress.files_scanned, self.progress.total_files))
+                            .animate(true);
+                        ui.add(progress_bar);
+
+                        ui.label(format!("{}/{} failures matched", self.progress.failures_matched, self.progress.total_failures));
+                        ui.label(format!("{} orders applied", self.progress.orders_applied));
+
+                        if worker.is_cancelled() {
+                            ui.label("Cancelling...");
+                        }
+                        else if ui.button("Cancel").clicked() {
+                            worker.cancel();
+                        }
+                    }
+
+                    if !self.grid.is_empty() {
+                        ui.separator();
+                        ui.horizontal(|ui| {
+                            ui.heading("Failures");
+
+                            if ui.add_enabled(!busy, egui::Button::new("Regenerate confirmation file")).clicked() {
+                                match self.regenerate_output() {
+                                    Ok(files) if files.is_empty() => self.log("No confirmation rows to regenerate"),
+                                    Ok(files) => files.iter().for_each(|f| self.log( format!("Confirmation file {} regenerated", f.display()) )),
+                                    Err(e) => self.log( e.to_string() ),
+                                }
+                            }
+
+                            if ui.add_enabled(!busy, egui::Button::new("Export Excel report")).clicked() {
+                                match self.export_report() {
+                                    Ok(file) => self.log( format!("Report written to {}", file.display()) ),
+                                    Err(e) => self.log( format!("Failed to write report: {}", e) ),
+                                }
+                            }
+                        });
+                        self.grid.show(ui);
+                    }
                 });
             });
     }
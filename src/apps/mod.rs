@@ -1,3 +1,19 @@
 
+#[cfg(feature = "cli")]
+mod bot;
+#[cfg(feature = "cli")]
+pub use bot::{BotConfig, InboxBot};
+
+#[cfg(feature = "gui")]
 mod inbox;
+#[cfg(feature = "gui")]
 pub use inbox::SapInboxApp;
+
+#[cfg(feature = "gui")]
+pub mod grid;
+#[cfg(feature = "gui")]
+pub mod history;
+#[cfg(feature = "gui")]
+pub mod ready_files;
+#[cfg(feature = "gui")]
+pub mod worker;
@@ -0,0 +1,399 @@
+//! Preview, edit and move pending `.ready` files
+
+use std::path::{Path, PathBuf};
+
+use csv::StringRecord;
+use eframe::egui::{self, Color32};
+use egui_extras::{Column, TableBuilder};
+
+use crate::api::{CnfFileRow, IssueFileRow, TransferFileRow};
+use crate::inbox::cnf_files::{read_records, deserialize_record, write_file, HEADERS, ISSUE_HEADERS, TRANSFER_HEADERS};
+use super::worker::move_file;
+
+const ROW_HEIGHT: f32 = 20.;
+const INVALID: Color32 = Color32::from_rgb(192, 0, 0);
+
+/// Type of `.ready` file, from its file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadyKind {
+    Transfer,
+    Production,
+    Issue,
+}
+
+impl ReadyKind {
+    const ALL: [Self; 3] = [Self::Transfer, Self::Production, Self::Issue];
+
+    fn pattern(&self) -> &'static str {
+        match self {
+            Self::Transfer   => "Transfer_*.ready",
+            Self::Production => "Production_*.ready",
+            Self::Issue      => "Issue_*.ready",
+        }
+    }
+
+    fn headers(&self) -> &'static [&'static str] {
+        match self {
+            Self::Transfer   => &TRANSFER_HEADERS,
+            Self::Production => &HEADERS,
+            Self::Issue      => &ISSUE_HEADERS,
+        }
+    }
+
+    /// Validate a row, returning the error message if it does not deserialize
+    fn validate(&self, row: &[String]) -> Option<String> {
+        if row.len() != self.headers().len() {
+            return Some( format!("Expected {} columns, found {}", self.headers().len(), row.len()) );
+        }
+
+        let record = StringRecord::from(row.to_vec());
+        let result = match self {
+            Self::Transfer   => deserialize_record::<TransferFileRow>(&record, self.headers()).map(|_| ()),
+            Self::Production => deserialize_record::<CnfFileRow>(&record, self.headers()).map(|_| ()),
+            Self::Issue      => deserialize_record::<IssueFileRow>(&record, self.headers()).map(|_| ()),
+        };
+
+        result.err().map(|e| e.to_string())
+    }
+
+    /// Write rows to `path`, failing without writing if any row is invalid
+    fn save(&self, rows: &[Vec<String>], path: PathBuf) -> anyhow::Result<()> {
+        if let Some((i, e)) = rows.iter().enumerate().find_map(|(i, r)| self.validate(r).map(|e| (i, e))) {
+            return Err( anyhow!("row {}: {}", i + 1, e) );
+        }
+
+        let records = rows.iter().map(|r| StringRecord::from(r.to_vec()));
+
+        match self {
+            Self::Transfer => {
+                let rows = records
+                    .map(|r| deserialize_record::<TransferFileRow>(&r, self.headers()))
+                    .collect::<csv::Result<Vec<_>>>()?;
+                write_file(rows, path)?;
+            },
+            Self::Production => {
+                let rows = records
+                    .map(|r| deserialize_record::<CnfFileRow>(&r, self.headers()))
+                    .collect::<csv::Result<Vec<_>>>()?;
+                write_file(rows, path)?;
+            },
+            Self::Issue => {
+                let rows = records
+                    .map(|r| deserialize_record::<IssueFileRow>(&r, self.headers()))
+                    .collect::<csv::Result<Vec<_>>>()?;
+                write_file(rows, path)?;
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Pending file in the working directory
+#[derive(Debug)]
+struct PendingFile {
+    path: PathBuf,
+    kind: ReadyKind,
+    selected: bool,
+}
+
+/// File opened for editing
+#[derive(Debug)]
+struct OpenFile {
+    path: PathBuf,
+    kind: ReadyKind,
+    rows: Vec<Vec<String>>,
+    errors: Vec<Option<String>>,
+    dirty: bool,
+}
+
+impl OpenFile {
+    fn open(path: &Path, kind: ReadyKind) -> anyhow::Result<Self> {
+        // rows are kept as read, so wrong column counts show as invalid instead of being padded/cut
+        let rows = read_records(path.to_path_buf())?
+            .iter()
+            .map(|r| r.iter().map(String::from).collect())
+            .collect();
+
+        let mut file = Self { path: path.to_path_buf(), kind, errors: Vec::new(), rows, dirty: false };
+        file.validate();
+
+        Ok(file)
+    }
+
+    fn validate(&mut self) {
+        self.errors = self.rows
+            .iter()
+            .map(|r| self.kind.validate(r))
+            .collect();
+    }
+
+    fn is_valid(&self) -> bool {
+        self.errors.iter().all(Option::is_none)
+    }
+}
+
+/// Browser for pending Production and Issue files in the working directory
+#[derive(Debug, Default)]
+pub struct ReadyFileBrowser {
+    pub visible: bool,
+
+    files: Vec<PendingFile>,
+    current: Option<OpenFile>,
+}
+
+impl ReadyFileBrowser {
+    /// Reload the list of pending files, keeping selections
+    pub fn refresh(&mut self) {
+        let mut files = Vec::new();
+
+        for kind in ReadyKind::ALL {
+            for path in glob::glob(kind.pattern()).unwrap().flatten() {
+                let selected = self.files.iter().any(|f| f.path == path && f.selected);
+
+                files.push( PendingFile { path, kind, selected } );
+            }
+        }
+
+        self.files = files;
+    }
+
+    /// Show the browser window, returning messages to log
+    pub fn show(&mut self, ctx: &egui::Context) -> Vec<String> {
+        let mut log = Vec::new();
+        let mut visible = self.visible;
+
+        egui::Window::new("Pending files")
+            .open(&mut visible)
+            .resizable(true)
+            .default_width(800.)
+            .show(ctx, |ui| {
+                egui::SidePanel::left("pending-files")
+                    .resizable(true)
+                    .show_inside(ui, |ui| self.show_files(ui, &mut log));
+
+                egui::CentralPanel::default()
+                    .show_inside(ui, |ui| self.show_current(ui, &mut log));
+            });
+
+        self.visible = visible;
+
+        log
+    }
+
+    fn show_files(&mut self, ui: &mut egui::Ui, log: &mut Vec<String>) {
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                self.refresh();
+            }
+
+            let any_selected = self.files.iter().any(|f| f.selected);
+            if ui.add_enabled(any_selected, egui::Button::new("Move selected")).clicked() {
+                self.move_selected(log);
+            }
+        });
+
+        ui.separator();
+        if self.files.is_empty() {
+            ui.label("No pending files");
+        }
+
+        let mut open = None;
+        for file in &mut self.files {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut file.selected, "");
+
+                let name = file.path.display().to_string();
+                let is_open = self.current.as_ref().is_some_and(|c| c.path == file.path);
+                if ui.selectable_label(is_open, name).clicked() {
+                    open = Some( (file.path.clone(), file.kind) );
+                }
+            });
+        }
+
+        if let Some((path, kind)) = open {
+            if self.current.as_ref().is_some_and(|c| c.dirty) {
+                log.push( format!("Discarded unsaved changes to {}", self.current.as_ref().unwrap().path.display()) );
+            }
+
+            match OpenFile::open(&path, kind) {
+                Ok(file) => self.current = Some(file),
+                Err(e) => log.push( format!("Failed to open {}: {}", path.display(), e) ),
+            }
+        }
+    }
+
+    fn move_selected(&mut self, log: &mut Vec<String>) {
+        for file in self.files.iter().filter(|f| f.selected) {
+            if self.current.as_ref().is_some_and(|c| c.path == file.path && c.dirty) {
+                log.push( format!("Not moving {}: it has unsaved changes", file.path.display()) );
+                continue;
+            }
+
+            match move_file(&file.path) {
+                Ok(_) => log.push( format!("Moved file {}", file.path.display()) ),
+                Err(e) => log.push( format!("Failed to move {}: {}", file.path.display(), e) ),
+            }
+        }
+
+        if self.current.as_ref().is_some_and(|c| !c.path.exists()) {
+            self.current = None;
+        }
+
+        self.refresh();
+    }
+
+    fn show_current(&mut self, ui: &mut egui::Ui, log: &mut Vec<String>) {
+        let file = match &mut self.current {
+            Some(file) => file,
+            None => {
+                ui.label("Select a file to preview");
+                return;
+            }
+        };
+
+        ui.horizontal(|ui| {
+            ui.heading(file.path.display().to_string());
+
+            let can_save = file.dirty && file.is_valid();
+            if ui.add_enabled(can_save, egui::Button::new("Save")).clicked() {
+                match file.kind.save(&file.rows, file.path.clone()) {
+                    Ok(_) => {
+                        file.dirty = false;
+                        log.push( format!("Saved {}", file.path.display()) );
+                    },
+                    Err(e) => log.push( format!("Failed to save {}: {}", file.path.display(), e) ),
+                }
+            }
+
+            if ui.add_enabled(file.dirty, egui::Button::new("Revert")).clicked() {
+                match OpenFile::open(&file.path, file.kind) {
+                    Ok(reverted) => *file = reverted,
+                    Err(e) => log.push( format!("Failed to reopen {}: {}", file.path.display(), e) ),
+                }
+            }
+        });
+
+        let invalid = file.errors.iter().filter(|e| e.is_some()).count();
+        if invalid > 0 {
+            ui.colored_label(INVALID, format!("{} invalid row(s), fix before saving", invalid));
+        }
+
+        let headers = file.kind.headers();
+        let mut changed = false;
+        let mut delete = None;
+        let mut fit = None;
+
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            TableBuilder::new(ui)
+                .striped(true)
+                .resizable(true)
+                .columns(Column::initial(90.).at_least(40.), headers.len())
+                .column(Column::auto())
+                .column(Column::remainder())
+                .header(ROW_HEIGHT, |mut header| {
+                    for name in headers {
+                        header.col(|ui| { ui.strong(*name); });
+                    }
+                    header.col(|_| ());
+                    header.col(|ui| { ui.strong("Error"); });
+                })
+                .body(|body| {
+                    body.rows(ROW_HEIGHT, file.rows.len(), |i, mut row| {
+                        for j in 0..headers.len() {
+                            row.col(|ui| {
+                                if let Some(cell) = file.rows[i].get_mut(j) {
+                                    changed |= ui.add(egui::TextEdit::singleline(cell)).changed();
+                                }
+                            });
+                        }
+
+                        row.col(|ui| {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Delete").clicked() {
+                                    delete = Some(i);
+                                }
+
+                                let cells = &file.rows[i];
+                                if cells.len() != headers.len() {
+                                    let extra = cells.get(headers.len()..).unwrap_or_default().join(", ");
+                                    let hint = if extra.is_empty() {
+                                        format!("Add empty cells up to {} columns", headers.len())
+                                    } else {
+                                        format!("Drop the extra cells: {}", extra)
+                                    };
+
+                                    if ui.small_button("Fit").on_hover_text(hint).clicked() {
+                                        fit = Some(i);
+                                    }
+                                }
+                            });
+                        });
+                        row.col(|ui| {
+                            if let Some(e) = &file.errors[i] {
+                                ui.colored_label(INVALID, e);
+                            }
+                        });
+                    });
+                });
+        });
+
+        if let Some(i) = delete {
+            file.rows.remove(i);
+            changed = true;
+        }
+
+        if let Some(i) = fit {
+            file.rows[i].resize(headers.len(), String::new());
+            changed = true;
+        }
+
+        if changed {
+            file.dirty = true;
+            file.validate();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROD_ROW: &str = "1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t5\tEA\t50W-0008\t\t1001.569\tIN2\tK2\tHS01\t54091";
+
+    fn cells(text: &str) -> Vec<String> {
+        text.split('\t').map(String::from).collect()
+    }
+
+    #[test]
+    fn validate_rows() {
+        let kind = ReadyKind::Production;
+        assert_eq!(kind.validate(&cells(PROD_ROW)), None);
+
+        let extra = cells( &format!("{}\textra", PROD_ROW) );
+        assert_eq!(kind.validate(&extra), Some("Expected 13 columns, found 14".into()));
+
+        let bad_qty = cells( &PROD_ROW.replace("\t5\t", "\tfive\t") );
+        assert!(kind.validate(&bad_qty).is_some());
+    }
+
+    #[test]
+    fn save_rejects_invalid_rows() {
+        let path = std::env::temp_dir().join( format!("sap-error-utils-ready-{}.ready", std::process::id()) );
+        let good = cells(PROD_ROW);
+        let extra = cells( &format!("{}\textra", PROD_ROW) );
+
+        let result = ReadyKind::Production.save(&[good.clone(), extra], path.clone());
+        assert_eq!(result.unwrap_err().to_string(), "row 2: Expected 13 columns, found 14");
+        assert!(!path.exists());
+
+        ReadyKind::Production.save(std::slice::from_ref(&good), path.clone()).unwrap();
+        let saved: Vec<Vec<String>> = read_records(path.clone()).unwrap()
+            .iter()
+            .map(|r| r.iter().map(String::from).collect())
+            .collect();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(saved, vec![good]);
+    }
+}
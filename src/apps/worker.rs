@@ -0,0 +1,419 @@
+//! Background worker for long running GUI jobs
+//!
+//! Jobs run on their own thread and stream [`Event`]s back to the GUI,
+//! which drains them each frame with [`Worker::poll`].
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use chrono::{DateTime, Local};
+use eframe::egui;
+
+use crate::api::OrderData;
+use crate::inbox::{Failure, FailureMatchStatus};
+use crate::inbox::compare::Progress;
+use crate::inbox::consolidate::Consolidation;
+use crate::inbox::validation::Findings;
+use crate::inbox::discovery::{ExportInfo, ExportSearch};
+use crate::inbox::workflow::{move_to_outbound, ArchiveSource, CohvSource, InboxWorkflow, WorkflowOptions};
+use crate::history::History;
+use crate::paths;
+use crate::report::RunReport;
+
+/// Job that can be run by the [`Worker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    /// Generate a production file from the inbox errors and COHV orders
+    Comparison,
+    /// Generate an issue file for all inbox errors
+    IssueAll,
+    /// Generate an issue file correcting the errors in a COGI export
+    IssueCogi,
+}
+
+impl Job {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Comparison => "Generating confirmation file",
+            Self::IssueAll => "Generating issue file",
+            Self::IssueCogi => "Generating COGI issue file",
+        }
+    }
+}
+
+/// Inputs for a job, copied from the GUI when the job is started
+#[derive(Debug, Clone, Default)]
+pub struct JobInput {
+    pub inbox_errors: String,
+    pub files_to_parse: usize,
+    pub auto_move_files: bool,
+    /// Folders searched for COHV exports (defaults if empty)
+    pub cohv_folders: Vec<PathBuf>,
+    /// When the inbox errors were last changed
+    pub inbox_updated: Option<DateTime<Local>>,
+    /// How generated rows are merged and split into files
+    pub consolidation: Consolidation,
+    /// COGI export to correct
+    pub cogi_file: Option<PathBuf>,
+}
+
+impl JobInput {
+    pub(crate) fn workflow(&self) -> InboxWorkflow {
+        let search = match self.cohv_folders.is_empty() {
+            true  => ExportSearch::default(),
+            false => ExportSearch::new(self.cohv_folders.clone()),
+        };
+
+        InboxWorkflow::new(self.inbox_errors.as_str())
+            .archive( ArchiveSource::SapArchive(self.files_to_parse) )
+            .cohv( CohvSource::Search(search) )
+            .options(WorkflowOptions {
+                inbox_updated: self.inbox_updated,
+                history: Some( paths::HISTORY_DB.to_path_buf() ),
+                consolidation: self.consolidation.clone(),
+                ..Default::default()
+            })
+    }
+}
+
+/// Events sent from a running job
+#[derive(Debug)]
+pub enum Event {
+    Log(String),
+    FilesScanned { scanned: usize, total: usize },
+    FailuresMatched { matched: usize, total: usize },
+    OrdersApplied(usize),
+    /// Failures not matched, in inbox error text format
+    NewInbox(String),
+    /// Failures parsed and matched by the job
+    Failures(Vec<Failure>),
+    /// Planned orders (or remainder of) not applied to any failure
+    Orders(Vec<OrderData>),
+    /// COHV export used, with freshness warnings
+    CohvExport { info: ExportInfo, warnings: Vec<String> },
+    /// Files written by the job
+    Generated(Vec<PathBuf>),
+    /// Validation of the generated rows
+    Findings(Findings),
+    /// Results of the job, for exporting
+    Report(RunReport),
+    /// Workflow of a comparison, to write its output again after manual edits
+    Workflow(Box<InboxWorkflow>),
+    /// Job finished, with an error message if it failed
+    Finished(Result<(), String>),
+}
+
+/// Progress of the current job, as accumulated from [`Event`]s
+#[derive(Debug, Default, Clone)]
+pub struct JobProgress {
+    pub files_scanned: usize,
+    pub total_files: usize,
+    pub failures_matched: usize,
+    pub total_failures: usize,
+    pub orders_applied: usize,
+}
+
+impl JobProgress {
+    /// Update progress from an event, returning whether the event was a progress event
+    pub fn update(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::FilesScanned { scanned, total } => {
+                self.files_scanned = scanned;
+                self.total_files = total;
+            },
+            Event::FailuresMatched { matched, total } => {
+                self.failures_matched = matched;
+                self.total_failures = total;
+            },
+            Event::OrdersApplied(applied) => self.orders_applied = applied,
+            _ => return false
+        }
+
+        true
+    }
+
+    /// Fraction of archive files scanned
+    pub fn fraction(&self) -> f32 {
+        match self.total_files {
+            0 => 0.,
+            total => self.files_scanned as f32 / total as f32
+        }
+    }
+}
+
+/// Handle to a job running on a background thread
+#[derive(Debug)]
+pub struct Worker {
+    job: Job,
+    events: Receiver<Event>,
+    cancel: Arc<AtomicBool>,
+    finished: bool,
+}
+
+impl Worker {
+    /// Start a job on a background thread
+    ///
+    /// `ctx` is repainted whenever an event is sent, so progress is shown without user input
+    pub fn spawn(job: Job, input: JobInput, ctx: egui::Context) -> Self {
+        let (tx, events) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let mut reporter = Reporter { tx, ctx: Some(ctx), cancel: Arc::clone(&cancel) };
+        thread::spawn(move || run_job(job, &input, &mut reporter));
+
+        Self { job, events, cancel, finished: false }
+    }
+
+    /// Run a job on the current thread, returning all events it sent
+    pub fn run_blocking(job: Job, input: JobInput) -> Vec<Event> {
+        let (tx, events) = mpsc::channel();
+
+        let mut reporter = Reporter { tx, ctx: None, cancel: Arc::new(AtomicBool::new(false)) };
+        run_job(job, &input, &mut reporter);
+        drop(reporter);
+
+        events.into_iter().collect()
+    }
+
+    pub fn job(&self) -> Job {
+        self.job
+    }
+
+    /// Request the job stop at the next checkpoint
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Whether the job has sent its [`Event::Finished`] event (or the thread died)
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Drain all events sent since the last poll
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => {
+                    if let Event::Finished(_) = event {
+                        self.finished = true;
+                    }
+
+                    events.push(event);
+                },
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    if !self.finished {
+                        self.finished = true;
+                        events.push( Event::Finished(Err("Worker stopped unexpectedly".into())) );
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// Sends job events to the GUI
+struct Reporter {
+    tx: Sender<Event>,
+    ctx: Option<egui::Context>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Reporter {
+    fn send(&self, event: Event) {
+        // receiver is only dropped if the app is closing, so nothing to report to
+        let _ = self.tx.send(event);
+
+        if let Some(ctx) = &self.ctx {
+            ctx.request_repaint();
+        }
+    }
+
+    fn log(&self, msg: impl Into<String>) {
+        self.send(Event::Log(msg.into()));
+    }
+}
+
+impl Progress for Reporter {
+    fn files_scanned(&mut self, scanned: usize, total: usize) {
+        self.send(Event::FilesScanned { scanned, total });
+    }
+
+    fn failures_matched(&mut self, matched: usize, total: usize) {
+        self.send(Event::FailuresMatched { matched, total });
+    }
+
+    fn orders_applied(&mut self, applied: usize) {
+        self.send(Event::OrdersApplied(applied));
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+}
+
+fn run_job(job: Job, input: &JobInput, reporter: &mut Reporter) {
+    let result = match job {
+        Job::Comparison => generate_comparison(input, reporter),
+        Job::IssueAll => issue_all(input, reporter),
+        Job::IssueCogi => issue_cogi(input, reporter),
+    };
+
+    reporter.send( Event::Finished(result.map_err(|e| e.to_string())) );
+}
+
+fn issue_all(input: &JobInput, reporter: &mut Reporter) -> anyhow::Result<()> {
+    let mut workflow = input.workflow();
+    let parsed = workflow.parse()?;
+    for w in &parsed.warnings {
+        reporter.log( format!("Warning: {}", w) );
+    }
+
+    // get confirmation file data
+    workflow.match_confirmations(reporter)?;
+    reporter.send( Event::Failures(workflow.failures().to_vec()) );
+
+    let generated = workflow.write_issue()?;
+    generated.errors.into_iter().for_each(|e| reporter.log(e));
+    reporter.log( format!("Report written to {}", generated.report_file.display()) );
+
+    finish(&mut workflow, input, reporter)?;
+    reporter.send( Event::Report(generated.report) );
+    reporter.send( Event::Workflow(Box::new(workflow)) );
+
+    Ok(())
+}
+
+fn issue_cogi(input: &JobInput, reporter: &mut Reporter) -> anyhow::Result<()> {
+    let cogi_file = input.cogi_file.as_ref().ok_or_else(|| anyhow!("No COGI export selected"))?;
+
+    let mut workflow = input.workflow();
+    let generated = workflow.write_cogi_issue(cogi_file, reporter)?;
+
+    let corrected = generated.report.cogi.iter().filter(|c| c.issue.is_ok()).count();
+    reporter.log( format!("{} of {} COGI errors corrected", corrected, generated.report.cogi.len()) );
+    generated.errors.into_iter().for_each(|e| reporter.log(e));
+    reporter.log( format!("Report written to {}", generated.report_file.display()) );
+
+    finish(&mut workflow, input, reporter)?;
+    reporter.send( Event::Report(generated.report) );
+
+    Ok(())
+}
+
+fn generate_comparison(input: &JobInput, reporter: &mut Reporter) -> anyhow::Result<()> {
+    let mut workflow = input.workflow();
+    let parsed = workflow.parse()?;
+    for w in &parsed.warnings {
+        reporter.log( format!("Warning: {}", w) );
+    }
+
+    // get confirmation file data
+    workflow.match_confirmations(reporter)?;
+
+    // get orders from the newest cohv export
+    let loaded = workflow.load_orders()?;
+    if let Some(info) = loaded.export {
+        reporter.log( format!("Using COHV export {}", info) );
+        for w in &loaded.warnings {
+            reporter.log( format!("Warning: {}", w) );
+        }
+        reporter.send( Event::CohvExport { info, warnings: loaded.warnings } );
+    }
+
+    workflow.apply_orders(reporter)?;
+
+    for f in workflow.failures() {
+        match f.status() {
+            FailureMatchStatus::NoConfirmationRow => {
+                reporter.log( format!("{}\t<{}, {}> has no confirmation row", f.mark, f.wbs, f.program) );
+            },
+            FailureMatchStatus::NotEnoughOrdersApplied(qty) => {
+                reporter.log( format!("{}\t<{}, {}> missing orders for qty of {}/{}", f.mark, f.wbs, f.program, qty, f.qty) );
+            },
+            _ => ()
+        }
+    }
+
+    let generated = workflow.write_production()?;
+    generated.errors.iter().for_each(|e| reporter.log(e));
+    generated.findings.lines().into_iter().for_each(|l| reporter.log(l));
+    if generated.findings.has_errors() {
+        reporter.log("Production and transfer files not written: confirmation rows have validation errors");
+    }
+    if !generated.report.transfers.is_empty() {
+        reporter.log( format!("{} plant to plant transfer(s) written, to be posted before production", generated.report.transfers.len()) );
+    }
+    reporter.send( Event::Findings(generated.findings.clone()) );
+    reporter.log( format!("Report written to {}", generated.report_file.display()) );
+
+    reporter.send( Event::NewInbox(generated.new_inbox.join("\n")) );
+    reporter.send( Event::Failures(workflow.failures().to_vec()) );
+    reporter.send( Event::Orders(workflow.not_applied().to_vec()) );
+    if !generated.files.is_empty() {
+        reporter.send( Event::Generated(generated.files) );
+    }
+
+    finish(&mut workflow, input, reporter)?;
+    reporter.send( Event::Report(generated.report) );
+
+    Ok(())
+}
+
+/// Move the generated files (if set to) and record the run
+///
+/// failing to record the run is logged, since the generated files are already written
+fn finish(workflow: &mut InboxWorkflow, input: &JobInput, reporter: &mut Reporter) -> io::Result<()> {
+    if input.auto_move_files {
+        for file in workflow.move_generated()? {
+            reporter.log( format!("Moved file {}", file.display()) );
+        }
+    }
+
+    if let Err(e) = workflow.record_history() {
+        reporter.log( format!("Failed to record run history: {}", e) );
+    }
+
+    Ok(())
+}
+
+/// Move files matching a glob pattern in the working directory to SAP outbound
+///
+/// returns the files moved
+pub fn move_files(pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let mut moved = Vec::new();
+
+    for file in glob::glob(pattern).unwrap().flatten() {
+        move_file(&file)?;
+
+        moved.push(file);
+    }
+
+    Ok(moved)
+}
+
+/// Move a single file to SAP outbound
+///
+/// the move is recorded against the run that wrote the file, if it is in the history
+pub fn move_file(file: &Path) -> io::Result<()> {
+    let to = move_to_outbound(file)?;
+
+    // history is informational, the file has already moved
+    let _ = History::open_default().and_then(|h| h.record_moved(file, &to));
+
+    Ok(())
+}
@@ -1,4 +1,77 @@
 
-fn main() {
-    println!("Not impl yet");
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+
+use sap_error_utils::apps::{BotConfig, InboxBot};
+use sap_error_utils::inbox::consolidate::Consolidation;
+use sap_error_utils::paths;
+
+/// Watch a drop folder for inbox error and COHV exports and generate confirmation files
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Folder to watch for inbox error (*.txt) and COHV (workbook, list, TSV or CSV) exports
+    drop_folder: PathBuf,
+
+    /// Folder to write generated files and reports to [default: {drop_folder}/staging]
+    #[arg(short, long)]
+    staging: Option<PathBuf>,
+
+    /// Seconds between polls of the drop folder
+    #[arg(short, long, default_value_t = 60)]
+    interval: u64,
+
+    /// Number of archived production files to search
+    #[arg(short, long, default_value_t = 200)]
+    files: usize,
+
+    /// Move generated files to outbound when every failure matched with no ambiguity
+    #[arg(short, long)]
+    auto_move: bool,
+
+    /// Run history database [default: history.db in the working directory]
+    #[arg(long)]
+    history: Option<PathBuf>,
+
+    /// Merge generated rows with the same mark, WBS, material, plant and program
+    #[arg(long)]
+    merge: bool,
+
+    /// Write a separate file for each plant
+    #[arg(long)]
+    split_plants: bool,
+
+    /// Maximum rows per generated file
+    #[arg(long)]
+    max_rows: Option<usize>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let config = BotConfig {
+        staging: args.staging.unwrap_or_else(|| args.drop_folder.join("staging")),
+        drop_folder: args.drop_folder,
+        poll_interval: Duration::from_secs(args.interval.max(1)),
+        files_to_parse: args.files,
+        auto_move_files: args.auto_move,
+        history: args.history.unwrap_or_else(|| paths::HISTORY_DB.to_path_buf()),
+        consolidation: Consolidation {
+            merge: args.merge,
+            split_by_plant: args.split_plants,
+            max_rows: args.max_rows,
+        },
+    };
+
+    let bot = InboxBot::new(config);
+
+    let shutdown = bot.shutdown_handle();
+    ctrlc::set_handler(move || {
+        eprintln!("Shutdown requested, finishing current poll...");
+        shutdown.store(true, std::sync::atomic::Ordering::SeqCst);
+    })?;
+
+    bot.run()
 }
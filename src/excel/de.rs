@@ -0,0 +1,416 @@
+//! Serde deserialization of worksheet rows
+//!
+//! Rows are deserialized as a map of header text to cell, so struct fields
+//! are matched to columns by their serde `rename` and `alias` attributes.
+
+use std::cell::Cell;
+use std::fmt::{self, Display};
+
+use calamine::DataType;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::Deserializer;
+
+/// Error deserializing a worksheet row
+#[derive(Debug)]
+pub enum DeError {
+    /// A required field had no matching header column
+    MissingColumn(&'static str),
+    Custom(String),
+}
+
+impl Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingColumn(col) => write!(f, "missing column `{}`", col),
+            Self::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Self::MissingColumn(field)
+    }
+}
+
+type Result<T> = std::result::Result<T, DeError>;
+
+/// Convert an Excel date serial number to a date and time
+///
+/// Excel (1900 date system) counts days from 1899-12-30,
+/// with the fractional part being the time of day.
+pub fn serial_to_datetime(serial: f64) -> Option<NaiveDateTime> {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30)?.and_hms_opt(0, 0, 0)?;
+    let ms = (serial * 86_400_000.).round() as i64;
+
+    epoch.checked_add_signed(Duration::milliseconds(ms))
+}
+
+/// Deserialize a date from an Excel serial number or a date string
+///
+/// for date columns that are not formatted as dates in the worksheet
+pub fn deserialize_date<'de, D>(deserializer: D) -> std::result::Result<NaiveDate, D::Error>
+    where D: Deserializer<'de>
+{
+    struct DateVisitor;
+
+    impl<'de> Visitor<'de> for DateVisitor {
+        type Value = NaiveDate;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a date or Excel date serial number")
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> std::result::Result<Self::Value, E> {
+            serial_to_datetime(v)
+                .map(|dt| dt.date())
+                .ok_or_else(|| E::custom(format!("invalid date serial {}", v)))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Self::Value, E> {
+            self.visit_f64(v as f64)
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Self::Value, E> {
+            self.visit_f64(v as f64)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Self::Value, E> {
+            let v = v.trim();
+
+            // ISO date, optionally with time, as produced for date formatted cells
+            let date = v.get(..10).unwrap_or(v);
+            ["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y"]
+                .iter()
+                .find_map(|fmt| NaiveDate::parse_from_str(date, fmt).ok())
+                .or_else(|| v.parse::<f64>().ok().and_then(serial_to_datetime).map(|dt| dt.date()))
+                .ok_or_else(|| E::custom(format!("invalid date `{}`", v)))
+        }
+    }
+
+    deserializer.deserialize_any(DateVisitor)
+}
+
+/// Deserializes a row as a map of header text to cell value
+pub struct RowDeserializer<'a> {
+    header: &'a [String],
+    row: &'a [DataType],
+}
+
+impl<'a> RowDeserializer<'a> {
+    pub fn new(header: &'a [String], row: &'a [DataType]) -> Self {
+        Self { header, row }
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for RowDeserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map( RowMapAccess { cells: self.header.iter().zip(self.row.iter()), value: None } )
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct RowMapAccess<'a, I> {
+    cells: I,
+    value: Option<&'a DataType>,
+}
+
+impl<'de, 'a, I> MapAccess<'de> for RowMapAccess<'a, I>
+    where I: Iterator<Item = (&'a String, &'a DataType)>
+{
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        // columns without a header are skipped
+        for (key, value) in self.cells.by_ref() {
+            if key.is_empty() {
+                continue;
+            }
+
+            self.value = Some(value);
+            return seed.deserialize(key.as_str().into_deserializer()).map(Some);
+        }
+
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(CellDeserializer(value)),
+            None => Err( de::Error::custom("value requested before key") )
+        }
+    }
+}
+
+/// Deserializes a single cell, with conversions between cell types
+///
+/// - whole floats as integers (and integer strings)
+/// - numbers as strings, whole floats without a decimal
+/// - date cells as ISO 8601 strings (for [`chrono`] types)
+/// - empty cells and blank strings as `None`
+pub struct CellDeserializer<'a>(pub &'a DataType);
+
+impl<'a> CellDeserializer<'a> {
+    fn error(&self, expected: &str) -> DeError {
+        DeError::Custom( format!("expected {}, found {:?}", expected, self.0) )
+    }
+
+    fn as_i64(&self) -> Result<i64> {
+        match self.0 {
+            DataType::Int(v) => Ok(*v),
+            DataType::Float(v) | DataType::DateTime(v) if v.fract() == 0. => Ok(*v as i64),
+            DataType::String(s) => s.trim().parse().map_err(|_| self.error("an integer")),
+            _ => Err( self.error("an integer") )
+        }
+    }
+
+    fn as_f64(&self) -> Result<f64> {
+        match self.0 {
+            DataType::Int(v) => Ok(*v as f64),
+            DataType::Float(v) | DataType::DateTime(v) => Ok(*v),
+            DataType::String(s) => s.trim().parse().map_err(|_| self.error("a number")),
+            _ => Err( self.error("a number") )
+        }
+    }
+
+    fn as_string(&self) -> Result<String> {
+        match self.0 {
+            DataType::String(s) => Ok(s.clone()),
+            DataType::Int(v) => Ok(v.to_string()),
+            DataType::Float(v) if v.fract() == 0. => Ok((*v as i64).to_string()),
+            DataType::Float(v) => Ok(v.to_string()),
+            DataType::Bool(v) => Ok(v.to_string()),
+            DataType::DateTime(v) => {
+                let dt = serial_to_datetime(*v).ok_or_else(|| self.error("a date"))?;
+
+                match v.fract() == 0. {
+                    true  => Ok(dt.date().format("%Y-%m-%d").to_string()),
+                    false => Ok(dt.format("%Y-%m-%dT%H:%M:%S").to_string()),
+                }
+            },
+            DataType::Empty => Ok(String::new()),
+            DataType::Error(e) => Err( DeError::Custom(format!("cell error {:?}", e)) ),
+        }
+    }
+}
+
+macro_rules! deserialize_int {
+    ($($method:ident => $visit:ident as $ty:ty),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                visitor.$visit(self.as_i64()? as $ty)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for CellDeserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            DataType::Int(v) => visitor.visit_i64(*v),
+            DataType::Float(v) | DataType::DateTime(v) => visitor.visit_f64(*v),
+            DataType::String(s) => visitor.visit_str(s),
+            DataType::Bool(v) => visitor.visit_bool(*v),
+            DataType::Empty => visitor.visit_unit(),
+            DataType::Error(e) => Err( DeError::Custom(format!("cell error {:?}", e)) ),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            DataType::Bool(v) => visitor.visit_bool(*v),
+            DataType::String(s) => match s.trim().to_lowercase().as_str() {
+                "true" | "x" | "yes" => visitor.visit_bool(true),
+                "false" | "" | "no"  => visitor.visit_bool(false),
+                _ => Err( self.error("a boolean") )
+            },
+            DataType::Empty => visitor.visit_bool(false),
+            _ => visitor.visit_bool(self.as_f64()? != 0.)
+        }
+    }
+
+    deserialize_int! {
+        deserialize_i8  => visit_i64 as i64,
+        deserialize_i16 => visit_i64 as i64,
+        deserialize_i32 => visit_i64 as i64,
+        deserialize_i64 => visit_i64 as i64,
+        deserialize_u8  => visit_i64 as i64,
+        deserialize_u16 => visit_i64 as i64,
+        deserialize_u32 => visit_i64 as i64,
+        deserialize_u64 => visit_i64 as i64
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(self.as_f64()?)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(self.as_f64()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.as_string()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            DataType::Empty => visitor.visit_none(),
+            DataType::String(s) if s.trim().is_empty() => visitor.visit_none(),
+            _ => visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        let variant = self.as_string()?;
+        visitor.visit_enum(variant.trim().into_deserializer())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+/// Every required field of `T` with no column in `header`, in field order
+///
+/// serde stops at the first missing field, so each one found is given a
+/// placeholder column to find the next. Placeholder values are an empty cell,
+/// a number, a date, then the cells of `row` (a row read with `header`); if none
+/// deserializes for a field, the fields after it are not listed.
+pub fn missing_columns<T: DeserializeOwned>(header: &[String], row: &[DataType]) -> Vec<&'static str> {
+    let candidates: Vec<DataType> = [DataType::Empty, DataType::Int(0), DataType::DateTime(1.)]
+        .into_iter()
+        .chain(row.iter().cloned())
+        .collect();
+
+    let mut header = header.to_vec();
+    let mut row = row.to_vec();
+    let mut missing = Vec::new();
+
+    while let Err(DeError::MissingColumn(col)) = T::deserialize(RowDeserializer::new(&header, &row)) {
+        missing.push(col);
+        header.push(col.to_string());
+        row.push(DataType::Empty);
+
+        let placeholder = candidates.iter().find(|cell| {
+            *row.last_mut().unwrap() = (*cell).clone();
+            !matches!(T::deserialize(RowDeserializer::new(&header, &row)), Err(DeError::Custom(_)))
+        });
+
+        if placeholder.is_none() {
+            break;
+        }
+    }
+
+    missing
+}
+
+/// Whether a column with header text `header` is matched by a field of `T`
+///
+/// Deserializes `T` from a map with the single key `header`, noting
+/// whether the value was requested as a field or ignored.
+pub fn matches_field<T: DeserializeOwned>(header: &str) -> bool {
+    let matched = Cell::new(false);
+
+    // always fails, either on the value or on missing fields
+    let _ = T::deserialize(ProbeDeserializer { key: header, matched: &matched });
+
+    matched.get()
+}
+
+struct ProbeDeserializer<'a> {
+    key: &'a str,
+    matched: &'a Cell<bool>,
+}
+
+impl<'de, 'a> Deserializer<'de> for ProbeDeserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map( ProbeMapAccess { key: Some(self.key), matched: self.matched } )
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct ProbeMapAccess<'a> {
+    key: Option<&'a str>,
+    matched: &'a Cell<bool>,
+}
+
+impl<'de, 'a> MapAccess<'de> for ProbeMapAccess<'a> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.key.take() {
+            Some(key) => seed.deserialize(key.into_deserializer()).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize( ProbeValue(self.matched) )
+    }
+}
+
+/// Value of a probed column: ignored if no field matched the key
+struct ProbeValue<'a>(&'a Cell<bool>);
+
+impl<'de, 'a> Deserializer<'de> for ProbeValue<'a> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        self.0.set(true);
+
+        Err( DeError::Custom("probe".into()) )
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier
+    }
+}
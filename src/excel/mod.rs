@@ -0,0 +1,451 @@
+//! Reading worksheet tables into serde types
+//!
+//! Columns are matched to struct fields by header text,
+//! using the field's serde `rename` and `alias` attributes.
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! struct Row {
+//!     #[serde(rename = "Material Number", alias = "Material")]
+//!     matl: String,
+//!     #[serde(rename = "Order quantity (GMEIN)")]
+//!     qty: u32,
+//!     // optional columns, or empty cells, are `None`
+//!     #[serde(rename = "Plant")]
+//!     plant: Option<String>,
+//! }
+//!
+//! let rows = XlsxTableReader::<Row>::new().read_file(path)?;
+//! ```
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use calamine::{Reader, open_workbook, open_workbook_auto, Xls, Xlsx, Xlsb, Ods, Sheets, DataType, Range};
+use serde::de::DeserializeOwned;
+
+mod de;
+pub mod writer;
+pub use de::{CellDeserializer, DeError, RowDeserializer, deserialize_date, matches_field, missing_columns, serial_to_datetime};
+
+/// Extensions of spreadsheet files that can be read
+pub const SPREADSHEET_EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xls", "xlsb", "ods"];
+
+/// Rows scanned for the header when auto-detecting it
+const HEADER_SCAN_ROWS: usize = 20;
+
+/// Worksheet to read the table from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sheet {
+    Index(usize),
+    Name(String),
+}
+
+impl Default for Sheet {
+    fn default() -> Self {
+        Self::Index(0)
+    }
+}
+
+impl From<usize> for Sheet {
+    fn from(value: usize) -> Self {
+        Self::Index(value)
+    }
+}
+
+impl From<&str> for Sheet {
+    fn from(value: &str) -> Self {
+        Self::Name(value.into())
+    }
+}
+
+/// Location of the header row
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeaderRow {
+    /// Scan the first rows for the one matching the most columns
+    #[default]
+    Auto,
+    /// Row index, relative to the start of the used range
+    Index(usize),
+}
+
+/// Maps header text to the name used for deserialization
+type HeaderMap = Box<dyn Fn(&str) -> Option<String>>;
+
+pub struct XlsxTableReader<T> {
+    sheet: Sheet,
+    header_row: HeaderRow,
+    header_map: Option<HeaderMap>,
+    header: Vec<String>,
+    matched: Vec<bool>,
+    _row: PhantomData<T>,
+}
+
+impl<T> Default for XlsxTableReader<T> {
+    fn default() -> Self {
+        Self {
+            sheet: Sheet::default(),
+            header_row: HeaderRow::default(),
+            header_map: None,
+            header: Vec::new(),
+            matched: Vec::new(),
+            _row: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for XlsxTableReader<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XlsxTableReader")
+            .field("sheet", &self.sheet)
+            .field("header_row", &self.header_row)
+            .field("header", &self.header)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> XlsxTableReader<T>
+    where
+        T: DeserializeOwned
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read from the worksheet with the given name or index (default: first sheet)
+    pub fn sheet(mut self, sheet: impl Into<Sheet>) -> Self {
+        self.sheet = sheet.into();
+        self
+    }
+
+    /// Set the header row location (default: auto-detect)
+    pub fn header_row(mut self, header_row: HeaderRow) -> Self {
+        self.header_row = header_row;
+        self
+    }
+
+    /// Rename header columns before matching them to fields
+    ///
+    /// Columns the map returns `None` for keep their header text.
+    /// Used where header titles are configurable, such as per SAP language.
+    pub fn map_header(mut self, map: impl Fn(&str) -> Option<String> + 'static) -> Self {
+        self.header_map = Some(Box::new(map));
+        self
+    }
+
+    /// Header text of each column, in column order (after [`Self::map_header`])
+    pub fn header(&self) -> &[String] {
+        &self.header
+    }
+
+    fn header_text(&self, cell: &DataType) -> String {
+        let text = header_text(cell);
+
+        match &self.header_map {
+            Some(map) => map(&text).unwrap_or(text),
+            None => text
+        }
+    }
+
+    pub fn parse_header(&mut self, row: &[DataType]) {
+        self.header = Vec::with_capacity(row.len());
+        for cell in row {
+            // first column wins if several have the same header
+            let text = self.header_text(cell);
+            match self.header.contains(&text) {
+                true  => self.header.push(String::new()),
+                false => self.header.push(text),
+            }
+        }
+
+        self.matched = self.header
+            .iter()
+            .map(|h| !h.is_empty() && matches_field::<T>(h))
+            .collect();
+    }
+
+    pub fn parse_row(&self, row: &[DataType]) -> Result<T, DeError> {
+        T::deserialize(RowDeserializer::new(&self.header, row))
+    }
+
+    /// Index of the row, within the first [`HEADER_SCAN_ROWS`], that matches the most fields
+    fn find_header(&self, rows: &[&[DataType]]) -> Option<usize> {
+        rows.iter()
+            .take(HEADER_SCAN_ROWS)
+            .map(|row| {
+                row.iter()
+                    .map(|c| self.header_text(c))
+                    .filter(|h| !h.is_empty() && matches_field::<T>(h))
+                    .count()
+            })
+            .enumerate()
+            .filter(|&(_, count)| count > 0)
+            // first row with the highest count
+            .min_by_key(|&(i, count)| (std::cmp::Reverse(count), i))
+            .map(|(i, _)| i)
+    }
+
+    /// Whether a row is filler or a total row: most matched columns are empty
+    fn is_filler(&self, row: &[DataType]) -> bool {
+        let matched = self.matched.iter().filter(|m| **m).count();
+        let filled = row.iter()
+            .zip(&self.matched)
+            .filter(|(cell, matched)| **matched && !is_blank(cell))
+            .count();
+
+        filled * 2 < matched
+    }
+
+    /// Read a table from a range
+    ///
+    /// Fails if a required field has no header column.
+    /// Empty rows, and total rows with most matched columns empty, are skipped.
+    pub fn read_range(&mut self, rng: &Range<DataType>) -> anyhow::Result<Vec<anyhow::Result<T>>> {
+        let rows: Vec<&[DataType]> = rng.rows().collect();
+
+        let header = match self.header_row {
+            HeaderRow::Index(i) => i,
+            HeaderRow::Auto => match self.find_header(&rows) {
+                Some(i) => i,
+                None => return Err( anyhow!("No header row found in the first {} rows", HEADER_SCAN_ROWS) )
+            }
+        };
+
+        match rows.get(header) {
+            Some(row) => self.parse_header(row),
+            None => return Err( anyhow!("Worksheet has no row {}", header + 1) )
+        }
+
+        let mut results = Vec::new();
+        for row in &rows[header + 1..] {
+            if row.iter().all(is_blank) || self.is_filler(row) {
+                continue;
+            }
+
+            match self.parse_row(row) {
+                Ok(val) => results.push(Ok(val)),
+                // every row has the same columns, so no row will match
+                Err(DeError::MissingColumn(_)) => {
+                    let missing = missing_columns::<T>(&self.header, row)
+                        .iter()
+                        .map(|col| format!("`{}`", col))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    return Err( anyhow!("Not all header columns matched. Missing columns: {}", missing) );
+                },
+                Err(e) => results.push(Err(e.into())),
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Read a table from a `.xlsx`, `.xlsm`, `.xls`, `.xlsb` or `.ods` file
+    pub fn read_file(&mut self, path: PathBuf) -> anyhow::Result<Vec<anyhow::Result<T>>> {
+        let mut wb = open_spreadsheet(&path)?;
+
+        let rng = match &self.sheet {
+            Sheet::Index(i) => wb.worksheet_range_at(*i),
+            Sheet::Name(name) => wb.worksheet_range(name),
+        };
+
+        match rng {
+            Some(rng) => self.read_range(&rng?),
+            None => Err( anyhow!("Workbook has no worksheet {:?}", self.sheet) )
+        }
+    }
+}
+
+/// Open a spreadsheet, detecting the format from its extension or content
+///
+/// Files are sometimes saved with the wrong extension (or none), so if opening
+/// by extension fails the format is detected from the file signature.
+pub fn open_spreadsheet(path: &Path) -> anyhow::Result<Sheets<BufReader<File>>> {
+    let by_ext = match open_workbook_auto(path) {
+        Ok(wb) => return Ok(wb),
+        Err(e) => e,
+    };
+
+    let mut magic = [0u8; 4];
+    File::open(path)?.read_exact(&mut magic)
+        .map_err(|e| anyhow!("failed to open file {}: {}", path.display(), e))?;
+
+    let wb = match magic {
+        // OLE compound file: legacy excel
+        [0xD0, 0xCF, 0x11, 0xE0] => open_workbook::<Xls<_>, _>(path).map(Sheets::Xls).ok(),
+        // zip archive: xlsx, xlsb or ods
+        [b'P', b'K', 3, 4] => open_workbook::<Xlsx<_>, _>(path).map(Sheets::Xlsx).ok()
+            .or_else(|| open_workbook::<Xlsb<_>, _>(path).map(Sheets::Xlsb).ok())
+            .or_else(|| open_workbook::<Ods<_>, _>(path).map(Sheets::Ods).ok()),
+        _ => None
+    };
+
+    wb.ok_or_else(|| anyhow!("failed to open file {}: {}", path.display(), by_ext))
+}
+
+/// Header text of a cell, tolerating non-string cells
+fn header_text(cell: &DataType) -> String {
+    match cell {
+        DataType::String(s) => s.trim().to_string(),
+        DataType::Empty => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn is_blank(cell: &DataType) -> bool {
+    match cell {
+        DataType::Empty => true,
+        DataType::String(s) => s.trim().is_empty(),
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Row {
+        #[serde(rename = "Order")]
+        id: u32,
+        #[serde(rename = "Material Number", alias = "Material")]
+        matl: String,
+        #[serde(rename = "Qty")]
+        qty: u32,
+        #[serde(rename = "Basic start date")]
+        start: NaiveDate,
+        #[serde(rename = "Finish", deserialize_with = "deserialize_date")]
+        finish: NaiveDate,
+        #[serde(rename = "Plant")]
+        plant: Option<String>,
+    }
+
+    fn range(rows: Vec<Vec<DataType>>) -> Range<DataType> {
+        let mut rng = Range::new((0, 0), (rows.len() as u32 - 1, rows[0].len() as u32 - 1));
+        for (r, row) in rows.into_iter().enumerate() {
+            for (c, cell) in row.into_iter().enumerate() {
+                rng.set_value((r as u32, c as u32), cell);
+            }
+        }
+
+        rng
+    }
+
+    fn s(val: &str) -> DataType {
+        DataType::String(val.into())
+    }
+
+    #[test]
+    fn deserialize_rows() {
+        let rng = range(vec![
+            vec![s("Order"), s("Material"), s("Qty"), s("Basic start date"), s("Finish"), s("Plant"), s("Extra")],
+            vec![DataType::Float(1234567.), s("1200248A-X1A"), DataType::Float(3.), DataType::DateTime(44927.), DataType::Float(44928.), s("HS01"), s("x")],
+            vec![s("1234568"), DataType::Int(1001), DataType::Int(2), s("2023-01-02"), s("01/03/2023"), DataType::Empty, DataType::Empty],
+            vec![DataType::Empty; 7],
+        ]);
+
+        let rows: Vec<Row> = XlsxTableReader::new().read_range(&rng).unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(rows, vec![
+            Row {
+                id: 1234567,
+                matl: "1200248A-X1A".into(),
+                qty: 3,
+                start: NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                finish: NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(),
+                plant: Some("HS01".into())
+            },
+            Row {
+                id: 1234568,
+                matl: "1001".into(),
+                qty: 2,
+                start: NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(),
+                finish: NaiveDate::from_ymd_opt(2023, 1, 3).unwrap(),
+                plant: None
+            },
+        ]);
+    }
+
+    #[test]
+    fn missing_column() {
+        let rng = range(vec![
+            vec![s("Order"), s("Material"), s("Basic start date"), s("Finish")],
+            vec![s("1234567"), s("1200248A-X1A"), s("2023-01-02"), s("2023-01-02")],
+        ]);
+
+        let err = XlsxTableReader::<Row>::new().read_range(&rng).unwrap_err();
+        assert_eq!(err.to_string(), "Not all header columns matched. Missing columns: `Qty`");
+    }
+
+    #[test]
+    fn missing_columns_all_listed() {
+        let rng = range(vec![
+            vec![s("Order"), s("Material"), s("Basic start date")],
+            vec![s("1234567"), s("1200248A-X1A"), s("2023-01-02")],
+        ]);
+
+        let err = XlsxTableReader::<Row>::new().read_range(&rng).unwrap_err();
+        assert_eq!(err.to_string(), "Not all header columns matched. Missing columns: `Qty`, `Finish`");
+
+        // optional columns are not required
+        let header: Vec<String> = ["Order", "Material Number"].map(String::from).to_vec();
+        assert_eq!(missing_columns::<Row>(&header, &[s("1234567"), s("1200248A-X1A")]), ["Qty", "Basic start date", "Finish"]);
+    }
+
+    #[test]
+    fn row_errors() {
+        let rng = range(vec![
+            vec![s("Order"), s("Material"), s("Qty"), s("Basic start date"), s("Finish")],
+            vec![s("1234567"), s("1200248A-X1A"), DataType::Float(1.5), s("2023-01-02"), s("2023-01-02")],
+            vec![s("1234568"), s("1200248A-X1B"), DataType::Float(1.), s("2023-01-02"), s("2023-01-02")],
+        ]);
+
+        let rows = XlsxTableReader::<Row>::new().read_range(&rng).unwrap();
+        assert!(rows[0].is_err());
+        assert!(rows[1].is_ok());
+    }
+
+    #[test]
+    fn auto_detect_header() {
+        let rng = range(vec![
+            vec![s("Order Info List"), DataType::Empty, DataType::Empty, DataType::Empty, DataType::Empty],
+            vec![DataType::Float(2023.), s("Plant HS01"), DataType::Empty, DataType::Empty, DataType::Empty],
+            vec![s("Order"), s("Material Number"), s("Qty"), s("Basic start date"), s("Finish")],
+            vec![s("1234567"), s("1200248A-X1A"), DataType::Float(2.), s("2023-01-02"), s("2023-01-02")],
+            vec![DataType::Empty; 5],
+            vec![DataType::Empty, DataType::Empty, DataType::Float(2.), DataType::Empty, DataType::Empty],
+        ]);
+
+        let mut reader = XlsxTableReader::<Row>::new();
+        let rows = reader.read_range(&rng).unwrap();
+        assert_eq!(reader.header()[0], "Order");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].as_ref().unwrap().id, 1234567);
+
+        let err = XlsxTableReader::<Row>::new()
+            .header_row(HeaderRow::Index(1))
+            .read_range(&rng)
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Not all header columns matched"));
+    }
+
+    #[test]
+    fn open_error_has_cause() {
+        let path = std::env::temp_dir().join("sap-error-utils-not-a-workbook.xls");
+        std::fs::write(&path, "Order\tMaterial\n").unwrap();
+
+        let err = XlsxTableReader::<Row>::new().read_file(path.clone()).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        let msg = err.to_string();
+        assert!(msg.starts_with("failed to open file"));
+        assert!(msg.len() > format!("failed to open file {}: ", path.display()).len());
+    }
+}
@@ -0,0 +1,303 @@
+//! Minimal xlsx workbook writer
+//!
+//! Writes inline string and number cells, with a bold frozen header row,
+//! column widths and number formats for quantities and areas.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Cell value and format
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    /// Whole number, formatted `#,##0`
+    Int(i64),
+    /// Quantity, formatted `#,##0.000`
+    Qty(f64),
+    /// Area, formatted `#,##0.00`
+    Area(f64),
+}
+
+impl Cell {
+    /// Style index in [`STYLES`]
+    fn style(&self) -> usize {
+        match self {
+            Self::Empty | Self::Text(_) => 0,
+            Self::Int(_)  => 2,
+            Self::Qty(_)  => 3,
+            Self::Area(_) => 4,
+        }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Self::Text(value.into())
+    }
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<u32> for Cell {
+    fn from(value: u32) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl From<u64> for Cell {
+    fn from(value: u64) -> Self {
+        Self::Int(value as i64)
+    }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Empty, Into::into)
+    }
+}
+
+/// Worksheet with a header row
+#[derive(Debug, Clone)]
+pub struct Worksheet {
+    name: String,
+    header: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Worksheet {
+    pub fn push_row(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Column widths, in characters, fitted to the content
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self.header.iter().map(|h| h.len()).collect();
+
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                let len = match cell {
+                    Cell::Empty => 0,
+                    Cell::Text(s) => s.chars().count(),
+                    Cell::Int(v) => v.to_string().len() + 2,
+                    Cell::Qty(v) | Cell::Area(v) => format!("{:.3}", v).len() + 2,
+                };
+
+                match widths.get_mut(i) {
+                    Some(w) => *w = (*w).max(len),
+                    None => widths.push(len),
+                }
+            }
+        }
+
+        widths.into_iter().map(|w| w.clamp(6, 60) + 2).collect()
+    }
+
+    fn xml(&self) -> String {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+<sheetPr><pageSetUpPr fitToPage="1"/></sheetPr>
+<sheetViews><sheetView workbookViewId="0"><pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/></sheetView></sheetViews>
+<cols>"#);
+
+        for (i, width) in self.widths().iter().enumerate() {
+            xml.push_str(&format!(r#"<col min="{0}" max="{0}" width="{1}" customWidth="1"/>"#, i + 1, width));
+        }
+        xml.push_str("</cols><sheetData>");
+
+        let header = self.header.iter().map(|h| Cell::Text(h.clone())).collect::<Vec<_>>();
+        push_row_xml(&mut xml, 1, &header, Some(1));
+        for (i, row) in self.rows.iter().enumerate() {
+            push_row_xml(&mut xml, i + 2, row, None);
+        }
+
+        xml.push_str("</sheetData>");
+        if !self.header.is_empty() {
+            xml.push_str(&format!(r#"<autoFilter ref="A1:{}{}"/>"#, column_name(self.header.len() - 1), self.rows.len() + 1));
+        }
+        xml.push_str(r#"<pageMargins left="0.5" right="0.5" top="0.75" bottom="0.75" header="0.3" footer="0.3"/>"#);
+        xml.push_str(r#"<pageSetup orientation="landscape" fitToWidth="1" fitToHeight="0"/>"#);
+        xml.push_str("</worksheet>");
+
+        xml
+    }
+}
+
+fn push_row_xml(xml: &mut String, row: usize, cells: &[Cell], style: Option<usize>) {
+    xml.push_str(&format!(r#"<row r="{}">"#, row));
+
+    for (col, cell) in cells.iter().enumerate() {
+        let r = format!("{}{}", column_name(col), row);
+        let s = style.unwrap_or_else(|| cell.style());
+
+        match cell {
+            Cell::Empty => continue,
+            Cell::Text(text) => xml.push_str(&format!(r#"<c r="{}" s="{}" t="inlineStr"><is><t xml:space="preserve">{}</t></is></c>"#, r, s, escape(text))),
+            Cell::Int(v) => xml.push_str(&format!(r#"<c r="{}" s="{}"><v>{}</v></c>"#, r, s, v)),
+            Cell::Qty(v) | Cell::Area(v) => xml.push_str(&format!(r#"<c r="{}" s="{}"><v>{}</v></c>"#, r, s, v)),
+        }
+    }
+
+    xml.push_str("</row>");
+}
+
+/// Column letters for a 0-based column index (`0` -> `A`, `26` -> `AA`)
+fn column_name(mut col: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (col % 26) as u8);
+        if col < 26 {
+            break;
+        }
+        col = col / 26 - 1;
+    }
+
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Styles: 0 default, 1 bold header, 2 integer, 3 quantity, 4 area
+const STYLES: &str = r##"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main">
+<numFmts count="2"><numFmt numFmtId="164" formatCode="#,##0.000"/><numFmt numFmtId="165" formatCode="#,##0.00"/></numFmts>
+<fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts>
+<fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills>
+<borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders>
+<cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs>
+<cellXfs count="5">
+<xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/>
+<xf numFmtId="0" fontId="1" fillId="0" borderId="0" xfId="0" applyFont="1"/>
+<xf numFmtId="3" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/>
+<xf numFmtId="164" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/>
+<xf numFmtId="165" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/>
+</cellXfs>
+<cellStyles count="1"><cellStyle name="Normal" xfId="0" builtinId="0"/></cellStyles>
+</styleSheet>"##;
+
+/// Workbook of [`Worksheet`]s, written with [`Workbook::save`]
+#[derive(Debug, Clone, Default)]
+pub struct Workbook {
+    sheets: Vec<Worksheet>,
+}
+
+impl Workbook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a worksheet with a header row
+    ///
+    /// names are truncated to excel's limit of 31 characters, without the characters excel forbids
+    pub fn add_sheet(&mut self, name: &str, header: &[&str]) -> &mut Worksheet {
+        self.sheets.push(Worksheet {
+            name: name.chars().filter(|c| !"[]:*?/\\".contains(*c)).take(31).collect(),
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        });
+
+        self.sheets.last_mut().unwrap()
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut zip = ZipWriter::new(File::create(path)?);
+        let options = FileOptions::default();
+
+        let mut content_types = String::from(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>
+<Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>"#);
+        let mut workbook = String::from(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>"#);
+        let mut workbook_rels = String::from(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#);
+
+        for (i, sheet) in self.sheets.iter().enumerate() {
+            let id = i + 1;
+            content_types.push_str(&format!(r#"<Override PartName="/xl/worksheets/sheet{}.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#, id));
+            workbook.push_str(&format!(r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#, escape(&sheet.name), id, id));
+            workbook_rels.push_str(&format!(r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{}.xml"/>"#, id, id));
+
+            zip.start_file(format!("xl/worksheets/sheet{}.xml", id), options)?;
+            zip.write_all(sheet.xml().as_bytes())?;
+        }
+
+        content_types.push_str("</Types>");
+        workbook.push_str("</sheets></workbook>");
+        workbook_rels.push_str(&format!(r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#, self.sheets.len() + 1));
+
+        zip.start_file("[Content_Types].xml", options)?;
+        zip.write_all(content_types.as_bytes())?;
+        zip.start_file("_rels/.rels", options)?;
+        zip.write_all(br#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#)?;
+        zip.start_file("xl/workbook.xml", options)?;
+        zip.write_all(workbook.as_bytes())?;
+        zip.start_file("xl/_rels/workbook.xml.rels", options)?;
+        zip.write_all(workbook_rels.as_bytes())?;
+        zip.start_file("xl/styles.xml", options)?;
+        zip.write_all(STYLES.as_bytes())?;
+
+        zip.finish()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{open_workbook, DataType, Reader, Xlsx};
+
+    #[test]
+    fn column_names() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(27 * 26), "AAA");
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join("sap-error-utils-writer.xlsx");
+
+        let mut wb = Workbook::new();
+        let sheet = wb.add_sheet("Failures & <Orders>", &["Mark", "Qty", "Area"]);
+        sheet.push_row(vec!["1200248A-X1A".into(), 3u32.into(), Cell::Area(12.5)]);
+        sheet.push_row(vec![Cell::Empty, Cell::Int(-1), Cell::Qty(0.125)]);
+        wb.save(&path).unwrap();
+
+        let mut xl: Xlsx<_> = open_workbook(&path).unwrap();
+        let rng = xl.worksheet_range("Failures & <Orders>").unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(rng.get_value((0, 0)), Some(&DataType::String("Mark".into())));
+        assert_eq!(rng.get_value((1, 0)), Some(&DataType::String("1200248A-X1A".into())));
+        assert_eq!(rng.get_value((1, 2)), Some(&DataType::Float(12.5)));
+        assert_eq!(rng.get_value((2, 2)), Some(&DataType::Float(0.125)));
+    }
+}
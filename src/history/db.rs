@@ -0,0 +1,341 @@
+//! SQLite storage of the run history
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::inbox::{Failure, FailureMatchStatus};
+use crate::paths;
+use crate::report::{RunKind, RunParams, RunReport};
+use super::{FailureRecord, FileRole, RunFile, RunRecord};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id              INTEGER PRIMARY KEY,
+    kind            TEXT NOT NULL,
+    started         TEXT NOT NULL,
+    files_searched  INTEGER NOT NULL,
+    cohv_file       TEXT,
+    strategy        TEXT NOT NULL,
+    production_rows INTEGER NOT NULL,
+    issue_rows      INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS files (
+    run_id  INTEGER NOT NULL REFERENCES runs(id),
+    role    TEXT NOT NULL,
+    path    TEXT NOT NULL,
+    name    TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS files_name ON files(name);
+
+CREATE TABLE IF NOT EXISTS failures (
+    run_id   INTEGER NOT NULL REFERENCES runs(id),
+    mark     TEXT NOT NULL,
+    wbs      TEXT NOT NULL,
+    program  TEXT NOT NULL,
+    qty      INTEGER NOT NULL,
+    outcome  TEXT NOT NULL,
+    matched  INTEGER NOT NULL,
+    cnf_file TEXT,
+    matl     TEXT,
+    orders   TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS failures_mark ON failures(mark);
+";
+
+const RUN_COLUMNS: &str = "runs.id, runs.kind, runs.started, runs.files_searched, runs.cohv_file, runs.strategy,
+    runs.production_rows, runs.issue_rows,
+    (SELECT COUNT(*) FROM failures WHERE failures.run_id = runs.id),
+    (SELECT COUNT(*) FROM failures WHERE failures.run_id = runs.id AND failures.matched)";
+
+const FAILURE_COLUMNS: &str = "failures.run_id, runs.started, runs.kind, failures.mark, failures.wbs, failures.program,
+    failures.qty, failures.outcome, failures.matched, failures.cnf_file, failures.matl, failures.orders";
+
+fn parse_started(text: String) -> rusqlite::Result<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(&text)
+        .map(|dt| dt.with_timezone(&Local))
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn parse_kind(text: String) -> rusqlite::Result<RunKind> {
+    RunKind::try_from(text.as_str())
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into()))
+}
+
+fn run_from_row(row: &Row) -> rusqlite::Result<RunRecord> {
+    let params = RunParams {
+        kind: parse_kind(row.get(1)?)?,
+        started: parse_started(row.get(2)?)?,
+        files_searched: row.get(3)?,
+        cohv_file: row.get::<_, Option<String>>(4)?.map(PathBuf::from),
+        strategy: row.get(5)?,
+    };
+
+    Ok(RunRecord {
+        id: row.get(0)?,
+        params,
+        production_rows: row.get(6)?,
+        issue_rows: row.get(7)?,
+        failures: row.get(8)?,
+        matched: row.get(9)?,
+    })
+}
+
+fn failure_from_row(row: &Row) -> rusqlite::Result<FailureRecord> {
+    Ok(FailureRecord {
+        run_id: row.get(0)?,
+        started: parse_started(row.get(1)?)?,
+        kind: parse_kind(row.get(2)?)?,
+        mark: row.get(3)?,
+        wbs: row.get(4)?,
+        program: row.get(5)?,
+        qty: row.get(6)?,
+        outcome: row.get(7)?,
+        matched: row.get(8)?,
+        cnf_file: row.get::<_, Option<String>>(9)?.map(PathBuf::from),
+        matl: row.get(10)?,
+        orders: row.get(11)?,
+    })
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn outcome(failure: &Failure) -> (String, bool) {
+    match failure.status() {
+        _ if failure.exclude => ("Excluded".into(), false),
+        FailureMatchStatus::MatchComplete => (FailureMatchStatus::MatchComplete.to_string(), true),
+        status => (status.to_string(), false),
+    }
+}
+
+/// Run history database
+#[derive(Debug)]
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Open (or create) a history database file
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let conn = Connection::open(path)
+            .map_err(|e| anyhow!("failed to open history {}: {}", path.display(), e))?;
+
+        Self::init(conn)
+    }
+
+    /// Open the history database in the working directory
+    pub fn open_default() -> anyhow::Result<Self> {
+        Self::open(*paths::HISTORY_DB)
+    }
+
+    /// History that is not saved, for testing
+    pub fn in_memory() -> anyhow::Result<Self> {
+        Self::init( Connection::open_in_memory()? )
+    }
+
+    fn init(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(Self { conn })
+    }
+
+    /// Record a run, returning its id
+    pub fn record_run(&mut self, report: &RunReport, files: &[RunFile]) -> anyhow::Result<i64> {
+        let tx = self.conn.transaction()?;
+        let params = &report.params;
+
+        tx.execute(
+            "INSERT INTO runs (kind, started, files_searched, cohv_file, strategy, production_rows, issue_rows)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                params.kind.to_string(),
+                params.started.to_rfc3339(),
+                params.files_searched,
+                params.cohv_file.as_ref().map(|p| p.display().to_string()),
+                params.strategy,
+                report.production.len(),
+                report.issue.len(),
+            ]
+        )?;
+        let run_id = tx.last_insert_rowid();
+
+        for file in files {
+            tx.execute(
+                "INSERT INTO files (run_id, role, path, name) VALUES (?1, ?2, ?3, ?4)",
+                params![run_id, file.role.as_str(), file.path.display().to_string(), file_name(&file.path)]
+            )?;
+        }
+
+        for f in &report.failures {
+            let (outcome, matched) = outcome(f);
+            let orders = f.applied
+                .iter()
+                .map(|o| format!("{}:{}", o.id, o.qty))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            tx.execute(
+                "INSERT INTO failures (run_id, mark, wbs, program, qty, outcome, matched, cnf_file, matl, orders)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    run_id,
+                    f.mark,
+                    f.wbs.to_string(),
+                    f.program,
+                    f.qty,
+                    outcome,
+                    matched,
+                    f.confirmation_file().map(|p| p.display().to_string()),
+                    f.confirmation_row().map(|r| r.matl.to_string()),
+                    orders,
+                ]
+            )?;
+        }
+
+        tx.commit()?;
+
+        Ok(run_id)
+    }
+
+    /// Record an output file being moved after its run was recorded
+    ///
+    /// returns the id of the run that wrote the file, if it is in the history
+    pub fn record_moved(&self, file: &Path, to: &Path) -> anyhow::Result<Option<i64>> {
+        let run_id = self.conn
+            .query_row(
+                "SELECT run_id FROM files WHERE role = 'output' AND name = ?1 ORDER BY run_id DESC LIMIT 1",
+                params![file_name(file)],
+                |row| row.get::<_, i64>(0)
+            )
+            .optional()?;
+
+        if let Some(id) = run_id {
+            self.conn.execute(
+                "INSERT INTO files (run_id, role, path, name) VALUES (?1, ?2, ?3, ?4)",
+                params![id, FileRole::Moved.as_str(), to.display().to_string(), file_name(to)]
+            )?;
+        }
+
+        Ok(run_id)
+    }
+
+    /// Most recent runs, newest first
+    pub fn runs(&self, limit: usize) -> anyhow::Result<Vec<RunRecord>> {
+        let mut stmt = self.conn.prepare( &format!("SELECT {} FROM runs ORDER BY runs.id DESC LIMIT ?1", RUN_COLUMNS) )?;
+        let runs = stmt
+            .query_map(params![limit], run_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(runs)
+    }
+
+    /// Run by id
+    pub fn run(&self, run_id: i64) -> anyhow::Result<Option<RunRecord>> {
+        let run = self.conn
+            .query_row(&format!("SELECT {} FROM runs WHERE runs.id = ?1", RUN_COLUMNS), params![run_id], run_from_row)
+            .optional()?;
+
+        Ok(run)
+    }
+
+    /// Run that read, wrote or moved a file with the same name
+    ///
+    /// e.g. which run produced `Production_20230105083000.ready`
+    pub fn find_file(&self, path: &Path) -> anyhow::Result<Option<RunRecord>> {
+        let run_id = self.conn
+            .query_row(
+                "SELECT run_id FROM files WHERE name = ?1 ORDER BY run_id DESC LIMIT 1",
+                params![file_name(path)],
+                |row| row.get::<_, i64>(0)
+            )
+            .optional()?;
+
+        match run_id {
+            Some(id) => self.run(id),
+            None => Ok(None)
+        }
+    }
+
+    /// Files of a run
+    pub fn files(&self, run_id: i64) -> anyhow::Result<Vec<RunFile>> {
+        let mut stmt = self.conn.prepare("SELECT role, path FROM files WHERE run_id = ?1 ORDER BY rowid")?;
+        let rows = stmt
+            .query_map(params![run_id], |row| Ok( (row.get::<_, String>(0)?, row.get::<_, String>(1)?) ))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .map(|(role, path)| Ok( RunFile { role: FileRole::try_from(role.as_str())?, path: path.into() } ))
+            .collect()
+    }
+
+    /// Failure outcomes of a run
+    pub fn failures(&self, run_id: i64) -> anyhow::Result<Vec<FailureRecord>> {
+        self.query_failures("failures.run_id = ?1", params![run_id])
+    }
+
+    /// Every recorded outcome for marks starting with `mark`, newest first
+    ///
+    /// filter on [`FailureRecord::matched`] for the times a mark was re-confirmed (or issued)
+    pub fn mark_history(&self, mark: &str) -> anyhow::Result<Vec<FailureRecord>> {
+        self.query_failures("failures.mark LIKE ?1 || '%'", params![mark])
+    }
+
+    fn query_failures(&self, filter: &str, params: impl rusqlite::Params) -> anyhow::Result<Vec<FailureRecord>> {
+        let sql = format!(
+            "SELECT {} FROM failures JOIN runs ON runs.id = failures.run_id WHERE {} ORDER BY runs.id DESC, failures.rowid",
+            FAILURE_COLUMNS, filter
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let records = stmt
+            .query_map(params, failure_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_query() {
+        let failure = Failure::try_from(
+            String::from("Planned order not found for 1200248A-X1A, D-1200248-10001, 2.000, Sigmanest Program:50123")
+        ).unwrap();
+        let report = RunReport {
+            params: RunParams { files_searched: 200, cohv_file: Some("cohv.xlsx".into()), ..Default::default() },
+            failures: vec![failure.clone(), failure],
+            ..Default::default()
+        };
+
+        let mut history = History::in_memory().unwrap();
+        let id = history.record_run(&report, &[
+            RunFile::input("cohv.xlsx"),
+            RunFile::output("out/Production_20230105083000.ready"),
+        ]).unwrap();
+
+        let runs = history.runs(10).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!((runs[0].id, runs[0].failures, runs[0].matched), (id, 2, 0));
+        assert_eq!(runs[0].params.cohv_file, Some(PathBuf::from("cohv.xlsx")));
+
+        let moved = history.record_moved(Path::new("Production_20230105083000.ready"), Path::new("outbound/Production_20230105083000.ready")).unwrap();
+        assert_eq!(moved, Some(id));
+        assert_eq!(history.files(id).unwrap().last(), Some(&RunFile::moved("outbound/Production_20230105083000.ready")));
+        assert_eq!(history.find_file(Path::new("Production_20230105083000.ready")).unwrap().map(|r| r.id), Some(id));
+        assert!(history.find_file(Path::new("Production_20230105090000.ready")).unwrap().is_none());
+
+        let marks = history.mark_history("1200248A").unwrap();
+        assert_eq!(marks.len(), 2);
+        assert_eq!(marks[0].outcome, "No confirmation row");
+        assert!(!marks[0].matched);
+    }
+}
@@ -0,0 +1,131 @@
+//! Local history of comparison and issue runs
+//!
+//! With the `history` feature, every run is recorded in a SQLite database file (no server),
+//! with its parameters, input, generated and moved files, and the outcome of each failure.
+//! Generated files get timestamped names and disappear into outbound, so the
+//! history is how a file (or a mark) is traced back to the run that produced it.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+
+use crate::report::{RunKind, RunParams};
+
+#[cfg(feature = "history")]
+mod db;
+#[cfg(feature = "history")]
+pub use db::History;
+
+/// Role of a file in a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileRole {
+    /// Inbox error or COHV export read by the run
+    Input,
+    /// File written by the run
+    Output,
+    /// Output file moved to outbound (path is the destination)
+    Moved,
+}
+
+impl FileRole {
+    /// Name stored in the history database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Input  => "input",
+            Self::Output => "output",
+            Self::Moved  => "moved",
+        }
+    }
+}
+
+impl std::fmt::Display for FileRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Input  => write!(f, "Input"),
+            Self::Output => write!(f, "Output"),
+            Self::Moved  => write!(f, "Moved"),
+        }
+    }
+}
+
+impl TryFrom<&str> for FileRole {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "input"  => Ok(Self::Input),
+            "output" => Ok(Self::Output),
+            "moved"  => Ok(Self::Moved),
+            _ => Err( anyhow!("Unknown file role `{}`", value) )
+        }
+    }
+}
+
+/// File read, written or moved by a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunFile {
+    pub role: FileRole,
+    pub path: PathBuf,
+}
+
+impl RunFile {
+    pub fn input(path: impl Into<PathBuf>) -> Self {
+        Self { role: FileRole::Input, path: path.into() }
+    }
+
+    pub fn output(path: impl Into<PathBuf>) -> Self {
+        Self { role: FileRole::Output, path: path.into() }
+    }
+
+    pub fn moved(path: impl Into<PathBuf>) -> Self {
+        Self { role: FileRole::Moved, path: path.into() }
+    }
+}
+
+/// Recorded run
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub id: i64,
+    pub params: RunParams,
+    pub production_rows: usize,
+    pub issue_rows: usize,
+    pub failures: usize,
+    pub matched: usize,
+}
+
+/// Recorded outcome of a failure in a run
+#[derive(Debug, Clone)]
+pub struct FailureRecord {
+    pub run_id: i64,
+    pub started: DateTime<Local>,
+    pub kind: RunKind,
+
+    pub mark: String,
+    pub wbs: String,
+    pub program: String,
+    pub qty: u32,
+    /// Match status, or `Excluded`
+    pub outcome: String,
+    /// Whether the failure was fully matched (and so re-confirmed or issued)
+    pub matched: bool,
+    /// Archived file the confirmation row came from
+    pub cnf_file: Option<PathBuf>,
+    pub matl: Option<String>,
+    /// Applied orders, as `order:qty` separated by spaces
+    pub orders: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_role_names() {
+        for role in [FileRole::Input, FileRole::Output, FileRole::Moved] {
+            assert_eq!(FileRole::try_from(role.as_str()).unwrap(), role);
+        }
+
+        assert_eq!(RunFile::moved("outbound/Production_1.ready").role, FileRole::Moved);
+        assert!(FileRole::try_from("deleted").is_err());
+    }
+}
@@ -1,17 +1,24 @@
 
 use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use serde::de::DeserializeOwned;
 
 use std::fs::DirEntry;
 // use std::fs::DirEntry;
 use std::{fs, io};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::api::CnfFileRow;
 use crate::paths;
 
-const HEADERS: [&str; 13] = [
+pub const HEADERS: [&str; 13] = [
     "Mark", "Id", "PartWbs", "PartLoc", "PartQty", "PartUom", "Matl", "MatlWbs" , "MatlQty", "MatlUom", "MatlLoc", "Plant", "Program"
 ];
+pub const ISSUE_HEADERS: [&str; 10] = [
+    "Code", "User1", "User2", "Matl", "MatlWbs" , "MatlQty", "MatlUom", "MatlLoc", "Plant", "Program"
+];
+pub const TRANSFER_HEADERS: [&str; 9] = [
+    "Matl", "MatlWbs", "MatlQty", "MatlUom", "FromPlant", "FromLoc", "ToPlant", "ToLoc", "Program"
+];
 const DELIM: u8 = b'\t';
 
 lazy_static! {
@@ -20,6 +27,8 @@ lazy_static! {
     static ref READY_READER: ReaderBuilder = {
         let mut reader = ReaderBuilder::new();
         reader
+            .has_headers(false)
+            .flexible(true)
             .delimiter(DELIM);
 
         reader
@@ -34,8 +43,14 @@ lazy_static! {
     };
 }
 
+/// Last `n` production files in the SAP archive, newest first
 pub fn get_last_n_files(n: usize) -> io::Result<Vec<DirEntry>> {
-    let mut entries = fs::read_dir(paths::SAP_ARCHIVE.to_path_buf())?
+    get_last_n_files_in(*paths::SAP_ARCHIVE, n)
+}
+
+/// Last `n` production files in a folder, newest first
+pub fn get_last_n_files_in(folder: &Path, n: usize) -> io::Result<Vec<DirEntry>> {
+    let mut entries = fs::read_dir(folder)?
         .filter_map(Result::ok)
         .filter(|entry| {
             paths::PROD_FILE_NAME.is_match(entry.file_name().to_str().unwrap_or(""))
@@ -75,15 +90,32 @@ pub fn parse_file(filepath: PathBuf) -> io::Result<Vec<CnfFileRow>> {
     let mut reader = READY_READER.from_path(filepath)?;
     reader.set_headers( StringRecord::from(HEADERS.to_vec()) );
 
-    for result in reader.deserialize::<CnfFileRow>() {
-        if let Ok(res) = result {
-            records.push(res);
-        }
+    for res in reader.deserialize::<CnfFileRow>().flatten() {
+        records.push(res);
+    }
+
+    Ok(records)
+}
+
+/// Read the raw records of a .ready file, for files that may not deserialize
+pub fn read_records(filepath: PathBuf) -> io::Result<Vec<StringRecord>> {
+    let mut reader = READY_READER.from_path(filepath)?;
+
+    let mut records = Vec::new();
+    for record in reader.records() {
+        records.push(record?);
     }
 
     Ok(records)
 }
 
+/// Deserialize a raw record with the given headers ([`HEADERS`], [`ISSUE_HEADERS`] or [`TRANSFER_HEADERS`])
+pub fn deserialize_record<T>(record: &StringRecord, headers: &[&str]) -> csv::Result<T>
+    where T: DeserializeOwned
+{
+    record.deserialize( Some(&StringRecord::from(headers.to_vec())) )
+}
+
 pub fn write_file<T>(records: Vec<T>, filepath: PathBuf) -> io::Result<()>
     where T: serde::Serialize
 {
@@ -95,3 +127,48 @@ pub fn write_file<T>(records: Vec<T>, filepath: PathBuf) -> io::Result<()>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{IssueFileRow, Qty};
+
+    const PROD_ROWS: &str = "1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t5\tEA\t50W-0008\t\t1001.569\tIN2\tK2\tHS01\t54091\n\
+        1210123A-X2A\tS-1210123\tD-1210123-10004\tPROD\t2\tEA\t50W-0008\t\t12.000\tIN2\tK2\tHS01\t54091\n";
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join( format!("{}-{}", std::process::id(), name) );
+        fs::write(&path, contents).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn parse_includes_first_row() {
+        let path = temp_file("Production_test.ready", PROD_ROWS);
+        let rows = parse_file(path.clone()).unwrap();
+        let _ = fs::remove_file(path);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].mark, "1210123A-X1A");
+        assert_eq!(rows[0].matl_qty, Qty::from_milli(1_001_569));
+    }
+
+    #[test]
+    fn issue_round_trip() {
+        let path = temp_file("Production_issue.ready", PROD_ROWS);
+        let issue: Vec<IssueFileRow> = parse_file(path.clone()).unwrap()
+            .iter()
+            .map(IssueFileRow::from)
+            .collect();
+
+        write_file(issue, path.clone()).unwrap();
+        let records = read_records(path.clone()).unwrap();
+        let _ = fs::remove_file(path);
+
+        assert_eq!(records.len(), 2);
+        let row: IssueFileRow = deserialize_record(&records[0], &ISSUE_HEADERS).unwrap();
+        assert_eq!(row.user1, "D-1210123");
+        assert_eq!(row.matl_qty, Qty::from_milli(1_001_569));
+    }
+}
@@ -0,0 +1,370 @@
+//! COGI (failed goods movement) exports, and the issue rows that correct them
+//!
+//! Material consumptions of confirmations that fail in SAP end up in COGI.
+//! Each COGI error is matched to the archived confirmation row that posted it,
+//! and an issue row is generated to consume the material instead.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserializer;
+
+use crate::api::{CnfFileRow, IssueFileRow, Material, Plant, Qty, Uom, Wbs};
+use super::cnf_files::{get_last_n_files_in, parse_file};
+use super::compare::Progress;
+
+/// Movement type of a goods issue for an order
+pub const CONSUMPTION: &str = "261";
+
+/// Header titles accepted for each column: English and German titles, and SAP technical field names
+///
+/// the first title is the one [`CogiError`] is deserialized with
+#[cfg(feature = "excel")]
+const COLUMNS: [&[&str]; 11] = [
+    &["Material", "Materialnummer", "MATNR"],
+    &["Plant", "Werk", "WERKS"],
+    &["Storage Location", "Stor. Loc.", "SLoc", "Lagerort", "LGORT"],
+    &["Quantity", "Quantity in Unit of Entry", "Qty in UnE", "Menge", "Menge in ErfassME", "ERFMG"],
+    &["Unit of Entry", "Unit", "UnE", "EUn", "ErfassME", "ERFME"],
+    &["Order", "Auftrag", "AUFNR"],
+    &["Header Material", "Assembly", "Mark", "Kopfmaterial"],
+    &["WBS Element", "PSP-Element", "PS_PSP_PNR"],
+    &["Reference", "Program", "Document Header Text", "Referenz", "BKTXT"],
+    &["Movement Type", "MvT", "Bewegungsart", "BWART"],
+    &["Message Text", "Error Text", "Message", "Meldungstext"],
+];
+
+/// Canonical name of a COGI header title, if it is a known column
+#[cfg(feature = "excel")]
+fn column_name(text: &str) -> Option<String> {
+    let text = text.trim();
+
+    COLUMNS
+        .iter()
+        .find(|titles| titles.iter().any(|t| t.eq_ignore_ascii_case(text)))
+        .map(|titles| titles[0].to_string())
+}
+
+/// Failed goods movement, from a row of a COGI export
+///
+/// only the material, plant, quantity and unit columns are required.
+/// The other columns narrow down the archived confirmation row the error is matched to.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CogiError {
+    /// Material master consumed
+    #[serde(rename = "Material", deserialize_with = "Material::deserialize")]
+    pub matl: Material,
+    #[serde(rename = "Plant")]
+    pub plant: Plant,
+    /// Storage location the material was consumed from
+    #[serde(rename = "Storage Location", default)]
+    pub matl_loc: Option<String>,
+    #[serde(rename = "Quantity", deserialize_with = "Qty::deserialize_sap")]
+    pub qty: Qty,
+    #[serde(rename = "Unit of Entry", deserialize_with = "Uom::deserialize")]
+    pub uom: Uom,
+
+    /// Order the material was consumed for
+    #[serde(rename = "Order", default)]
+    pub order: Option<u32>,
+    /// Part the order is for
+    #[serde(rename = "Header Material", default)]
+    pub mark: Option<String>,
+    /// WBS element of the order
+    #[serde(rename = "WBS Element", default, deserialize_with = "deserialize_wbs")]
+    pub wbs: Option<Wbs>,
+    /// Program number of the confirmation
+    #[serde(rename = "Reference", default)]
+    pub program: Option<String>,
+    #[serde(rename = "Movement Type", default)]
+    pub movement: Option<String>,
+    /// Why the goods movement failed
+    #[serde(rename = "Message Text", default)]
+    pub message: Option<String>,
+}
+
+impl CogiError {
+    /// Whether the error is a consumption (or has no movement type column)
+    pub fn is_consumption(&self) -> bool {
+        self.movement.as_deref().is_none_or(|m| m.trim() == CONSUMPTION)
+    }
+
+    /// Whether an archived confirmation row could have posted this consumption
+    pub fn matches(&self, row: &CnfFileRow) -> bool {
+        self.matl == row.matl
+            && self.mark.as_ref().is_none_or(|m| *m == row.mark)
+            && self.wbs.as_ref().is_none_or(|w| *w == row.part_wbs)
+            && self.program.as_ref().is_none_or(|p| *p == row.program)
+    }
+
+    /// Issue row consuming the failed quantity, charged as the archived row would have been
+    ///
+    /// the code and user columns (and G/L account for cost centers) are inferred from the archived row;
+    /// quantity, unit, storage location and plant are taken from the COGI error
+    pub fn issue_row(&self, archived: &CnfFileRow) -> Result<IssueFileRow, String> {
+        let mut row = IssueFileRow::try_from_cnf(archived)?;
+        row.matl_qty = self.qty;
+        row.matl_uom = self.uom.clone();
+        row.plant = self.plant;
+        if self.matl_loc.is_some() {
+            row.matl_loc = self.matl_loc.clone();
+        }
+
+        Ok(row)
+    }
+
+    /// Short description of the error, for logs
+    pub fn describe(&self) -> String {
+        let order = self.order.map_or(String::new(), |o| format!("order {} ", o));
+
+        format!("{}{} {} {} in {}", order, self.matl, self.qty, self.uom, self.plant)
+    }
+}
+
+/// A COGI error, with the archived row it was matched to and its corrective issue row
+#[derive(Debug, Clone)]
+pub struct Correction {
+    pub error: CogiError,
+    /// Archived confirmation row that posted the consumption, with the file it came from
+    pub archived: Option<(CnfFileRow, PathBuf)>,
+    /// Corrective issue row, or why none was generated
+    pub issue: Result<IssueFileRow, String>,
+}
+
+impl Correction {
+    pub fn new(error: CogiError) -> Self {
+        Self { error, archived: None, issue: Err("not matched yet".into()) }
+    }
+
+    /// Whether the archived row consumed exactly the failed quantity
+    fn is_exact(&self) -> bool {
+        self.archived.as_ref().is_some_and(|(row, _)| row.matl_qty == self.error.qty)
+    }
+
+    /// Match an archived row, if it is a better match than the current one
+    ///
+    /// rows are read newest first, so the first match is kept unless a later one consumed exactly the failed quantity
+    pub fn match_row(&mut self, row: &CnfFileRow, file: &Path) {
+        if !self.error.is_consumption() || !self.error.matches(row) || self.is_exact() {
+            return;
+        }
+
+        if self.archived.is_none() || row.matl_qty == self.error.qty {
+            self.archived = Some( (row.clone(), file.to_path_buf()) );
+        }
+    }
+
+    /// Generate the corrective issue row from the matched archived row
+    pub fn generate(&mut self) {
+        self.issue = match (&self.archived, self.error.is_consumption()) {
+            (_, false) => Err( format!("movement type {} is not a consumption", self.error.movement.as_deref().unwrap_or_default()) ),
+            (None, _) => Err( "no archived confirmation row".into() ),
+            (Some((row, _)), _) => self.error.issue_row(row),
+        };
+    }
+}
+
+/// Match each COGI error to a row of the last `n` production files in a folder
+///
+/// stops searching once every error has a row that consumed exactly the failed quantity.
+/// Returns an [`io::ErrorKind::Interrupted`] error if cancelled.
+pub fn match_archive_in(corrections: &mut [Correction], folder: &Path, n: usize, progress: &mut impl Progress) -> io::Result<()> {
+    let files = get_last_n_files_in(folder, n)?;
+    let total = files.len();
+
+    for (i, f) in files.into_iter().enumerate() {
+        if progress.is_cancelled() {
+            return Err( io::Error::new(io::ErrorKind::Interrupted, "cancelled") );
+        }
+
+        let path = f.path();
+        for row in parse_file(path.clone())? {
+            corrections.iter_mut().for_each(|c| c.match_row(&row, &path));
+        }
+
+        let matched = corrections.iter().filter(|c| c.archived.is_some()).count();
+        progress.files_scanned(i + 1, total);
+        progress.failures_matched(matched, corrections.len());
+
+        if corrections.iter().all(Correction::is_exact) {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Deserialize an optional WBS element, with empty cells as `None`
+fn deserialize_wbs<'de, D>(deserializer: D) -> Result<Option<Wbs>, D::Error>
+    where D: Deserializer<'de>
+{
+    let text: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+
+    text.map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .map(|s| Wbs::try_from(s.as_str()))
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Read a COGI export: excel workbook, `|` list download, tab delimited text or CSV
+///
+/// returns the result of each data row
+#[cfg(feature = "excel")]
+pub fn read_cogi(path: &Path) -> anyhow::Result<Vec<anyhow::Result<CogiError>>> {
+    use crate::excel::XlsxTableReader;
+    use super::parsers::{is_spreadsheet, read_text};
+
+    let mut reader = XlsxTableReader::<CogiError>::new().map_header(column_name);
+    if is_spreadsheet(path)? {
+        return reader.read_file(path.to_path_buf());
+    }
+
+    reader.read_range(&text_range(&read_text(path)?)?)
+}
+
+#[cfg(not(feature = "excel"))]
+pub fn read_cogi(path: &Path) -> anyhow::Result<Vec<anyhow::Result<CogiError>>> {
+    Err( anyhow!("Cannot read {}: COGI exports need the `excel` feature", path.display()) )
+}
+
+/// Whether cells are a COGI header: they have every required column
+#[cfg(feature = "excel")]
+fn is_header(cells: &[&str]) -> bool {
+    let names: Vec<String> = cells.iter().filter_map(|c| column_name(c)).collect();
+
+    ["Material", "Plant", "Quantity", "Unit of Entry"]
+        .iter()
+        .all(|required| names.iter().any(|n| n == required))
+}
+
+/// Table of a text export, for the table reader
+///
+/// the format is detected and lines split as for COHV exports.
+/// Headers repeated on each page are left out.
+#[cfg(feature = "excel")]
+fn text_range(text: &str) -> anyhow::Result<calamine::Range<calamine::DataType>> {
+    use calamine::{DataType, Range};
+    use super::parsers::{text_rows, CohvFormat};
+
+    let format = CohvFormat::detect_text_with(text, is_header)
+        .ok_or_else(|| anyhow!("Could not detect COGI export format: no header row found"))?;
+
+    let mut seen_header = false;
+    let mut rows = Vec::new();
+    for row in text_rows(text, format) {
+        let (_, cells) = row?;
+        let is_repeat = is_header(&cells.iter().map(String::as_str).collect::<Vec<_>>())
+            && std::mem::replace(&mut seen_header, true);
+
+        if !is_repeat {
+            rows.push(cells);
+        }
+    }
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if rows.is_empty() || width == 0 {
+        return Ok( Range::default() );
+    }
+
+    let mut range = Range::new((0, 0), (rows.len() as u32 - 1, width as u32 - 1));
+    for (r, cells) in rows.into_iter().enumerate() {
+        for (c, cell) in cells.into_iter().enumerate().filter(|(_, cell)| !cell.is_empty()) {
+            range.set_value((r as u32, c as u32), DataType::String(cell));
+        }
+    }
+
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::IssueCode;
+    use crate::inbox::cnf_files::{deserialize_record, HEADERS};
+
+    fn archived(text: &str) -> CnfFileRow {
+        let record = csv::StringRecord::from(text.split('\t').collect::<Vec<_>>());
+
+        deserialize_record(&record, &HEADERS).unwrap()
+    }
+
+    #[cfg(feature = "excel")]
+    #[test]
+    fn pipe_list_export() {
+        let list = "\
+Postprocessing of Faulty Goods Movements
+--------------------------------------------------------------------------------------------
+|Order  |Material|Plant|Stor. Loc.|Qty in UnE|UnE|MvT|Message Text                          |
+|------------------------------------------------------------------------------------------|
+|1234567|50W-0008|HS02 |W1        |  1.234,5 |IN2|261|Deficit of SL Unrestricted-use 1.234,5|
+|1234568|50W-0008|HS02 |W1        |     abc  |IN2|261|Deficit of SL Unrestricted-use        |
+|1234570|50W-0008|HS02 |W1        |   1.234  |IN2|261|Deficit of SL Unrestricted-use        |
+--------------------------------------------------------------------------------------------
+|Order  |Material|Plant|Stor. Loc.|Qty in UnE|UnE|MvT|Message Text                          |
+|1234569|A36-0004|HS01 |K2        |       20 |IN2|262|Deficit of SL Unrestricted-use        |
+";
+
+        let mut reader = crate::excel::XlsxTableReader::<CogiError>::new().map_header(column_name);
+        let results = reader.read_range(&text_range(list).unwrap()).unwrap();
+        assert_eq!(results.len(), 4);
+
+        let first = results[0].as_ref().unwrap();
+        assert_eq!((first.order, first.plant, first.qty), (Some(1234567), Plant::Williamsport, Qty::from_milli(1_234_500)));
+        assert_eq!(first.matl_loc.as_deref(), Some("W1"));
+        assert!(results[1].is_err());
+        // material quantities have decimals: a single separator is the decimal point
+        assert_eq!(results[2].as_ref().unwrap().qty, Qty::from_milli(1234));
+        assert!(!results[3].as_ref().unwrap().is_consumption());
+    }
+
+    #[cfg(feature = "excel")]
+    #[test]
+    fn delimited_export() {
+        let csv = "\
+Material;Plant;Quantity;Unit of Entry;Movement Type
+50W-0008;HS01;1,234;IN2;261
+50W-0008;HS01;1.234,5;IN2;261
+";
+
+        let mut reader = crate::excel::XlsxTableReader::<CogiError>::new().map_header(column_name);
+        let qtys: Vec<Qty> = reader.read_range(&text_range(csv).unwrap()).unwrap()
+            .into_iter()
+            .map(|r| r.unwrap().qty)
+            .collect();
+
+        assert_eq!(qtys, vec![Qty::from_milli(1234), Qty::from_milli(1_234_500)]);
+    }
+
+    #[test]
+    fn correct_consumption() {
+        let error = CogiError {
+            matl: "50W-0008".into(),
+            plant: Plant::Williamsport,
+            matl_loc: Some("W1".into()),
+            qty: Qty::from_milli(10_000),
+            uom: Uom::In2,
+            order: Some(1234567),
+            mark: None,
+            wbs: Some("D-1210123-10004".try_into().unwrap()),
+            program: None,
+            movement: Some(CONSUMPTION.into()),
+            message: None,
+        };
+
+        let older = archived("1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t1\tEA\t50W-0008\t\t10.000\tIN2\tK2\tHS01\t54001");
+        let newer = archived("1210123A-X1B\tS-1210123\tD-1210123-10004\tPROD\t2\tEA\t50W-0008\t\t30.000\tIN2\tK2\tHS01\t54091");
+        let other = archived("1210123A-X1B\tS-1210123\tD-1210123-10005\tPROD\t1\tEA\t50W-0008\t\t10.000\tIN2\tK2\tHS01\t54091");
+
+        let mut correction = Correction::new(error);
+        for row in [&other, &newer, &older] {
+            correction.match_row(row, Path::new("Production_20230814120000.ready"));
+        }
+        correction.generate();
+
+        assert_eq!(correction.archived.as_ref().unwrap().0.program, "54001");
+        let issue = correction.issue.unwrap();
+        assert_eq!((issue.code, issue.user1.as_str(), issue.user2.as_str()), (IssueCode::ProjectFromStock, "D-1210123", "01"));
+        assert_eq!((issue.matl_qty, issue.plant, issue.matl_loc.as_deref()), (Qty::from_milli(10_000), Plant::Williamsport, Some("W1")));
+    }
+}
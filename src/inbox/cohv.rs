@@ -1,49 +1,302 @@
 
-use crate::api::{Order, OrderData};
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+use std::path::Path;
 
-#[derive(Debug)]
-pub struct Header {
-    _type: usize,
+use regex::Regex;
+
+use crate::api::{Order, OrderData, Qty};
+use crate::paths::{read_pairs, COHV_ALIASES};
+
+/// Logical COHV column, independent of SAP GUI language and layout
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum CohvColumn {
+    OrderType,
+    Order,
+    Material,
+    Qty,
+    Wbs,
+    Plant,
+}
+
+impl CohvColumn {
+    pub const ALL: [Self; 6] = [
+        Self::OrderType,
+        Self::Order,
+        Self::Material,
+        Self::Qty,
+        Self::Wbs,
+        Self::Plant,
+    ];
+
+    /// Canonical column name, as used by the COHV row types
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::OrderType => "Order Type",
+            Self::Order     => "Order",
+            Self::Material  => "Material",
+            Self::Qty       => "Target qty",
+            Self::Wbs       => "WBS Element",
+            Self::Plant     => "Plant",
+        }
+    }
 
-    order: usize,
-    mark:  usize,
-    qty:   usize,
-    wbs:   usize,
-    plant: usize,
+    /// Built-in aliases: English and German column titles, and SAP technical field names
+    fn default_aliases(&self) -> &'static [&'static str] {
+        match self {
+            Self::OrderType => &["Order Type", "Auftragsart", "AUART"],
+            Self::Order     => &["Order", "Auftrag", "AUFNR"],
+            Self::Material  => &["Material", "Material Number", "Materialnummer", "MATNR"],
+            Self::Qty       => &["Target qty", "Order quantity (GMEIN)", "Order quantity", "Sollmenge", "Auftragsmenge (GMEIN)", "Auftragsmenge", "GAMNG"],
+            Self::Wbs       => &["WBS Element", "PSP-Element", "PSPEL", "PROJN"],
+            Self::Plant     => &["Plant", "Werk", "WERKS", "PWERK"],
+        }
+    }
 }
 
-impl Default for Header {
+impl TryFrom<&str> for CohvColumn {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.name().eq_ignore_ascii_case(value.trim()))
+            .ok_or_else(|| format!("Unknown COHV column `{}`", value))
+    }
+}
+
+/// Header titles accepted for each [`CohvColumn`]
+///
+/// Matching ignores case and surrounding whitespace.
+/// Extra aliases can be added in [`COHV_ALIASES`], one `column<TAB>alias` pair per line:
+///
+/// ```text
+/// # German layout variant
+/// Target qty	Gesamtmenge
+/// Material	Materialkurztext
+/// ```
+#[allow(clippy::tabs_in_doc_comments)]
+#[derive(Debug, Clone)]
+pub struct CohvAliases {
+    aliases: HashMap<String, CohvColumn>,
+}
+
+impl Default for CohvAliases {
     fn default() -> Self {
-        // we are going to assume no index will be at 255
-        //  so then we can find an unmatched index
-        Self {
-            _type:  usize::MAX,
-
-            order:  usize::MAX,
-            mark:   usize::MAX,
-            qty:    usize::MAX,
-            wbs:    usize::MAX,
-            plant:  usize::MAX,
+        let mut result = Self { aliases: HashMap::new() };
+        for col in CohvColumn::ALL {
+            for alias in col.default_aliases() {
+                result.add(col, alias);
+            }
         }
+
+        result
     }
 }
 
+impl CohvAliases {
+    /// Built-in aliases, plus those in [`COHV_ALIASES`] if it exists
+    pub fn load_default() -> io::Result<Self> {
+        let mut result = Self::default();
+        if COHV_ALIASES.exists() {
+            result.load(*COHV_ALIASES)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Add aliases from a tab delimited file of `column<TAB>alias` lines
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        for (col, alias) in read_pairs(path)? {
+            let col = CohvColumn::try_from(col.as_str())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            self.add(col, &alias);
+        }
+
+        Ok(())
+    }
+
+    pub fn add(&mut self, col: CohvColumn, alias: &str) {
+        self.aliases.insert(alias.trim().to_lowercase(), col);
+    }
+
+    pub fn match_header(&self, text: &str) -> Option<CohvColumn> {
+        self.aliases.get(&text.trim().to_lowercase()).copied()
+    }
+}
+
+#[derive(Debug)]
+pub struct Header {
+    columns: HashMap<CohvColumn, usize>,
+}
+
 impl Header {
-    pub fn parse_row(&self, row: String) -> Order {
-        let split_row: Vec<&str> = row.split("|").map(|c| c.trim()).collect();
+    /// Parse a header line, matching columns with `aliases`
+    pub fn parse(value: &str, aliases: &CohvAliases) -> Result<Self, String> {
+        Self::from_cells(&split_cells(value), aliases)
+    }
+
+    /// Match header cells with `aliases`
+    pub fn from_cells(cells: &[&str], aliases: &CohvAliases) -> Result<Self, String> {
+        let mut columns = HashMap::new();
+
+        for (i, item) in cells.iter().enumerate() {
+            if let Some(col) = aliases.match_header(item) {
+                columns.entry(col).or_insert(i);
+            }
+        }
 
+        // validate that all columns matched
+        let missing_columns: Vec<String> = CohvColumn::ALL
+            .iter()
+            .filter(|c| !columns.contains_key(c))
+            .map(|c| format!("`{}`", c.name()))
+            .collect();
+        if !missing_columns.is_empty() {
+            return Err(format!("Failed to parse header: missing columns {:?}", missing_columns));
+        }
+
+        Ok(Self { columns })
+    }
+
+    fn cell<'a>(&self, cells: &[&'a str], col: CohvColumn) -> anyhow::Result<&'a str> {
+        cells.get(self.columns[&col])
+            .map(|c| c.trim())
+            .ok_or_else(|| anyhow!("missing `{}` column", col.name()))
+    }
+
+    /// Whether a row is a total/subtotal line (marked with `*`, or without an order number)
+    pub fn is_total(&self, row: &str) -> bool {
+        self.is_total_cells(&split_cells(row))
+    }
+
+    pub fn is_total_cells(&self, cells: &[&str]) -> bool {
+        cells.iter().any(|c| c.trim().starts_with('*'))
+            || self.cell(cells, CohvColumn::Order).map_or(true, str::is_empty)
+    }
+
+    pub fn parse_row(&self, row: &str) -> anyhow::Result<Order> {
+        self.parse_cells(&split_cells(row))
+    }
+
+    pub fn parse_cells(&self, cells: &[&str]) -> anyhow::Result<Order> {
+        let id = self.cell(cells, CohvColumn::Order)?;
         let data = OrderData {
-            id:    split_row[self.order].parse().unwrap(),
-            mark:  split_row[self.mark].into(),
-            qty:   split_row[self.qty].parse().unwrap(),
-            wbs:   split_row[self.wbs].try_into().unwrap(),
-            plant: split_row[self.plant].into(),
+            id:    id.parse().map_err(|_| anyhow!("invalid order number `{}`", id))?,
+            mark:  self.cell(cells, CohvColumn::Material)?.into(),
+            qty:   parse_qty(self.cell(cells, CohvColumn::Qty)?)?,
+            wbs:   self.cell(cells, CohvColumn::Wbs)?.try_into()?,
+            plant: self.cell(cells, CohvColumn::Plant)?.try_into()?,
         };
 
-        match split_row[self._type] {
-            "PP01" => Order::ProductionOrder(data),
-            "PR"   => Order::PlannedOrder(data),
-            _ => unreachable!()
+        match self.cell(cells, CohvColumn::OrderType)? {
+            "PP01" => Ok( Order::ProductionOrder(data) ),
+            "PR"   => Ok( Order::PlannedOrder(data) ),
+            other  => Err( anyhow!("unexpected order type `{}`", other) )
+        }
+    }
+}
+
+/// Cells of a `|` list line, trimmed
+pub(crate) fn split_cells(row: &str) -> Vec<&str> {
+    row.split('|').map(|c| c.trim()).collect()
+}
+
+/// Cells of a `|` list line, or `None` for page titles, blank lines and `----` separators
+pub(crate) fn list_cells(line: &str) -> Option<Vec<&str>> {
+    let line = line.trim();
+    let is_row = DATA_ROW.is_match(line) && !line.chars().all(|c| matches!(c, '|' | '-' | '+' | ' '));
+
+    is_row.then(|| split_cells(line))
+}
+
+/// Parse a whole quantity, as SAP GUI writes it (see [`Qty::parse_sap`])
+///
+/// order quantities are whole, so a single separator followed by exactly 3 digits
+/// groups thousands (`1.234`, `1,234`); decimals are allowed if they are zero (`1.234,000`)
+fn parse_qty(qty: &str) -> anyhow::Result<u32> {
+    let text = qty.trim();
+    let grouped = text.matches(['.', ',']).count() == 1
+        && text.rfind(['.', ',']).is_some_and(|i| text.len() - i - 1 == 3);
+
+    let value = match grouped {
+        true  => Qty::parse_sap(&text.replace(['.', ','], "")),
+        false => Qty::parse_sap(text),
+    }.map_err(|e| anyhow!(e))?;
+
+    u32::try_from(value.milli() / 1000)
+        .ok()
+        .filter(|_| value.milli() % 1000 == 0)
+        .ok_or_else(|| anyhow!("invalid quantity `{}`", qty))
+}
+
+lazy_static! {
+    /// `|` delimited list line
+    pub static ref DATA_ROW: Regex = Regex::new(r"^\|.*\|$").expect("Failed to build DATA_ROW regex");
+}
+
+/// Streaming parser for COHV list output (`|` delimited text downloads)
+///
+/// Handles multi-page output: header lines repeated on each page are
+/// re-matched, and page titles, `----` separators and total lines are skipped.
+/// Each data line yields an order, or an error with its line number.
+pub struct CohvListParser<I> {
+    lines: I,
+    aliases: CohvAliases,
+    header: Option<Header>,
+    line_no: usize,
+}
+
+impl<I> CohvListParser<I>
+    where I: Iterator<Item = io::Result<String>>
+{
+    pub fn new(lines: I, aliases: CohvAliases) -> Self {
+        Self { lines, aliases, header: None, line_no: 0 }
+    }
+}
+
+impl<R: BufRead> CohvListParser<io::Lines<R>> {
+    pub fn from_reader(reader: R, aliases: CohvAliases) -> Self {
+        Self::new(reader.lines(), aliases)
+    }
+}
+
+impl<I> Iterator for CohvListParser<I>
+    where I: Iterator<Item = io::Result<String>>
+{
+    type Item = anyhow::Result<Order>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some( Err(e.into()) )
+            };
+            self.line_no += 1;
+
+            let cells = match list_cells(&line) {
+                Some(cells) => cells,
+                None => continue
+            };
+
+            // headers are repeated on each page
+            if let Ok(header) = Header::from_cells(&cells, &self.aliases) {
+                self.header = Some(header);
+                continue;
+            }
+
+            // lines before the first header are titles
+            let header = match &self.header {
+                Some(header) => header,
+                None => continue
+            };
+
+            if header.is_total_cells(&cells) {
+                continue;
+            }
+
+            let line_no = self.line_no;
+            return Some( header.parse_cells(&cells).map_err(|e| anyhow!("line {}: {}", line_no, e)) );
         }
     }
 }
@@ -52,33 +305,76 @@ impl TryFrom<String> for Header {
     type Error = String;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let mut head = Header::default();
-
-        for (i, item) in value.split("|").enumerate() {
-            match item.trim() {
-                "Order Type"  => head._type = i,
-                "Order"       => head.order = i,
-                "Material"    => head.mark  = i,
-                "Target qty"  => head.qty   = i,
-                "WBS Element" => head.wbs   = i,
-                "Plant"       => head.plant = i,
-
-                _ => ()
-            }
-        }
+        Self::parse(&value, &CohvAliases::default())
+    }
+}
 
-        // validate that all columns matched
-        let mut missing_columns = Vec::new();
-        if head._type == usize::MAX { missing_columns.push("`Order Type`" ); }
-        if head.order == usize::MAX { missing_columns.push("`Order`"      ); }
-        if head.mark  == usize::MAX { missing_columns.push("`Material`"   ); }
-        if head.qty   == usize::MAX { missing_columns.push("`Target Qty`" ); }
-        if head.wbs   == usize::MAX { missing_columns.push("`WBS Element`"); }
-        if head.plant == usize::MAX { missing_columns.push("`Plant`"      ); }
-        if missing_columns.len() > 0 {
-            return Err(format!("Failed to parse header: missing columns {:?}", missing_columns));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn german_and_technical_headers() {
+        let aliases = CohvAliases::default();
+
+        let header = Header::parse("|Auftragsart|AUFNR|Materialnummer|gamng|PSP-Element|Werk|", &aliases).unwrap();
+        let order = header.parse_row("|PR|1234567|1200248A-X1A|3|D-1200248-10001|HS02|").unwrap();
+
+        match order {
+            Order::PlannedOrder(data) => {
+                assert_eq!(data.id, 1234567);
+                assert_eq!(data.qty, 3);
+            },
+            _ => panic!("expected planned order")
         }
+    }
+
+    #[test]
+    fn configured_alias() {
+        let mut aliases = CohvAliases::default();
+        assert!(Header::parse("|Order Type|Order|Material|Gesamtmenge|WBS Element|Plant|", &aliases).is_err());
 
-        Ok(head)
+        aliases.add(CohvColumn::try_from("target qty").unwrap(), "Gesamtmenge");
+        assert!(Header::parse("|Order Type|Order|Material|Gesamtmenge|WBS Element|Plant|", &aliases).is_ok());
+    }
+
+    #[test]
+    fn quantities() {
+        assert_eq!(parse_qty("3").unwrap(), 3);
+        assert_eq!(parse_qty("1.234").unwrap(), 1234);
+        assert_eq!(parse_qty("1,234").unwrap(), 1234);
+        assert_eq!(parse_qty("12.345.678").unwrap(), 12345678);
+        assert_eq!(parse_qty("1.234,000").unwrap(), 1234);
+        assert_eq!(parse_qty("5,0").unwrap(), 5);
+        assert!(parse_qty("2,5").is_err());
+        assert!(parse_qty("").is_err());
+    }
+
+    #[test]
+    fn multi_page_list() {
+        let list = "\
+08/14/2023          Order Info System
+------------------------------------------------------------------
+|Order Type|Order  |Material    |Target qty|WBS Element    |Plant|
+|----------------------------------------------------------------|
+|PR        |1234567|1200248A-X1A|     1.200|D-1200248-10001|HS01 |
+|PR        |1234568|1200248A-X1B|       abc|D-1200248-10001|HS01 |
+|*         |       |            |     1.200|               |     |
+------------------------------------------------------------------
+08/14/2023          Order Info System                      Page 2
+|Order Type|Order  |Material    |Target qty|WBS Element    |Plant|
+|----------------------------------------------------------------|
+|PP01      |1234569|1200248A-X1C|         2|D-1200248-10002|HS02 |
+";
+
+        let results: Vec<_> = CohvListParser::from_reader(list.as_bytes(), CohvAliases::default()).collect();
+        assert_eq!(results.len(), 3);
+
+        match &results[0] {
+            Ok(Order::PlannedOrder(data)) => assert_eq!(data.qty, 1200),
+            other => panic!("unexpected {:?}", other)
+        }
+        assert!(results[1].as_ref().unwrap_err().to_string().starts_with("line 6:"));
+        assert!(matches!(results[2], Ok(Order::ProductionOrder(_))));
     }
 }
@@ -0,0 +1,200 @@
+//! Matching of inbox failures against archived confirmation files and COHV orders
+
+use std::io;
+use std::path::Path;
+
+use crate::api::{CnfFileRow, Order, OrderData};
+use crate::paths;
+use super::Failure;
+use super::cnf_files::{get_last_n_files_in, parse_file};
+use super::parsers::parse_failures;
+
+/// Progress reporting and cancellation for long running comparisons
+///
+/// all methods default to no-ops, so `()` can be used when progress is not needed
+pub trait Progress {
+    /// `scanned` of `total` archived production files have been searched
+    fn files_scanned(&mut self, _scanned: usize, _total: usize) {}
+    /// `matched` of `total` failures have a confirmation row
+    fn failures_matched(&mut self, _matched: usize, _total: usize) {}
+    /// `applied` planned orders have been applied to failures
+    fn orders_applied(&mut self, _applied: usize) {}
+    /// whether the comparison should stop early
+    fn is_cancelled(&self) -> bool { false }
+}
+
+impl Progress for () {}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
+
+/// Parse inbox error lines into sorted [`Failure`]s
+///
+/// lines that fail to parse are returned as their error message
+pub fn parse_inbox(lines: impl Iterator<Item = impl ToString>) -> (Vec<Failure>, Vec<String>) {
+    let mut inbox = Vec::new();
+    let mut errors = Vec::new();
+
+    for result in parse_failures(lines) {
+        match result {
+            Ok(failure) => inbox.push(failure),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+
+    inbox.sort_by( |a, b| a.partial_cmp(b).unwrap() );
+
+    (inbox, errors)
+}
+
+/// Search the last `n` archived production files for the confirmation row of each failure
+///
+/// stops searching once every failure has a confirmation row.
+/// Returns an [`io::ErrorKind::Interrupted`] error if cancelled.
+pub fn match_cnf_rows(inbox: &mut [Failure], n: usize, progress: &mut impl Progress) -> io::Result<()> {
+    match_cnf_rows_in(inbox, *paths::SAP_ARCHIVE, n, progress)
+}
+
+/// Search the last `n` production files in a folder for the confirmation row of each failure
+///
+/// see [`match_cnf_rows`]
+pub fn match_cnf_rows_in(inbox: &mut [Failure], folder: &Path, n: usize, progress: &mut impl Progress) -> io::Result<()> {
+    match_cnf_rows_with(inbox, folder, n, progress, |_| ())
+}
+
+/// Search the last `n` production files in a folder for the confirmation row of each failure,
+/// calling `on_row` with every row read
+///
+/// see [`match_cnf_rows`]
+pub fn match_cnf_rows_with(
+    inbox: &mut [Failure],
+    folder: &Path,
+    n: usize,
+    progress: &mut impl Progress,
+    mut on_row: impl FnMut(&CnfFileRow)
+) -> io::Result<()> {
+    let files = get_last_n_files_in(folder, n)?;
+    let total = files.len();
+
+    for (i, f) in files.into_iter().enumerate() {
+        if progress.is_cancelled() {
+            return Err(cancelled());
+        }
+
+        let path = f.path();
+        for cnf_row in parse_file(path.clone())? {
+            on_row(&cnf_row);
+            inbox
+                .iter_mut()
+                .filter(|f| **f == cnf_row)
+                .for_each(|f| f.set_confirmation_row_from(cnf_row.clone(), path.clone()));
+        }
+
+        let matched = inbox.iter().filter(|f| f.has_confirmation_row()).count();
+        progress.files_scanned(i + 1, total);
+        progress.failures_matched(matched, inbox.len());
+
+        if matched == inbox.len() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// How [`apply_orders`] allocates planned orders, for run reports
+pub const ORDER_ALLOCATION: &str = "Planned orders applied to failures with the same mark, in sorted order";
+
+/// Apply planned orders to failures with the same mark
+///
+/// orders are applied to failures in sorted order until the order qty is used up.
+/// Production orders are ignored.
+/// Returns the planned orders (or remainder of) that were not applied,
+/// or an [`io::ErrorKind::Interrupted`] error if cancelled.
+pub fn apply_orders(inbox: &mut [Failure], orders: Vec<Order>, progress: &mut impl Progress) -> io::Result<Vec<OrderData>> {
+    let mut applied = 0;
+    let mut not_applied = Vec::new();
+
+    for order in orders {
+        if progress.is_cancelled() {
+            return Err(cancelled());
+        }
+
+        if let Order::PlannedOrder(data) = order {
+            let qty = data.qty;
+            let mark = data.mark.clone();
+            let mut remaining = Some(data);
+
+            for failure in inbox.iter_mut().filter(|f| f.mark == mark) {
+                match remaining.take() {
+                    Some(d) => remaining = failure.apply_order_unchecked(d),
+
+                    // break loop if order is 100% applied
+                    None => break
+                }
+            }
+
+            if remaining.as_ref().map_or(0, |d| d.qty) < qty {
+                applied += 1;
+                progress.orders_applied(applied);
+            }
+
+            not_applied.extend( remaining.filter(|d| d.qty > 0) );
+        }
+    }
+
+    Ok(not_applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Plant;
+
+    struct Cancelled;
+    impl Progress for Cancelled {
+        fn is_cancelled(&self) -> bool { true }
+    }
+
+    fn planned_order(id: u32, mark: &str, qty: u32) -> Order {
+        Order::PlannedOrder(OrderData {
+            id,
+            mark: mark.into(),
+            qty,
+            wbs: "D-1210123-10004".try_into().unwrap(),
+            plant: Plant::Lancaster,
+        })
+    }
+
+    fn test_inbox() -> Vec<Failure> {
+        let (inbox, _) = parse_inbox([
+            "Planned order not found for 1210123A-X1A, D-1210123-10004, 3.000, Sigmanest Program:54091",
+            "Planned order not found for 1210123A-X1A, D-1210123-10004, 2.000, Sigmanest Program:54092",
+        ].iter());
+
+        inbox
+    }
+
+    #[test]
+    fn apply_orders_across_failures() {
+        let mut inbox = test_inbox();
+        let not_applied = apply_orders(&mut inbox, vec![planned_order(1, "1210123A-X1A", 4), planned_order(2, "1210123A-X1A", 4)], &mut ()).unwrap();
+
+        assert_eq!(inbox[0].qty(), 0);
+        assert_eq!(inbox[1].qty(), 0);
+        assert_eq!(inbox[1].applied.iter().map(|o| o.id).collect::<Vec<_>>(), vec![1, 2]);
+
+        assert_eq!(not_applied.len(), 1);
+        assert_eq!((not_applied[0].id, not_applied[0].qty), (2, 3));
+    }
+
+    #[test]
+    fn apply_orders_cancelled() {
+        let mut inbox = test_inbox();
+        let err = apply_orders(&mut inbox, vec![planned_order(1, "1210123A-X1A", 4)], &mut Cancelled).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert_eq!(inbox[0].qty(), 3);
+    }
+}
@@ -0,0 +1,212 @@
+//! Consolidation of generated rows before they are written
+
+use std::collections::HashMap;
+use std::ops::AddAssign;
+
+use crate::api::{CnfFileRow, IssueFileRow, Plant, TransferFileRow};
+
+/// Row that can be merged with rows of the same key
+pub trait Consolidate: Clone + AddAssign {
+    /// Every field except the quantities
+    fn key(&self) -> String;
+    fn plant(&self) -> Plant;
+    /// Short description of the row, for provenance
+    fn describe(&self) -> String;
+}
+
+impl Consolidate for CnfFileRow {
+    fn key(&self) -> String {
+        [
+            self.mark.clone(),
+            self.id.clone(),
+            self.part_wbs.to_string(),
+            self.part_loc.clone(),
+            self.part_uom.to_string(),
+            self.matl.to_string(),
+            self.matl_wbs.to_string(),
+            self.matl_uom.to_string(),
+            self.matl_loc.clone().unwrap_or_default(),
+            self.plant.to_string(),
+            self.program.clone(),
+        ].join("\t")
+    }
+
+    fn plant(&self) -> Plant {
+        self.plant
+    }
+
+    fn describe(&self) -> String {
+        format!("{} {} x{} ({} {} {})", self.mark, self.part_wbs, self.part_qty, self.matl, self.matl_qty, self.matl_uom)
+    }
+}
+
+impl Consolidate for IssueFileRow {
+    fn key(&self) -> String {
+        [
+            self.code.to_string(),
+            self.user1.clone(),
+            self.user2.clone(),
+            self.matl.to_string(),
+            self.matl_wbs.to_string(),
+            self.matl_uom.to_string(),
+            self.matl_loc.clone().unwrap_or_default(),
+            self.plant.to_string(),
+            self.program.clone(),
+        ].join("\t")
+    }
+
+    fn plant(&self) -> Plant {
+        self.plant
+    }
+
+    fn describe(&self) -> String {
+        format!("{} {} {} ({} {} {})", self.code, self.user1, self.user2, self.matl, self.matl_qty, self.matl_uom)
+    }
+}
+
+impl Consolidate for TransferFileRow {
+    fn key(&self) -> String {
+        [
+            self.matl.to_string(),
+            self.matl_wbs.to_string(),
+            self.matl_uom.to_string(),
+            self.from_plant.to_string(),
+            self.from_loc.clone().unwrap_or_default(),
+            self.to_plant.to_string(),
+            self.to_loc.clone().unwrap_or_default(),
+            self.program.clone(),
+        ].join("\t")
+    }
+
+    /// plant the material is transferred from
+    fn plant(&self) -> Plant {
+        self.from_plant
+    }
+
+    fn describe(&self) -> String {
+        format!("{} {} {} {} -> {}", self.matl, self.matl_qty, self.matl_uom, self.from_plant, self.to_plant)
+    }
+}
+
+/// Row merged from several generated rows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merged {
+    /// The merged row
+    pub row: String,
+    /// The rows merged into it, in the order they were generated
+    pub sources: Vec<String>,
+}
+
+/// How generated rows are consolidated and split into files
+///
+/// The default writes every row as generated to a single file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Consolidation {
+    /// Merge rows with the same [`key`](Consolidate::key), adding their quantities
+    pub merge: bool,
+    /// Write a file per plant
+    pub split_by_plant: bool,
+    /// Maximum rows per file
+    pub max_rows: Option<usize>,
+}
+
+impl Consolidation {
+    /// Consolidate rows, returning the rows of each file and the merged rows
+    ///
+    /// rows keep the order they were generated in (merged rows at their first occurrence).
+    /// Files are split by plant (in order of first occurrence), then by `max_rows`.
+    pub fn apply<T: Consolidate>(&self, rows: Vec<T>) -> (Vec<Vec<T>>, Vec<Merged>) {
+        let (rows, merged) = match self.merge {
+            true => merge(rows),
+            false => (rows, Vec::new()),
+        };
+
+        let mut files: Vec<(Option<Plant>, Vec<T>)> = Vec::new();
+        for row in rows {
+            let plant = self.split_by_plant.then(|| row.plant());
+            match files.iter_mut().find(|(p, _)| *p == plant) {
+                Some((_, file)) => file.push(row),
+                None => files.push( (plant, vec![row]) ),
+            }
+        }
+
+        let files = files
+            .into_iter()
+            .flat_map(|(_, rows)| match self.max_rows {
+                Some(max) => rows.chunks(max.max(1)).map(<[T]>::to_vec).collect(),
+                None => vec![rows],
+            })
+            .collect();
+
+        (files, merged)
+    }
+}
+
+fn merge<T: Consolidate>(rows: Vec<T>) -> (Vec<T>, Vec<Merged>) {
+    let mut result: Vec<T> = Vec::new();
+    let mut sources: Vec<Vec<String>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for row in rows {
+        let description = row.describe();
+
+        match index.get(&row.key()) {
+            Some(&i) => {
+                result[i] += row;
+                sources[i].push(description);
+            },
+            None => {
+                index.insert(row.key(), result.len());
+                result.push(row);
+                sources.push(vec![description]);
+            }
+        }
+    }
+
+    let merged = result
+        .iter()
+        .zip(sources)
+        .filter(|(_, sources)| sources.len() > 1)
+        .map(|(row, sources)| Merged { row: row.describe(), sources })
+        .collect();
+
+    (result, merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Qty;
+    use crate::inbox::cnf_files::{deserialize_record, HEADERS};
+
+    fn row(wbs: &str, qty: u64, matl_qty: &str, plant: &str) -> CnfFileRow {
+        let text = format!("1210123A-X1A\tS-1210123\t{}\tPROD\t{}\tEA\t50W-0008\t\t{}\tIN2\tK2\t{}\t54091", wbs, qty, matl_qty, plant);
+        let record = csv::StringRecord::from(text.split('\t').collect::<Vec<_>>());
+
+        deserialize_record(&record, &HEADERS).unwrap()
+    }
+
+    #[test]
+    fn merge_and_split() {
+        let rows = vec![
+            row("D-1210123-10004", 2, "20.000", "HS01"),
+            row("D-1210123-10005", 1, "10.000", "HS02"),
+            row("D-1210123-10004", 1, "10.001", "HS01"),
+            row("D-1210123-10006", 1, "10.000", "HS01"),
+        ];
+
+        let (files, merged) = Consolidation { merge: true, ..Default::default() }.apply(rows.clone());
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].len(), 3);
+        assert_eq!((files[0][0].part_qty, files[0][0].matl_qty), (3, Qty::from_milli(30_001)));
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].sources.len(), 2);
+
+        let (files, merged) = Consolidation { split_by_plant: true, max_rows: Some(2), ..Default::default() }.apply(rows);
+        let sizes: Vec<usize> = files.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 1, 1]);
+        assert_eq!(files[2][0].plant, Plant::Williamsport);
+        assert!(merged.is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+//! Discovery of SAP GUI COHV export files
+//!
+//! Searches configured folders for COHV exports, picks the newest readable one
+//! and checks that it is fresh enough for the inbox errors being processed.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::api::Order;
+use super::cohv::CohvAliases;
+use super::parsers::{is_cohv_text, read_cohv, COHV_EXPORT_EXTENSIONS, SPREADSHEET_EXTENSIONS};
+
+/// Marks listed in a missing marks warning, before eliding the rest
+const MISSING_MARKS_SHOWN: usize = 5;
+
+/// Default folders searched for COHV exports
+///
+/// the SAP GUI export folder, then downloads and desktop
+pub fn default_export_folders() -> Vec<PathBuf> {
+    let userprofile = match std::env::var_os("USERPROFILE") {
+        Some(path) => PathBuf::from(path),
+        None => return Vec::new()
+    };
+
+    vec![
+        userprofile.join("Documents/SAP/SAP GUI"),
+        userprofile.join("Downloads"),
+        userprofile.join("Desktop"),
+    ]
+}
+
+/// Search for COHV exports in a set of folders
+#[derive(Debug, Clone)]
+pub struct ExportSearch {
+    folders: Vec<PathBuf>,
+}
+
+impl Default for ExportSearch {
+    fn default() -> Self {
+        Self::new(default_export_folders())
+    }
+}
+
+impl ExportSearch {
+    pub fn new(folders: Vec<PathBuf>) -> Self {
+        Self { folders }
+    }
+
+    pub fn folders(&self) -> &[PathBuf] {
+        &self.folders
+    }
+
+    /// Candidate export files, newest first
+    ///
+    /// text files are only included if they have a COHV header near the start
+    pub fn candidates(&self) -> Vec<(PathBuf, DateTime<Local>)> {
+        let mut files: Vec<(PathBuf, DateTime<Local>)> = self.folders
+            .iter()
+            .filter_map(|folder| fs::read_dir(folder).ok())
+            .flat_map(|entries| entries.filter_map(Result::ok))
+            .map(|entry| entry.path())
+            .filter(|path| has_extension(path, &COHV_EXPORT_EXTENSIONS))
+            .filter_map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+
+                Some( (path, modified.into()) )
+            })
+            .collect();
+
+        files.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+        files.retain(|(path, _)| has_extension(path, &SPREADSHEET_EXTENSIONS) || is_cohv_text(path));
+
+        files
+    }
+
+    /// Load the newest file that reads as a COHV export
+    ///
+    /// spreadsheets that are not COHV exports (no matching header), and exports
+    /// with no readable orders, are skipped
+    pub fn newest(&self, aliases: &CohvAliases) -> anyhow::Result<CohvExport> {
+        let mut errors = Vec::new();
+        for (path, modified) in self.candidates() {
+            match read_cohv(path.clone(), aliases.clone()) {
+                Ok(rows) => {
+                    let export = CohvExport::new(path, modified, rows);
+                    if !export.orders.is_empty() {
+                        return Ok(export);
+                    }
+
+                    errors.push( format!("{}: no orders read", export.info.path.display()) );
+                },
+                Err(e) => errors.push( format!("{}: {}", path.display(), e) ),
+            }
+        }
+
+        let searched = self.folders
+            .iter()
+            .map(|f| f.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        match errors.is_empty() {
+            true  => Err( anyhow!("No COHV export found in {}", searched) ),
+            false => Err( anyhow!("No readable COHV export found in {}:\n{}", searched, errors.join("\n")) ),
+        }
+    }
+}
+
+fn has_extension(path: &Path, exts: &[&str]) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| exts.iter().any(|ext| e.eq_ignore_ascii_case(ext)))
+}
+
+/// Summary of a COHV export, for display
+#[derive(Debug, Clone)]
+pub struct ExportInfo {
+    pub path: PathBuf,
+    pub modified: DateTime<Local>,
+    /// Orders read from the export
+    pub rows: usize,
+    /// Rows that failed to parse
+    pub errors: usize,
+}
+
+impl std::fmt::Display for ExportInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (exported {}, {} orders", self.path.display(), self.modified.format("%Y-%m-%d %H:%M"), self.rows)?;
+        if self.errors > 0 {
+            write!(f, ", {} unreadable rows", self.errors)?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+/// COHV export loaded from disk
+#[derive(Debug)]
+pub struct CohvExport {
+    pub info: ExportInfo,
+    pub orders: Vec<Order>,
+}
+
+impl CohvExport {
+    fn new(path: PathBuf, modified: DateTime<Local>, rows: Vec<anyhow::Result<Order>>) -> Self {
+        let total = rows.len();
+        let orders: Vec<Order> = rows.into_iter().filter_map(Result::ok).collect();
+
+        let info = ExportInfo { path, modified, rows: orders.len(), errors: total - orders.len() };
+        Self { info, orders }
+    }
+
+    /// Load an export file
+    pub fn load(path: PathBuf, aliases: CohvAliases) -> anyhow::Result<Self> {
+        let modified = fs::metadata(&path)?.modified()?.into();
+        let rows = read_cohv(path.clone(), aliases)?;
+
+        Ok( Self::new(path, modified, rows) )
+    }
+
+    /// Warnings if the export looks stale
+    ///
+    /// - exported before the inbox error list was updated
+    /// - missing marks from the parts list
+    pub fn freshness_warnings<'a>(&self, inbox_updated: Option<DateTime<Local>>, marks: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if let Some(updated) = inbox_updated {
+            if self.info.modified < updated {
+                warnings.push(format!(
+                    "COHV export ({}) is older than the inbox error list ({})",
+                    self.info.modified.format("%Y-%m-%d %H:%M"),
+                    updated.format("%Y-%m-%d %H:%M")
+                ));
+            }
+        }
+
+        let exported: HashSet<&str> = self.orders
+            .iter()
+            .map(|o| match o {
+                Order::PlannedOrder(data) | Order::ProductionOrder(data) => data.mark.as_str()
+            })
+            .collect();
+
+        let mut missing: Vec<&str> = marks
+            .into_iter()
+            .filter(|m| !exported.contains(m))
+            .collect();
+        missing.sort();
+        missing.dedup();
+
+        if !missing.is_empty() {
+            let mut shown = missing.iter().take(MISSING_MARKS_SHOWN).copied().collect::<Vec<_>>().join(", ");
+            if missing.len() > MISSING_MARKS_SHOWN {
+                shown.push_str(", ...");
+            }
+
+            warnings.push( format!("COHV export is missing {} mark(s) from the parts list: {}", missing.len(), shown) );
+        }
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newest_export_and_warnings() {
+        let folder = std::env::temp_dir().join("sap-error-utils-discovery");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+
+        let header = "Order Type\tOrder\tMaterial\tTarget qty\tWBS Element\tPlant\n";
+        fs::write(folder.join("notes.txt"), "not an export\n").unwrap();
+        fs::write(folder.join("export.txt"), format!("{}PR\t1234567\t1200248A-X1A\t2\tD-1200248-10001\tHS01\n", header)).unwrap();
+
+        // newer, but no orders: the older export is used
+        fs::write(folder.join("empty.txt"), header).unwrap();
+        let newer = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+        fs::File::options().write(true).open(folder.join("empty.txt")).unwrap().set_modified(newer).unwrap();
+        assert_eq!(ExportSearch::new(vec![folder.clone()]).candidates()[0].0, folder.join("empty.txt"));
+
+        let export = ExportSearch::new(vec![folder.clone()]).newest(&CohvAliases::default()).unwrap();
+        assert_eq!(export.info.path, folder.join("export.txt"));
+        assert_eq!(export.info.rows, 1);
+
+        let warnings = export.freshness_warnings(Some(Local::now() + chrono::Duration::hours(1)), ["1200248A-X1A", "1200248A-X1B"]);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[1].ends_with("1200248A-X1B"));
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1,31 +1,64 @@
 
-use std::{cmp::Ordering, hash::{Hash, Hasher}};
+use std::{cmp::Ordering, fmt::Display, hash::{Hash, Hasher}};
+use std::path::{Path, PathBuf};
 
 use regex::Regex;
 
-use crate::api::{CnfFileRow, Wbs, Order, OrderData, IssueFileRow};
+use crate::api::{CnfFileRow, Mark, Wbs, Order, OrderData, IssueFileRow, Plant};
 
 lazy_static! {
     static ref INBOX_TEXT: Regex = Regex::new(r"Planned order not found for (\d{7}[a-zA-Z]-[\w-]+), (D-\d{7}-\d{5}), ([\d,]+).000, Sigmanest Program:([\d-]+)")
         .expect("Failed to build INBOX_TEXT regex");
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FailureMatchStatus {
     MatchComplete,
     NoConfirmationRow,
     NotEnoughOrdersApplied(u32),
 }
 
+impl Display for FailureMatchStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MatchComplete                => write!(f, "Matched"),
+            Self::NoConfirmationRow            => write!(f, "No confirmation row"),
+            Self::NotEnoughOrdersApplied(qty)  => write!(f, "Missing orders ({})", qty),
+        }
+    }
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Failure {
     pub mark: String,
+    #[serde(deserialize_with="Wbs::deserialize")]
     pub wbs: Wbs,
     pub qty: u32,
     pub program: String,
 
     cnf_row: Option<CnfFileRow>,
+    cnf_file: Option<PathBuf>,
     pub applied: Vec<OrderData>,
+
+    /// Leave failure out of generated output (it stays in the inbox)
+    #[serde(default)]
+    pub exclude: bool,
+    /// WBS element to use for output instead of the applied order's
+    #[serde(default, deserialize_with="deserialize_wbs_override")]
+    pub wbs_override: Option<Wbs>,
+    /// Plant to use for output instead of the applied order's
+    #[serde(default)]
+    pub plant_override: Option<Plant>,
+}
+
+fn deserialize_wbs_override<'de, D>(deserializer: D) -> Result<Option<Wbs>, D::Error>
+    where D: serde::Deserializer<'de>
+{
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with="Wbs::deserialize")] Wbs);
+
+    let wbs: Option<Wrapper> = serde::Deserialize::deserialize(deserializer)?;
+    Ok(wbs.map(|w| w.0))
 }
 
 impl Failure {
@@ -34,7 +67,7 @@ impl Failure {
 
         match order {
             Order::PlannedOrder(order_data) => {
-                self.apply_order_unchecked(order_data).map(|d| Order::PlannedOrder(d))
+                self.apply_order_unchecked(order_data).map(Order::PlannedOrder)
             },
             Order::ProductionOrder(_) => panic!("cannot apply a production order to a failure")
         }
@@ -66,6 +99,13 @@ impl Failure {
         }
     }
 
+    /// Check that the mark's job matches the failure WBS job
+    ///
+    /// a mismatch usually means SAP will not find a planned order
+    pub fn check_mark_job(&self) -> Result<(), String> {
+        Mark::from(self.mark.as_str()).check_wbs(&self.wbs)
+    }
+
     /// Returns the qty left to be applied
     pub fn qty(&self) -> u32 {
         let applied = self.applied
@@ -79,18 +119,55 @@ impl Failure {
         self.cnf_row = Some(row);
     }
 
+    /// Set the confirmation row and the archived file it was read from
+    pub fn set_confirmation_row_from(&mut self, row: CnfFileRow, file: PathBuf) {
+        self.cnf_row = Some(row);
+        self.cnf_file = Some(file);
+    }
+
     pub fn has_confirmation_row(&self) -> bool {
         self.cnf_row.is_some()
     }
 
+    pub fn confirmation_row(&self) -> Option<&CnfFileRow> {
+        self.cnf_row.as_ref()
+    }
+
+    /// Archived production file the confirmation row was read from
+    pub fn confirmation_file(&self) -> Option<&Path> {
+        self.cnf_file.as_deref()
+    }
+
+    /// Remove an applied order, returning it so its qty can be applied elsewhere
+    pub fn remove_applied(&mut self, index: usize) -> OrderData {
+        self.applied.remove(index)
+    }
+
+    /// Apply user overrides to an output row
+    fn with_overrides(&self, mut row: CnfFileRow) -> CnfFileRow {
+        if let Some(wbs) = &self.wbs_override {
+            row.part_wbs = wbs.clone();
+        }
+
+        if let Some(plant) = self.plant_override {
+            row.plant = plant;
+        }
+
+        row
+    }
+
     pub fn generate_output(&self) -> Result<Vec<CnfFileRow>, String> {
+        if self.exclude {
+            return Ok(Vec::new());
+        }
+
         match &self.cnf_row {
             Some(row) => {
-                let mut result = Vec::new();
-                
-                for appl in &self.applied {
-                    result.push(row.modify_with(appl));
-                }
+                let result = row
+                    .split_across(&self.applied)
+                    .into_iter()
+                    .map(|row| self.with_overrides(row))
+                    .collect();
         
                 Ok(result)
             },
@@ -99,10 +176,14 @@ impl Failure {
     }
 
     pub fn generate_issue_output(&mut self) -> Result<IssueFileRow, String> {
+        if self.exclude {
+            return Err(format!("{} is excluded from output", self.mark));
+        }
+
         match &self.cnf_row {
             Some(row) => {
-                let mut row = row.clone();
-                row.matl_qty = self.qty() as f64 * row.area_per_ea();
+                let mut row = self.with_overrides(row.clone());
+                row.matl_qty = row.matl_qty_for(self.qty() as u64);
 
                 self.qty = 0;
 
@@ -113,7 +194,7 @@ impl Failure {
     }
 
     pub fn status(&self) -> FailureMatchStatus {
-        if let None = self.cnf_row {
+        if self.cnf_row.is_none() {
             return FailureMatchStatus::NoConfirmationRow;
         }
 
@@ -127,6 +208,7 @@ impl Failure {
 
     pub fn new_inbox_text(&self) -> Option<String> {
         let qty = match self.status() {
+            _ if self.exclude => self.qty,
             FailureMatchStatus::MatchComplete => 0,
             FailureMatchStatus::NoConfirmationRow => self.qty,
             FailureMatchStatus::NotEnoughOrdersApplied(qty) => qty,
@@ -136,7 +218,16 @@ impl Failure {
             return None;
         }
 
-        Some(format!("Planned order not found for {}, {}, {}.000, Sigmanest Program:{}", self.mark, self.wbs, qty, self.program))
+        Some( self.inbox_text_with(qty) )
+    }
+
+    /// Inbox error text of the failure at its full qty, for failures whose output was not written
+    pub fn inbox_text(&self) -> String {
+        self.inbox_text_with(self.qty)
+    }
+
+    fn inbox_text_with(&self, qty: u32) -> String {
+        format!("Planned order not found for {}, {}, {}.000, Sigmanest Program:{}", self.mark, self.wbs, qty, self.program)
     }
 }
 
@@ -158,19 +249,13 @@ impl PartialOrd for Failure {
             return Some(Ordering::Equal);
         }
 
-        if self.mark < other.mark {
+        let less = self.mark < other.mark
+            || (self.mark == other.mark && self.program < other.program)
+            || (self.mark == other.mark && self.program == other.program && self.wbs < other.wbs);
+
+        if less {
             return Some(Ordering::Less);
         }
-        else if self.mark == other.mark {
-            if self.program < other.program {
-                return Some(Ordering::Less);
-            }
-            else if self.program == other.program {
-                if self.wbs < other.wbs {
-                    return Some(Ordering::Less);
-                }
-            }
-        }
 
         Some(Ordering::Greater)
     }
@@ -203,6 +288,10 @@ impl TryFrom<String> for Failure {
                     program: caps.get(4).unwrap().as_str().into(),
 
                     cnf_row: None::<CnfFileRow>,
+                    cnf_file: None,
+                    exclude: false,
+                    wbs_override: None,
+                    plant_override: None,
                     applied: Vec::new(),
                 }
             )
@@ -211,3 +300,107 @@ impl TryFrom<String> for Failure {
         Err( anyhow!("Failed to parse line `{}`", value) )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{Plant, Qty};
+
+    fn test_failure() -> Failure {
+        let mut failure = Failure::try_from(
+            String::from("Planned order not found for 1210123A-X1A, D-1210123-10004, 5.000, Sigmanest Program:54091")
+        ).unwrap();
+
+        failure.set_confirmation_row_data(CnfFileRow {
+            mark: "1210123A-X1A".into(),
+            id: "S-1210123".into(),
+            part_wbs: "D-1210123-10004".try_into().unwrap(),
+            part_loc: "PROD".into(),
+            part_qty: 5u64,
+            part_uom: "EA".into(),
+
+            matl: "50W-0008".into(),
+            matl_wbs: Wbs::None,
+            matl_qty: Qty::from(1_000),
+            matl_uom: "IN2".into(),
+            matl_loc: Some("K2".into()),
+
+            plant: Plant::Lancaster,
+            program: "54091".into()
+        });
+
+        failure.apply_order_unchecked(OrderData {
+            id: 1,
+            mark: "1210123A-X1A".into(),
+            qty: 5,
+            wbs: "D-1210123-10005".try_into().unwrap(),
+            plant: Plant::Lancaster,
+        });
+
+        failure
+    }
+
+    #[test]
+    fn output_with_overrides() {
+        let mut failure = test_failure();
+        failure.wbs_override = Some("D-1210123-10009".try_into().unwrap());
+        failure.plant_override = Some(Plant::Williamsport);
+
+        let output = failure.generate_output().unwrap();
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].part_wbs.to_string(), "D-1210123-10009");
+        assert_eq!(output[0].plant, Plant::Williamsport);
+        assert_eq!(output[0].matl_qty, Qty::from(1_000));
+    }
+
+    #[test]
+    fn split_output_adds_up() {
+        let mut failure = Failure::try_from(
+            String::from("Planned order not found for 1210123A-X1A, D-1210123-10004, 5.000, Sigmanest Program:54091")
+        ).unwrap();
+
+        let mut row = test_failure().confirmation_row().unwrap().clone();
+        row.matl_qty = "1000.001".parse().unwrap();
+        failure.set_confirmation_row_data(row);
+
+        for (id, qty) in [(1, 2), (2, 2), (3, 1)] {
+            failure.apply_order_unchecked(OrderData {
+                id,
+                mark: "1210123A-X1A".into(),
+                qty,
+                wbs: "D-1210123-10005".try_into().unwrap(),
+                plant: Plant::Lancaster,
+            });
+        }
+
+        let output = failure.generate_output().unwrap();
+        let qtys: Vec<String> = output.iter().map(|r| r.matl_qty.to_string()).collect();
+        assert_eq!(qtys, vec!["400.001", "400.000", "200.000"]);
+        assert_eq!(output.iter().map(|r| r.matl_qty).sum::<Qty>(), "1000.001".parse().unwrap());
+    }
+
+    #[test]
+    fn status_and_confirmation_file() {
+        let mut failure = Failure::try_from(
+            String::from("Planned order not found for 1210123A-X1A, D-1210123-10004, 5.000, Sigmanest Program:54091")
+        ).unwrap();
+        assert_eq!(failure.status().to_string(), "No confirmation row");
+        assert_eq!(failure.confirmation_file(), None);
+
+        let row = test_failure().confirmation_row().unwrap().clone();
+        failure.set_confirmation_row_from(row, "archive/Production_1.ready".into());
+        assert_eq!(failure.status().to_string(), "Missing orders (5)");
+        assert_eq!(failure.confirmation_file(), Some(Path::new("archive/Production_1.ready")));
+
+        assert_eq!(test_failure().status().to_string(), "Matched");
+    }
+
+    #[test]
+    fn excluded_failure_stays_in_inbox() {
+        let mut failure = test_failure();
+        failure.exclude = true;
+
+        assert!(failure.generate_output().unwrap().is_empty());
+        assert!(failure.new_inbox_text().unwrap().contains(", 5.000,"));
+    }
+}
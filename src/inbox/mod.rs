@@ -3,6 +3,13 @@ mod failure;
 pub use failure::{Failure, FailureMatchStatus};
 
 pub mod cnf_files;
+pub mod compare;
+pub mod consolidate;
 
+pub mod cogi;
 pub mod cohv;
+pub mod discovery;
 pub mod parsers;
+pub mod transfer;
+pub mod validation;
+pub mod workflow;
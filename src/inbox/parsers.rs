@@ -1,15 +1,20 @@
 
-use calamine::DataType;
-use regex::Regex;
-
-use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::api::Order;
+#[cfg(feature = "excel")]
+use crate::api::{OrderData, Plant, Wbs};
+#[cfg(feature = "excel")]
+use crate::excel::XlsxTableReader;
 
-use crate::api::{Order, OrderData};
-use crate::excel::{XlsxTableReader, HeaderColumn};
-use super::{Failure, cohv::Header};
+#[cfg(feature = "excel")]
+pub(crate) use crate::excel::SPREADSHEET_EXTENSIONS;
+/// workbooks are detected by signature only, and can't be read without the `excel` feature
+#[cfg(not(feature = "excel"))]
+pub(crate) const SPREADSHEET_EXTENSIONS: [&str; 0] = [];
+use super::{Failure, cohv::{list_cells, split_cells, CohvAliases, CohvListParser, Header, DATA_ROW}};
 
 pub fn parse_failures(failures: impl Iterator<Item = impl ToString>) -> Vec<anyhow::Result<Failure>> {
     failures
@@ -17,113 +22,407 @@ pub fn parse_failures(failures: impl Iterator<Item = impl ToString>) -> Vec<anyh
         .collect()
 }
 
-enum ParsingMode {
-    Header,
-    Row(Header)
+
+/// Row of a COHV excel export, with columns named by [`super::cohv::CohvColumn::name`]
+#[cfg(feature = "excel")]
+#[derive(Debug, Deserialize)]
+struct CohvRow {
+    #[serde(rename = "Order")]
+    order: u32,
+    #[serde(rename = "Material")]
+    matl: String,
+    #[serde(rename = "Target qty")]
+    qty: u32,
+    #[serde(rename = "WBS Element", deserialize_with = "Wbs::deserialize")]
+    wbs: Wbs,
+    #[serde(rename = "Order Type")]
+    order_type: String,
+    #[serde(rename = "Plant")]
+    plant: Plant,
 }
 
-pub fn parse_cohv_txt(cohv_file: PathBuf) -> io::Result<Vec<Order>> {
-    let data_row = Regex::new(r"^(?:\|?[^\|]+)*\|$")
-        .expect("Failed to build DATA_ROW regex");
+#[cfg(feature = "excel")]
+impl TryFrom<CohvRow> for Order {
+    type Error = anyhow::Error;
 
-    let mut results = Vec::new();
+    fn try_from(row: CohvRow) -> anyhow::Result<Self> {
+        let data = OrderData { id: row.order, mark: row.matl, qty: row.qty, wbs: row.wbs, plant: row.plant };
+
+        match row.order_type.as_str() {
+            "PP01" | "PR" => Ok( Order::new(&row.order_type, data) ),
+            _ => Err( anyhow!("Unexpected order type `{}` for order {}", row.order_type, data.id) )
+        }
+    }
+}
+
+#[cfg(feature = "excel")]
+pub fn parse_cohv_xl(cohv_file: PathBuf) -> anyhow::Result<Vec<Order>> {
+    parse_cohv_xl_with(cohv_file, CohvAliases::load_default()?)
+}
+
+/// Parse a COHV excel export, matching header columns with `aliases`
+#[cfg(feature = "excel")]
+pub fn parse_cohv_xl_with(cohv_file: PathBuf, aliases: CohvAliases) -> anyhow::Result<Vec<Order>> {
+    let vals = read_cohv_xl(cohv_file, aliases)?
+        .into_iter()
+        .filter_map(|r| r.ok())
+        .collect();
 
-    let file = File::open(cohv_file)?;
-    let reader = io::BufReader::new(file);
+    Ok(vals)
+}
+
+#[cfg(feature = "excel")]
+fn read_cohv_xl(cohv_file: PathBuf, aliases: CohvAliases) -> anyhow::Result<Vec<anyhow::Result<Order>>> {
+    let mut reader = XlsxTableReader::<CohvRow>::new()
+        .map_header(move |text| aliases.match_header(text).map(|c| c.name().to_string()));
+    let vals = reader.read_file(cohv_file)?
+        .into_iter()
+        .map(|r| r.and_then(Order::try_from))
+        .collect();
+
+    Ok(vals)
+}
+
+#[cfg(not(feature = "excel"))]
+fn read_cohv_xl(cohv_file: PathBuf, _aliases: CohvAliases) -> anyhow::Result<Vec<anyhow::Result<Order>>> {
+    Err( anyhow!("Cannot read {}: workbook exports need the `excel` feature", cohv_file.display()) )
+}
 
-    let mut mode = ParsingMode::Header;
+/// Extensions of COHV exports, in order of preference
+pub const COHV_EXPORT_EXTENSIONS: [&str; 9] = ["xlsx", "xlsm", "xls", "xlsb", "ods", "txt", "tsv", "csv", "dat"];
 
-    for line in reader.lines() {
-        if let Ok(l) = line {
-            if data_row.is_match(&l) {
-                match mode {
-                    ParsingMode::Header => mode = ParsingMode::Row(Header::try_from(l).unwrap()),
-                    ParsingMode::Row(ref header) => results.push(header.parse_row(l)),
+/// Lines scanned for the header when detecting the format of a text export
+const HEADER_SCAN_LINES: usize = 50;
+
+/// Format of a COHV export file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CohvFormat {
+    /// Excel or OpenDocument workbook
+    Spreadsheet,
+    /// `|` delimited list download
+    PipeList,
+    /// Delimited text ("Text with tabs" or CSV), with its delimiter
+    Delimited(u8),
+}
+
+impl CohvFormat {
+    /// Delimiters tried for delimited text, in order
+    const DELIMITERS: [u8; 3] = [b'\t', b';', b','];
+
+    /// Detect the format of a COHV export from its extension or content
+    pub fn detect(path: &Path, aliases: &CohvAliases) -> anyhow::Result<Self> {
+        if is_spreadsheet(path)? {
+            return Ok( Self::Spreadsheet );
+        }
+
+        Self::detect_text(&read_text(path)?, aliases)
+            .ok_or_else(|| anyhow!("Could not detect COHV export format of {}: no header row found", path.display()))
+    }
+
+    /// Detect the format of a text export by the first line that matches a header
+    fn detect_text(text: &str, aliases: &CohvAliases) -> Option<Self> {
+        Self::detect_text_with(text, |cells| Header::from_cells(cells, aliases).is_ok())
+    }
+
+    /// Detect the format of a text export by the first line whose cells `is_header` accepts
+    pub(crate) fn detect_text_with(text: &str, is_header: impl Fn(&[&str]) -> bool) -> Option<Self> {
+        for line in text.lines().take(HEADER_SCAN_LINES).map(str::trim) {
+            if DATA_ROW.is_match(line) && is_header(&split_cells(line)) {
+                return Some( Self::PipeList );
+            }
+
+            for delim in Self::DELIMITERS {
+                let cells: Vec<&str> = line.split(delim as char).collect();
+                if cells.len() > 1 && is_header(&cells) {
+                    return Some( Self::Delimited(delim) );
                 }
             }
         }
+
+        None
+    }
+}
+
+/// Whether a file is a workbook, by extension or file signature
+pub(crate) fn is_spreadsheet(path: &Path) -> io::Result<bool> {
+    let by_ext = path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| SPREADSHEET_EXTENSIONS.iter().any(|ext| e.eq_ignore_ascii_case(ext)));
+    if by_ext {
+        return Ok(true);
     }
 
-    Ok(results)
+    let mut magic = [0u8; 4];
+    let read = io::Read::read(&mut File::open(path)?, &mut magic)?;
+
+    // OLE compound file (xls) or zip archive (xlsx, xlsb, ods)
+    Ok( read == 4 && matches!(magic, [0xD0, 0xCF, 0x11, 0xE0] | [b'P', b'K', 3, 4]) )
 }
 
+/// Start of a text file read when looking for a header: enough for [`HEADER_SCAN_LINES`] of a wide export
+const HEADER_SCAN_BYTES: u64 = 64 * 1024;
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
-enum CohvHeader {
-    Order,
-    Matl,
-    Qty,
-    Wbs,
-    Type,
-    Plant,
+/// Read a text export, which SAP GUI may write as UTF-16 or UTF-8 with a BOM
+pub(crate) fn read_text(path: &Path) -> io::Result<String> {
+    Ok( text_lines(File::open(path)?)?.collect::<io::Result<Vec<_>>>()?.join("\n") )
 }
 
-impl HeaderColumn for CohvHeader {
-    type Row = Order;
+/// Read the start of a text export, for format detection
+fn read_text_head(path: &Path) -> io::Result<String> {
+    Ok( text_lines(io::Read::take(File::open(path)?, HEADER_SCAN_BYTES))?.collect::<io::Result<Vec<_>>>()?.join("\n") )
+}
 
-    fn column_name(&self) -> String {
-        use CohvHeader::*;
-    
-        match self {
-            Order => "Order",
-            Matl => "Material",
-            Qty => "Qty",
-            Wbs => "WBS Element",
-            Type => "Order Type",
-            Plant => "Plant"
-        }.into()
-    }
+/// Lines of a text export, decoded as UTF-16 or UTF-8 by its BOM
+///
+/// read as they are needed, so a large export is not held in memory.
+/// Invalid characters are replaced rather than failing the export.
+pub(crate) fn text_lines<R: io::Read>(reader: R) -> io::Result<TextLines<io::BufReader<R>>> {
+    let mut reader = io::BufReader::new(reader);
+    let (bom, utf16) = match io::BufRead::fill_buf(&mut reader)? {
+        [0xFF, 0xFE, ..] => (2, true),
+        [0xEF, 0xBB, 0xBF, ..] => (3, false),
+        _ => (0, false),
+    };
+    io::BufRead::consume(&mut reader, bom);
+
+    Ok( TextLines { reader, utf16 } )
+}
+
+/// Iterator over the lines of a text export, see [`text_lines`]
+pub(crate) struct TextLines<R> {
+    reader: R,
+    /// UTF-16 little endian, otherwise UTF-8
+    utf16: bool,
+}
+
+impl<R: io::BufRead> TextLines<R> {
+    /// Read the bytes of a UTF-16 line, up to and including its `\n` code unit
+    fn read_utf16_line(&mut self, bytes: &mut Vec<u8>) -> io::Result<()> {
+        loop {
+            if self.reader.read_until(b'\n', bytes)? == 0 {
+                return Ok(());
+            }
 
-    fn columns_to_match() -> Vec<Self> where Self: Sized {
-        vec![
-            CohvHeader::Order,
-            CohvHeader::Matl,
-            CohvHeader::Qty,
-            CohvHeader::Wbs,
-            CohvHeader::Type,
-            CohvHeader::Plant,
-        ]
+            // `\n` is the low byte of a code unit if it ends at an even index
+            if bytes.len() % 2 == 1 {
+                let mut high = [0u8];
+                if io::Read::read(&mut self.reader, &mut high)? == 0 {
+                    return Ok(());
+                }
+
+                bytes.push(high[0]);
+                if high[0] == 0 {
+                    return Ok(());
+                }
+            }
+        }
     }
+}
+
+impl<R: io::BufRead> Iterator for TextLines<R> {
+    type Item = io::Result<String>;
 
-    fn match_header_column(column_text: &str) -> Option<Self>
-        where Self: Sized
-    {
-        match column_text {
-            "Order"                  => Some( Self::Order ),
-            "Material Number"        => Some( Self::Matl  ),
-            "Order quantity (GMEIN)" => Some( Self::Qty   ),
-            "WBS Element"            => Some( Self::Wbs   ),
-            "Order Type"             => Some( Self::Type  ),
-            "Plant"                  => Some( Self::Plant ),
-            _                        => None
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut bytes = Vec::new();
+        let read = match self.utf16 {
+            true  => self.read_utf16_line(&mut bytes),
+            false => self.reader.read_until(b'\n', &mut bytes).map(|_| ()),
+        };
+
+        if let Err(e) = read {
+            return Some( Err(e) );
+        }
+        if bytes.is_empty() {
+            return None;
         }
+
+        let line = match self.utf16 {
+            true => {
+                let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+                String::from_utf16_lossy(&units)
+            },
+            false => String::from_utf8_lossy(&bytes).into_owned(),
+        };
+
+        Some( Ok(line.trim_end_matches(['\r', '\n']).to_string()) )
     }
+}
+
+/// Cells of each line of a text export, with its line number
+///
+/// `|` list page titles and `----` separators, and blank lines, are left out.
+/// Spreadsheets have no text lines.
+pub(crate) fn text_rows(text: &str, format: CohvFormat) -> Vec<anyhow::Result<(usize, Vec<String>)>> {
+    let delim = match format {
+        CohvFormat::Spreadsheet => return Vec::new(),
+        CohvFormat::PipeList => {
+            return text.lines()
+                .enumerate()
+                .filter_map(|(i, line)| list_cells(line).map(|cells| Ok( (i + 1, cells.into_iter().map(String::from).collect()) )))
+                .collect();
+        },
+        CohvFormat::Delimited(delim) => delim,
+    };
 
-    fn parse_row(header: &HashMap<Self, usize>, row: &[DataType]) -> anyhow::Result<Self::Row>
-        where Self: Sized
-    {
-        // TODO: handle parsing errors (get_string/get_int)
-        let order = row[*header.get(&Self::Order).unwrap()].get_string().ok_or( anyhow!("Failed to coerce order to String") )?.parse()?;
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delim)
+        .from_reader(text.as_bytes());
 
-        let matl  = row[*header.get(&Self::Matl).unwrap() ].get_string().ok_or( anyhow!("Failed to read Material as String") )?.into();
-        let qty   = row[*header.get(&Self::Qty).unwrap()  ].get_float() .ok_or( anyhow!("Failed to read qty as Float") )? as u32;
-        let wbs   = row[*header.get(&Self::Wbs).unwrap()  ].get_string().ok_or( anyhow!("Failed to read Wbs Element") )?.try_into()?;
-        let _type = row[*header.get(&Self::Type).unwrap() ].get_string().ok_or( anyhow!("Failed to read Order Type") )?;
-        let plant = row[*header.get(&Self::Plant).unwrap()].get_string().ok_or( anyhow!("Failed to read Plant") )?.into();
+    reader.records()
+        .map(|record| {
+            let record = record?;
+            let line = record.position().map_or(0, |p| p.line() as usize);
 
-        let data = OrderData { id: order, mark: matl, qty, wbs, plant };
+            Ok( (line, record.iter().map(String::from).collect::<Vec<_>>()) )
+        })
+        .filter(|row| !matches!(row, Ok((_, cells)) if cells.iter().all(|c| c.trim().is_empty())))
+        .collect()
+}
+
+/// Parse a delimited text export, re-matching repeated headers and skipping titles and total rows
+///
+/// `|` list downloads are parsed line by line with [`CohvListParser`]
+fn parse_cohv_text(text: &str, format: CohvFormat, aliases: &CohvAliases) -> Vec<anyhow::Result<Order>> {
+    let mut header: Option<Header> = None;
+    let mut results = Vec::new();
+    for row in text_rows(text, format) {
+        let (line, cells) = match row {
+            Ok(row) => row,
+            Err(e) => {
+                results.push( Err(e) );
+                continue;
+            }
+        };
+
+        let cells: Vec<&str> = cells.iter().map(String::as_str).collect();
+        if let Ok(h) = Header::from_cells(&cells, aliases) {
+            header = Some(h);
+            continue;
+        }
 
-        Ok( Order::new(_type, data) )
+        match &header {
+            Some(h) if !h.is_total_cells(&cells) => {
+                results.push( h.parse_cells(&cells).map_err(|e| anyhow!("line {}: {}", line, e)) );
+            },
+            _ => ()
+        }
     }
+
+    results
 }
 
-pub fn parse_cohv_xl(cohv_file: PathBuf) -> anyhow::Result<Vec<Order>> {
-    
-    let mut reader = XlsxTableReader::<CohvHeader>::new();
-    let vals = reader.read_file(cohv_file)?
+/// Read a COHV export in any supported format, returning the result of each row
+pub fn read_cohv(cohv_file: PathBuf, aliases: CohvAliases) -> anyhow::Result<Vec<anyhow::Result<Order>>> {
+    match CohvFormat::detect(&cohv_file, &aliases)? {
+        CohvFormat::Spreadsheet => read_cohv_xl(cohv_file, aliases),
+        CohvFormat::PipeList => Ok( CohvListParser::new(text_lines(File::open(&cohv_file)?)?, aliases).collect() ),
+        format => Ok( parse_cohv_text(&read_text(&cohv_file)?, format, &aliases) ),
+    }
+}
+
+/// Parse a COHV export: excel workbook, `|` list download, tab delimited text or CSV
+pub fn parse_cohv(cohv_file: PathBuf) -> anyhow::Result<Vec<Order>> {
+    let vals = read_cohv(cohv_file, CohvAliases::load_default()?)?
         .into_iter()
         .filter_map(|r| r.ok())
         .collect();
 
     Ok(vals)
 }
+
+/// Whether a text file is a COHV export, rather than an inbox error list
+///
+/// only the start of the file is read
+pub fn is_cohv_text(path: &Path) -> bool {
+    let aliases = match CohvAliases::load_default() {
+        Ok(aliases) => aliases,
+        Err(_) => return false
+    };
+
+    read_text_head(path)
+        .ok()
+        .and_then(|text| CohvFormat::detect_text(&text, &aliases))
+        .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn detect_text_formats() {
+        let aliases = CohvAliases::default();
+        let header = ["Order Type", "Order", "Material", "Target qty", "WBS Element", "Plant"];
+
+        let pipe = format!("Order Info System\n|{}|\n", header.join("|"));
+        assert_eq!(CohvFormat::detect_text(&pipe, &aliases), Some(CohvFormat::PipeList));
+
+        let tsv = format!("\n{}\nPR\t1234567\t1200248A-X1A\t3\tD-1200248-10001\tHS01\n", header.join("\t"));
+        assert_eq!(CohvFormat::detect_text(&tsv, &aliases), Some(CohvFormat::Delimited(b'\t')));
+
+        let csv = format!("{}\n", header.join(","));
+        assert_eq!(CohvFormat::detect_text(&csv, &aliases), Some(CohvFormat::Delimited(b',')));
+
+        assert_eq!(CohvFormat::detect_text("1200248A-X1A\tD-1200248-10001\t2\n", &aliases), None);
+    }
+
+    #[test]
+    fn workbook_detected_by_signature() {
+        let path = std::env::temp_dir().join("sap-error-utils-cohv-workbook.dat");
+        fs::write(&path, b"PK\x03\x04 not really a workbook").unwrap();
+
+        assert_eq!(CohvFormat::detect(&path, &CohvAliases::default()).unwrap(), CohvFormat::Spreadsheet);
+        let err = read_cohv(path.clone(), CohvAliases::default()).unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        // without the `excel` feature, workbooks are recognised but not read
+        if cfg!(not(feature = "excel")) {
+            assert!(err.to_string().ends_with("workbook exports need the `excel` feature"));
+        }
+    }
+
+    #[test]
+    fn utf16_pipe_list() {
+        let list = "\
+Order Info System
+----------------------------------------------------------------
+|Order Type|Order  |Material    |Target qty|WBS Element    |Plant|
+|PR        |1234567|1200248A-X1A|     1.234|D-1200248-10001|HS01 |
+|*         |       |            |     1.234|               |     |
+----------------------------------------------------------------
+|Order Type|Order  |Material    |Target qty|WBS Element    |Plant|
+|PP01      |1234568|1200248A-X1B|         2|D-1200248-10001|HS02 |
+";
+        let path = std::env::temp_dir().join( format!("sap-error-utils-cohv-utf16-{}.txt", std::process::id()) );
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend( list.replace('\n', "\r\n").encode_utf16().flat_map(u16::to_le_bytes) );
+        fs::write(&path, bytes).unwrap();
+
+        assert_eq!(read_text(&path).unwrap().lines().nth(2).unwrap(), "|Order Type|Order  |Material    |Target qty|WBS Element    |Plant|");
+
+        let results = read_cohv(path.clone(), CohvAliases::default()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(matches!(&results[0], Ok(Order::PlannedOrder(data)) if data.qty == 1234));
+        assert!(matches!(&results[1], Ok(Order::ProductionOrder(data)) if data.id == 1234568));
+    }
+
+    #[test]
+    fn parse_csv() {
+        let csv = "\
+Order Type;Order;Material;Target qty;WBS Element;Plant
+PR;1234567;1200248A-X1A;1.200;D-1200248-10001;HS01
+*;;;1.200;;
+PP01;\"1234568\";1200248A-X1B;2;D-1200248-10001;HS02
+";
+
+        let results = parse_cohv_text(csv, CohvFormat::Delimited(b';'), &CohvAliases::default());
+        assert_eq!(results.len(), 2);
+        assert!(matches!(&results[0], Ok(Order::PlannedOrder(data)) if data.qty == 1200));
+        assert!(matches!(&results[1], Ok(Order::ProductionOrder(data)) if data.id == 1234568));
+    }
+}
@@ -0,0 +1,136 @@
+//! Cross-plant consumption detection and plant to plant transfers
+
+use crate::api::{CnfFileRow, Plant, StorageLocations, TransferFileRow};
+use super::Failure;
+
+/// Plant the material of an archived row is stored in
+///
+/// the plant of its storage location if known, otherwise the archived part plant
+pub fn material_plant(archived: &CnfFileRow, locations: &StorageLocations) -> Plant {
+    archived.matl_loc
+        .as_deref()
+        .and_then(|loc| locations.plant(loc))
+        .unwrap_or(archived.plant)
+}
+
+/// Transfer for a generated row that consumes material stored in another plant
+///
+/// `archived` is the confirmation row `row` was generated from. If the material plant
+/// differs from the plant of `row` (the order plant), the material is transferred to that
+/// plant, and `row` is changed to consume it from the receiving location.
+/// Fails if the order plant has no receiving location, since the row would still
+/// consume from the location in the other plant.
+pub fn transfer_for(archived: &CnfFileRow, row: &mut CnfFileRow, locations: &StorageLocations) -> Result<Option<TransferFileRow>, String> {
+    let from_plant = material_plant(archived, locations);
+    if from_plant == row.plant {
+        return Ok(None);
+    }
+
+    let to_loc = match locations.receiving_location(row.plant) {
+        Some(loc) => loc.to_string(),
+        None => return Err( format!(
+            "{}: {} is stored in {:?}, but {:?} has no storage location to transfer it to",
+            row.mark, row.matl, from_plant, row.plant
+        ) ),
+    };
+
+    let transfer = TransferFileRow {
+        matl: row.matl.clone(),
+        matl_wbs: row.matl_wbs.clone(),
+        matl_qty: row.matl_qty,
+        matl_uom: row.matl_uom.clone(),
+        from_plant,
+        from_loc: row.matl_loc.clone(),
+        to_plant: row.plant,
+        to_loc: Some(to_loc.clone()),
+        program: row.program.clone(),
+    };
+
+    row.matl_loc = Some(to_loc);
+
+    Ok( Some(transfer) )
+}
+
+/// Generate a failure's production rows, with the transfers they need
+///
+/// transfers must be posted before the production rows, or the consumption fails into COGI
+pub fn generate_with_transfers(failure: &Failure, locations: &StorageLocations) -> Result<(Vec<CnfFileRow>, Vec<TransferFileRow>), String> {
+    let mut rows = failure.generate_output()?;
+    let transfers = match failure.confirmation_row() {
+        Some(archived) => rows
+            .iter_mut()
+            .filter_map(|row| transfer_for(archived, row, locations).transpose())
+            .collect::<Result<_, _>>()?,
+        None => Vec::new(),
+    };
+
+    Ok( (rows, transfers) )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::OrderData;
+    use crate::inbox::cnf_files::{deserialize_record, HEADERS};
+
+    fn archived() -> CnfFileRow {
+        let text = "1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t3\tEA\t50W-0008\t\t30.000\tIN2\tK2\tHS01\t54091";
+        let record = csv::StringRecord::from(text.split('\t').collect::<Vec<_>>());
+
+        deserialize_record(&record, &HEADERS).unwrap()
+    }
+
+    fn order(plant: Plant) -> OrderData {
+        OrderData { id: 1, mark: "1210123A-X1A".into(), qty: 3, wbs: "D-1210123-10004".try_into().unwrap(), plant }
+    }
+
+    #[test]
+    fn same_plant_no_transfer() {
+        let archived = archived();
+        let mut row = archived.modify_with(&order(Plant::Lancaster));
+
+        assert!(transfer_for(&archived, &mut row, &StorageLocations::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn order_in_other_plant() {
+        let archived = archived();
+        let mut locations = StorageLocations::default();
+        locations.add("K2", Plant::Lancaster);
+        locations.add("W1", Plant::Williamsport);
+
+        let mut row = archived.modify_with(&order(Plant::Williamsport));
+        let transfer = transfer_for(&archived, &mut row, &locations).unwrap().unwrap();
+
+        assert_eq!((transfer.from_plant, transfer.to_plant), (Plant::Lancaster, Plant::Williamsport));
+        assert_eq!((transfer.from_loc.as_deref(), transfer.to_loc.as_deref()), (Some("K2"), Some("W1")));
+        assert_eq!(transfer.matl_qty, row.matl_qty);
+        assert_eq!(row.matl_loc.as_deref(), Some("W1"));
+    }
+
+    #[test]
+    fn location_in_other_plant() {
+        let archived = archived();
+        let mut locations = StorageLocations::default();
+        locations.add("K2", Plant::Williamsport);
+        locations.add("K1", Plant::Lancaster);
+
+        let mut row = archived.modify_with(&order(Plant::Lancaster));
+        let transfer = transfer_for(&archived, &mut row, &locations).unwrap().unwrap();
+
+        assert_eq!((transfer.from_plant, transfer.to_plant), (Plant::Williamsport, Plant::Lancaster));
+        assert_eq!(transfer.to_loc.as_deref(), Some("K1"));
+        assert_eq!(row.matl_loc.as_deref(), Some("K1"));
+    }
+
+    #[test]
+    fn no_receiving_location() {
+        let archived = archived();
+        let mut locations = StorageLocations::default();
+        locations.add("K2", Plant::Williamsport);
+
+        let mut row = archived.modify_with(&order(Plant::Lancaster));
+        let err = transfer_for(&archived, &mut row, &locations).unwrap_err();
+        assert!(err.contains("no storage location to transfer it to"));
+    }
+}
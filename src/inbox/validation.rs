@@ -0,0 +1,344 @@
+//! Validation of confirmation rows before they are written
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use crate::api::{CnfFileRow, Qty, StorageLocations, Uom, UomRegistry, Wbs};
+
+/// Default allowed difference of a row's area per part from its mark's average (10%)
+pub const AREA_TOLERANCE: f64 = 0.1;
+
+/// How serious a [`Finding`] is
+///
+/// rows with errors are not written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "Warning"),
+            Self::Error   => write!(f, "Error"),
+        }
+    }
+}
+
+/// A rule a row broke
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Name of the [`Rule`]
+    pub rule: &'static str,
+    pub mark: String,
+    pub wbs: Wbs,
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} <{}>: {}", self.mark, self.wbs, self.message)
+    }
+}
+
+/// Check of a single confirmation row
+pub trait Rule: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn severity(&self) -> Severity;
+    /// Returns why the row breaks the rule, if it does
+    fn check(&self, row: &CnfFileRow) -> Option<String>;
+}
+
+/// Part location must be `PROD`
+pub struct PartLocation;
+
+impl Rule for PartLocation {
+    fn name(&self) -> &'static str { "Part location" }
+    fn severity(&self) -> Severity { Severity::Error }
+
+    fn check(&self, row: &CnfFileRow) -> Option<String> {
+        (row.part_loc != "PROD").then(|| format!("part location is `{}`, not PROD", row.part_loc))
+    }
+}
+
+/// Part unit of measure must be `EA`
+pub struct PartUom;
+
+impl Rule for PartUom {
+    fn name(&self) -> &'static str { "Part unit" }
+    fn severity(&self) -> Severity { Severity::Error }
+
+    fn check(&self, row: &CnfFileRow) -> Option<String> {
+        (row.part_uom != Uom::Ea).then(|| format!("part unit is {}, not EA", row.part_uom))
+    }
+}
+
+/// Part and material quantities must be positive
+pub struct PositiveQty;
+
+impl Rule for PositiveQty {
+    fn name(&self) -> &'static str { "Positive qty" }
+    fn severity(&self) -> Severity { Severity::Error }
+
+    fn check(&self, row: &CnfFileRow) -> Option<String> {
+        if row.part_qty == 0 {
+            return Some( "part qty is 0".into() );
+        }
+
+        (row.matl_qty <= Qty::ZERO).then(|| format!("material qty is {}", row.matl_qty))
+    }
+}
+
+/// Cost center WBS elements must have a numeric (2xxx) cost center
+pub struct CostCenter;
+
+impl Rule for CostCenter {
+    fn name(&self) -> &'static str { "Cost center" }
+    fn severity(&self) -> Severity { Severity::Error }
+
+    fn check(&self, row: &CnfFileRow) -> Option<String> {
+        match row.part_wbs {
+            Wbs::CostCenter { cc } if !Wbs::COST_CENTERS.contains(&cc) => Some( format!("cost center {} is not a 2xxx cost center", cc) ),
+            _ => None
+        }
+    }
+}
+
+/// Material quantity must belong with its unit (see [`UomRegistry::validate`])
+pub struct MaterialUom(pub UomRegistry);
+
+impl Rule for MaterialUom {
+    fn name(&self) -> &'static str { "Material unit" }
+    fn severity(&self) -> Severity { Severity::Error }
+
+    fn check(&self, row: &CnfFileRow) -> Option<String> {
+        self.0.validate(row.matl_qty, &row.matl_uom, &row.matl).err()
+    }
+}
+
+/// Mark job should match the part WBS job
+pub struct MarkJob;
+
+impl Rule for MarkJob {
+    fn name(&self) -> &'static str { "Mark job" }
+    fn severity(&self) -> Severity { Severity::Warning }
+
+    fn check(&self, row: &CnfFileRow) -> Option<String> {
+        row.check_mark_job().err()
+    }
+}
+
+/// Material storage location should be in the part plant
+pub struct StoragePlant(pub StorageLocations);
+
+impl Rule for StoragePlant {
+    fn name(&self) -> &'static str { "Storage location plant" }
+    fn severity(&self) -> Severity { Severity::Warning }
+
+    fn check(&self, row: &CnfFileRow) -> Option<String> {
+        let loc = row.matl_loc.as_deref()?;
+
+        match self.0.plant(loc) {
+            Some(plant) if plant != row.plant => Some( format!("material location {} is in {}, part is in {}", loc, plant, row.plant) ),
+            _ => None
+        }
+    }
+}
+
+/// Area per part should be within a tolerance of the mark's archived average
+pub struct AreaTolerance {
+    pub history: AreaHistory,
+    /// Allowed difference, as a fraction of the average
+    pub tolerance: f64,
+}
+
+impl Rule for AreaTolerance {
+    fn name(&self) -> &'static str { "Area per part" }
+    fn severity(&self) -> Severity { Severity::Warning }
+
+    fn check(&self, row: &CnfFileRow) -> Option<String> {
+        let average = self.history.average(&row.mark)?;
+        let area = row.area_per_ea();
+
+        ((area - average).abs() > average * self.tolerance)
+            .then(|| format!("area per part {:.3} is not within {:.0}% of the archived average {:.3}", area, self.tolerance * 100., average))
+    }
+}
+
+/// Area per part of archived confirmation rows, by mark
+#[derive(Debug, Clone, Default)]
+pub struct AreaHistory {
+    // mark -> (sum of area per part, rows)
+    areas: HashMap<String, (f64, usize)>,
+}
+
+impl AreaHistory {
+    pub fn add(&mut self, row: &CnfFileRow) {
+        if row.part_qty == 0 {
+            return;
+        }
+
+        let entry = self.areas.entry(row.mark.clone()).or_default();
+        entry.0 += row.area_per_ea();
+        entry.1 += 1;
+    }
+
+    /// Average area per part of a mark, if it has archived rows
+    pub fn average(&self, mark: &str) -> Option<f64> {
+        self.areas
+            .get(mark)
+            .map(|(sum, count)| sum / *count as f64)
+    }
+}
+
+/// Rules that confirmation rows are checked against
+pub struct Validator {
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl Default for Validator {
+    /// Rules that need no archive history or configuration tables
+    fn default() -> Self {
+        Self::empty()
+            .rule(PartLocation)
+            .rule(PartUom)
+            .rule(PositiveQty)
+            .rule(CostCenter)
+            .rule(MarkJob)
+    }
+}
+
+impl Validator {
+    /// Validator with no rules
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Default rules, plus unit, storage location and (if any history) area per part checks
+    pub fn with_tables(uoms: UomRegistry, locations: StorageLocations, history: AreaHistory) -> Self {
+        Self::default()
+            .rule(MaterialUom(uoms))
+            .rule(StoragePlant(locations))
+            .rule(AreaTolerance { history, tolerance: AREA_TOLERANCE })
+    }
+
+    pub fn rule(mut self, rule: impl Rule + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+
+        self
+    }
+
+    pub fn validate<'a>(&self, rows: impl IntoIterator<Item = &'a CnfFileRow>) -> Findings {
+        let mut findings = Vec::new();
+        for row in rows {
+            for rule in &self.rules {
+                if let Some(message) = rule.check(row) {
+                    findings.push(Finding {
+                        severity: rule.severity(),
+                        rule: rule.name(),
+                        mark: row.mark.clone(),
+                        wbs: row.part_wbs.clone(),
+                        message,
+                    });
+                }
+            }
+        }
+
+        Findings(findings)
+    }
+}
+
+/// Findings of a [`Validator`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Findings(pub Vec<Finding>);
+
+impl Findings {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.0.iter().any(|f| f.severity == Severity::Error)
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.0.iter().filter(|f| f.severity == severity).count()
+    }
+
+    /// Findings grouped by severity, errors first, leaving out empty groups
+    pub fn by_severity(&self) -> Vec<(Severity, Vec<&Finding>)> {
+        [Severity::Error, Severity::Warning]
+            .into_iter()
+            .map(|severity| (severity, self.0.iter().filter(|f| f.severity == severity).collect::<Vec<_>>()))
+            .filter(|(_, findings)| !findings.is_empty())
+            .collect()
+    }
+
+    /// Findings as log lines, grouped by severity
+    pub fn lines(&self) -> Vec<String> {
+        self.by_severity()
+            .into_iter()
+            .flat_map(|(severity, findings)| findings.into_iter().map(move |f| format!("{}: [{}] {}", severity, f.rule, f)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Plant;
+    use crate::inbox::cnf_files::{deserialize_record, HEADERS};
+
+    fn row(text: &str) -> CnfFileRow {
+        let record = csv::StringRecord::from(text.split('\t').collect::<Vec<_>>());
+
+        deserialize_record(&record, &HEADERS).unwrap()
+    }
+
+    #[test]
+    fn default_rules() {
+        let good = row("1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t3\tEA\t50W-0008\t\t30.000\tIN2\tK2\tHS01\t54091");
+        let bad = row("1210123A-X1B\tS-1210123\tD-1210124-10004\tSTOCK\t3\tEA\t50W-0008\t\t0.000\tIN2\tK2\tHS01\t54091");
+
+        let findings = Validator::default().validate([&good, &bad]);
+        assert!(findings.has_errors());
+        assert_eq!((findings.count(Severity::Error), findings.count(Severity::Warning)), (2, 1));
+        assert!(findings.0.iter().all(|f| f.mark == "1210123A-X1B"));
+
+        let groups = findings.by_severity();
+        assert_eq!(groups[0].0, Severity::Error);
+        assert_eq!(groups[1].1[0].rule, "Mark job");
+    }
+
+    #[test]
+    fn cost_center_out_of_range() {
+        let good = row("1210123A-X1A\tS-HSU\tS-HSU-2-2062\tPROD\t3\tEA\t50W-0008\t\t30.000\tIN2\tK2\tHS01\t54091");
+        let bad = row("1210123A-X1A\tS-HSU\tS-HSU-2-5062\tPROD\t3\tEA\t50W-0008\t\t30.000\tIN2\tK2\tHS01\t54091");
+
+        assert!(Validator::empty().rule(CostCenter).validate([&good]).is_empty());
+
+        let findings = Validator::empty().rule(CostCenter).validate([&bad]);
+        assert!(findings.has_errors());
+        assert_eq!(findings.0[0].message, "cost center 5062 is not a 2xxx cost center");
+    }
+
+    #[test]
+    fn table_rules() {
+        let mut history = AreaHistory::default();
+        history.add( &row("1210123A-X1A\tS-1210123\tD-1210123-10001\tPROD\t2\tEA\t50W-0008\t\t20.000\tIN2\tK2\tHS01\t54000") );
+
+        let mut locations = StorageLocations::default();
+        locations.add("W1", Plant::Williamsport);
+
+        let validator = Validator::with_tables(UomRegistry::default(), locations, history);
+        let close = row("1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t3\tEA\t50W-0008\t\t31.000\tIN2\tK2\tHS01\t54091");
+        let far = row("1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t3\tEA\t50W-0008\t\t45.000\tIN2\tW1\tHS01\t54091");
+
+        assert!(validator.validate([&close]).is_empty());
+
+        let rules: Vec<&str> = validator.validate([&far]).0.iter().map(|f| f.rule).collect();
+        assert_eq!(rules, vec!["Storage location plant", "Area per part"]);
+    }
+}
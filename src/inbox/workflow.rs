@@ -0,0 +1,721 @@
+//! Inbox error workflow, independent of any user interface
+//!
+//! ```no_run
+//! use sap_error_utils::inbox::workflow::{ArchiveSource, InboxWorkflow};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let mut workflow = InboxWorkflow::new(std::fs::read_to_string("inbox.txt")?)
+//!     .archive(ArchiveSource::SapArchive(200));
+//!
+//! let parsed = workflow.parse()?;
+//! println!("{} failures, {} unreadable lines", parsed.failures, parsed.errors.len());
+//!
+//! workflow.match_confirmations(&mut ())?;
+//! workflow.load_orders()?;
+//! workflow.apply_orders(&mut ())?;
+//!
+//! let generated = workflow.write_production()?;
+//! println!("report written to {}", generated.report_file.display());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{fs, io};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local};
+
+use crate::api::{CnfFileRow, IssueFileRow, Order, OrderData, StorageLocations, TransferFileRow, UomRegistry};
+#[cfg(feature = "history")]
+use crate::history::History;
+use crate::history::{FileRole, RunFile};
+use crate::paths::{self, timestamped_file, timestamped_file_at};
+use crate::report::{report_path, RunKind, RunParams, RunReport};
+use super::{Failure, FailureMatchStatus};
+use super::cnf_files::write_file;
+use super::cogi::{self, read_cogi, Correction};
+use super::cohv::CohvAliases;
+use super::consolidate::Consolidation;
+use super::compare::{self, parse_inbox, match_cnf_rows_with, Progress};
+use super::discovery::{ExportInfo, ExportSearch, CohvExport};
+use super::transfer::generate_with_transfers;
+use super::validation::{AreaHistory, Findings, Validator};
+
+/// Where confirmation rows are searched for
+#[derive(Debug, Clone)]
+pub enum ArchiveSource {
+    /// Last `n` production files in the SAP archive
+    SapArchive(usize),
+    /// Last `n` production files in a folder
+    Folder(PathBuf, usize),
+    /// Confirmation rows already read, with the file each came from
+    Rows(Vec<(CnfFileRow, PathBuf)>),
+}
+
+impl Default for ArchiveSource {
+    fn default() -> Self {
+        Self::SapArchive(200)
+    }
+}
+
+/// Where COHV orders are read from
+#[derive(Debug, Clone)]
+pub enum CohvSource {
+    /// Newest export in a set of folders
+    Search(ExportSearch),
+    /// A specific export file
+    File(PathBuf),
+    /// Orders already read
+    Orders(Vec<Order>),
+}
+
+impl Default for CohvSource {
+    fn default() -> Self {
+        Self::Search(ExportSearch::default())
+    }
+}
+
+/// Workflow options
+#[derive(Debug, Clone, Default)]
+pub struct WorkflowOptions {
+    /// Folder generated files are written to (working directory if empty)
+    pub output_dir: PathBuf,
+    /// When the inbox errors were last changed, to check the COHV export is newer
+    pub inbox_updated: Option<DateTime<Local>>,
+    /// History database the run is recorded in, if any
+    pub history: Option<PathBuf>,
+    /// How generated rows are merged and split into files
+    pub consolidation: Consolidation,
+}
+
+/// Result of [`InboxWorkflow::parse`]
+#[derive(Debug, Clone)]
+pub struct Parsed {
+    pub failures: usize,
+    /// Lines that failed to parse
+    pub errors: Vec<String>,
+    /// Failures whose mark job does not match their WBS job
+    pub warnings: Vec<String>,
+}
+
+/// Result of [`InboxWorkflow::match_confirmations`]
+#[derive(Debug, Clone, Copy)]
+pub struct Matched {
+    pub matched: usize,
+    pub total: usize,
+}
+
+/// Result of [`InboxWorkflow::load_orders`]
+#[derive(Debug, Clone)]
+pub struct OrdersLoaded {
+    /// Export the orders were read from, if not given directly
+    pub export: Option<ExportInfo>,
+    pub orders: usize,
+    /// Freshness warnings for the export
+    pub warnings: Vec<String>,
+}
+
+/// Result of [`InboxWorkflow::apply_orders`]
+#[derive(Debug, Clone, Copy)]
+pub struct Applied {
+    /// Failures fully matched
+    pub matched: usize,
+    pub total: usize,
+    /// Planned orders (or remainder of) not applied to any failure
+    pub not_applied: usize,
+}
+
+/// Result of [`InboxWorkflow::write_production`], [`InboxWorkflow::write_issue`] and [`InboxWorkflow::write_cogi_issue`]
+#[derive(Debug, Clone)]
+pub struct Generated {
+    /// `.ready` files written (transfers first), none if there were no rows
+    pub files: Vec<PathBuf>,
+    /// Failures not matched, in inbox error text format
+    pub new_inbox: Vec<String>,
+    /// File the not matched failures were written to, if any
+    pub new_inbox_file: Option<PathBuf>,
+    /// HTML report, next to the `.ready` file
+    pub report_file: PathBuf,
+    pub report: RunReport,
+    /// Failures output could not be generated for
+    pub errors: Vec<String>,
+    /// Validation of the generated rows; nothing is written if there are errors
+    pub findings: Findings,
+}
+
+/// Move a file to SAP outbound, returning its destination
+pub fn move_to_outbound(file: &Path) -> io::Result<PathBuf> {
+    let to = paths::SAP_OUTBOUND.join(file.file_name().unwrap_or(file.as_os_str()));
+
+    fs::copy(file, &to)?;
+    fs::remove_file(file)?;
+
+    Ok(to)
+}
+
+/// Comparison or issue of a set of inbox errors
+///
+/// build with [`InboxWorkflow::new`] and the input methods, then run the steps in order:
+/// [`parse`](Self::parse), [`match_confirmations`](Self::match_confirmations),
+/// then [`load_orders`](Self::load_orders) and [`apply_orders`](Self::apply_orders) for a comparison,
+/// [`write_production`](Self::write_production) or [`write_issue`](Self::write_issue),
+/// and optionally [`move_generated`](Self::move_generated) and [`record_history`](Self::record_history).
+#[derive(Debug)]
+pub struct InboxWorkflow {
+    inbox_text: String,
+    archive: ArchiveSource,
+    cohv: CohvSource,
+    options: WorkflowOptions,
+
+    failures: Vec<Failure>,
+    orders: Vec<Order>,
+    not_applied: Vec<OrderData>,
+    params: RunParams,
+    files: Vec<RunFile>,
+    generated: Vec<PathBuf>,
+    /// Area per part of the archived rows of each failure mark
+    area_history: AreaHistory,
+    /// Report of the last output written
+    report: Option<RunReport>,
+}
+
+impl InboxWorkflow {
+    pub fn new(inbox_text: impl Into<String>) -> Self {
+        Self {
+            inbox_text: inbox_text.into(),
+            archive: ArchiveSource::default(),
+            cohv: CohvSource::default(),
+            options: WorkflowOptions::default(),
+
+            failures: Vec::new(),
+            orders: Vec::new(),
+            not_applied: Vec::new(),
+            params: RunParams::default(),
+            files: Vec::new(),
+            generated: Vec::new(),
+            area_history: AreaHistory::default(),
+            report: None,
+        }
+    }
+
+    /// Set where confirmation rows are searched for
+    pub fn archive(mut self, archive: ArchiveSource) -> Self {
+        self.archive = archive;
+
+        self
+    }
+
+    /// Set where COHV orders are read from
+    pub fn cohv(mut self, cohv: CohvSource) -> Self {
+        self.cohv = cohv;
+
+        self
+    }
+
+    pub fn options(mut self, options: WorkflowOptions) -> Self {
+        self.options = options;
+
+        self
+    }
+
+    /// Failures parsed, with confirmation rows and orders applied by the steps run so far
+    pub fn failures(&self) -> &[Failure] {
+        &self.failures
+    }
+
+    /// Planned orders (or remainder of) not applied to any failure
+    pub fn not_applied(&self) -> &[OrderData] {
+        &self.not_applied
+    }
+
+    /// Set how generated rows are merged and split into files
+    pub fn set_consolidation(&mut self, consolidation: Consolidation) {
+        self.options.consolidation = consolidation;
+    }
+
+    /// Replace the failures and not applied orders with edited ones, to write the output again
+    ///
+    /// the files written by the last output are forgotten, since the caller replaces them
+    pub fn edit(&mut self, failures: Vec<Failure>, not_applied: Vec<OrderData>) {
+        self.failures = failures;
+        self.not_applied = not_applied;
+
+        self.files.retain(|f| f.role == FileRole::Input);
+        self.generated.clear();
+        self.report = None;
+    }
+
+    /// Record a file read or written outside the workflow with the run, e.g. an inbox file
+    pub fn add_file(&mut self, file: RunFile) {
+        self.files.push(file);
+    }
+
+    /// Parse the inbox errors into sorted failures
+    ///
+    /// blank lines are skipped, other lines that fail to parse are returned as errors
+    pub fn parse(&mut self) -> anyhow::Result<Parsed> {
+        let lines: Vec<&str> = self.inbox_text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+
+        if lines.is_empty() {
+            return Err( anyhow!("No inbox errors to parse") );
+        }
+
+        let (failures, errors) = parse_inbox(lines.into_iter());
+        self.failures = failures;
+
+        let warnings = self.failures
+            .iter()
+            .filter_map(|f| f.check_mark_job().err())
+            .collect();
+
+        Ok( Parsed { failures: self.failures.len(), errors, warnings } )
+    }
+
+    /// Unique marks of the parsed failures, sorted
+    pub fn parts_list(&self) -> Vec<String> {
+        let mut marks: Vec<String> = self.failures
+            .iter()
+            .map(|f| f.mark.clone())
+            .collect();
+
+        marks.sort();
+        marks.dedup();
+
+        marks
+    }
+
+    /// Find the confirmation row of each failure in the archive
+    ///
+    /// archived rows of the failure marks are kept, to validate the area per part of generated rows
+    pub fn match_confirmations(&mut self, progress: &mut impl Progress) -> io::Result<Matched> {
+        let marks: HashSet<String> = self.failures.iter().map(|f| f.mark.clone()).collect();
+        let history = &mut self.area_history;
+        let mut on_row = |row: &CnfFileRow| if marks.contains(&row.mark) {
+            history.add(row);
+        };
+
+        match &self.archive {
+            ArchiveSource::SapArchive(n) => {
+                self.params.files_searched = *n;
+                match_cnf_rows_with(&mut self.failures, *paths::SAP_ARCHIVE, *n, progress, on_row)?;
+            },
+            ArchiveSource::Folder(folder, n) => {
+                self.params.files_searched = *n;
+                match_cnf_rows_with(&mut self.failures, folder, *n, progress, on_row)?;
+            },
+            ArchiveSource::Rows(rows) => {
+                rows.iter().for_each(|(row, _)| on_row(row));
+
+                for (row, file) in rows {
+                    self.failures
+                        .iter_mut()
+                        .filter(|f| **f == *row)
+                        .for_each(|f| f.set_confirmation_row_from(row.clone(), file.clone()));
+                }
+            },
+        }
+
+        let matched = self.failures.iter().filter(|f| f.has_confirmation_row()).count();
+
+        Ok( Matched { matched, total: self.failures.len() } )
+    }
+
+    /// Read orders from the COHV source, checking that an export is fresh
+    pub fn load_orders(&mut self) -> anyhow::Result<OrdersLoaded> {
+        let export = match &self.cohv {
+            CohvSource::Search(search) => search.newest(&CohvAliases::load_default()?)?,
+            CohvSource::File(path) => CohvExport::load(path.clone(), CohvAliases::load_default()?)?,
+            CohvSource::Orders(orders) => {
+                self.orders = orders.clone();
+
+                return Ok( OrdersLoaded { export: None, orders: self.orders.len(), warnings: Vec::new() } );
+            },
+        };
+
+        let marks = self.failures.iter().map(|f| f.mark.as_str());
+        let warnings = export.freshness_warnings(self.options.inbox_updated, marks);
+
+        self.params.cohv_file = Some(export.info.path.clone());
+        self.files.push( RunFile::input(&export.info.path) );
+        self.orders = export.orders;
+
+        Ok( OrdersLoaded { export: Some(export.info), orders: self.orders.len(), warnings } )
+    }
+
+    /// Apply the loaded planned orders to the failures
+    pub fn apply_orders(&mut self, progress: &mut impl Progress) -> io::Result<Applied> {
+        let orders = std::mem::take(&mut self.orders);
+        self.not_applied = compare::apply_orders(&mut self.failures, orders, progress)?;
+
+        let matched = self.failures
+            .iter()
+            .filter(|f| f.status() == FailureMatchStatus::MatchComplete)
+            .count();
+
+        Ok( Applied { matched, total: self.failures.len(), not_applied: self.not_applied.len() } )
+    }
+
+    /// Write the production file, not matched failures and report of a comparison
+    ///
+    /// generated rows are validated first, and the production file is not written if
+    /// any break a rule with [`Severity::Error`](super::validation::Severity::Error).
+    /// Every failure is then written to the not matched list at its full qty.
+    /// Material stored in another plant than the order is transferred by a `Transfer` file,
+    /// which must be posted before the production file.
+    pub fn write_production(&mut self) -> anyhow::Result<Generated> {
+        self.params.kind = RunKind::Comparison;
+
+        let locations = StorageLocations::load_default()?;
+        let mut records: Vec<CnfFileRow> = Vec::new();
+        let mut transfers: Vec<TransferFileRow> = Vec::new();
+        let mut errors = Vec::new();
+        for f in &self.failures {
+            match generate_with_transfers(f, &locations) {
+                Ok((rows, needed)) => {
+                    records.extend(rows);
+                    transfers.extend(needed);
+                },
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let findings = self.validate(&records)?;
+        if findings.has_errors() {
+            records.clear();
+            transfers.clear();
+        }
+
+        // nothing is written if any row has errors, so every failure stays in the inbox
+        let new_inbox: Vec<String> = match findings.has_errors() {
+            true  => self.failures.iter().map(Failure::inbox_text).collect(),
+            false => self.failures.iter().filter_map(|f| f.new_inbox_text()).collect(),
+        };
+
+        let new_inbox_file = match new_inbox.is_empty() {
+            true => None,
+            false => {
+                let path = self.output_file("new_inbox", "txt");
+                fs::write(&path, new_inbox.join("\n"))?;
+                self.files.push( RunFile::output(&path) );
+
+                Some(path)
+            }
+        };
+
+        let now = Local::now();
+        let (transfer_batches, mut merged) = self.options.consolidation.apply(transfers);
+        let (batches, production_merged) = self.options.consolidation.apply(records);
+        merged.extend(production_merged);
+
+        let report = RunReport {
+            params: self.params.clone(),
+            production: batches.concat(),
+            transfers: transfer_batches.concat(),
+            merged,
+            findings: findings.clone(),
+            ..RunReport::from_failures(self.failures.clone(), self.not_applied.clone())
+        };
+
+        // transfers are written (and moved) first, and production files are timestamped after
+        // the last of them, so they are posted before the production rows
+        let mut files = self.write_batches("Transfer", transfer_batches, now)?;
+        let production_time = now + Duration::seconds(files.len() as i64);
+        let (production, report_file) = self.write_ready("Production", batches, production_time, &report)?;
+        files.extend(production);
+
+        Ok( Generated { files, new_inbox, new_inbox_file, report_file, report, errors, findings } )
+    }
+
+    /// Write the issue file and report for all failures
+    ///
+    /// rows are converted to the unit SAP stocks each material in (see [`UomRegistry::load_default`])
+    pub fn write_issue(&mut self) -> anyhow::Result<Generated> {
+        self.params.kind = RunKind::Issue;
+
+        let uoms = UomRegistry::load_default()?;
+        let mut records: Vec<IssueFileRow> = Vec::new();
+        let mut errors = Vec::new();
+        for f in self.failures.iter_mut() {
+            match f.generate_issue_output() {
+                Ok(result) => match result.into_stock_uom(&uoms) {
+                    Ok(result) => records.push(result),
+                    Err(e) => errors.push( format!("{}: {}", f.mark, e) ),
+                },
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let (batches, merged) = self.options.consolidation.apply(records);
+        let report = RunReport { params: self.params.clone(), failures: self.failures.clone(), issue: batches.concat(), merged, ..Default::default() };
+        let (files, report_file) = self.write_ready("Issue", batches, Local::now(), &report)?;
+
+        Ok( Generated { files, new_inbox: Vec::new(), new_inbox_file: None, report_file, report, errors, findings: Findings::default() } )
+    }
+
+    /// Write the issue file and report correcting the consumptions in a COGI export
+    ///
+    /// each COGI error is matched to the archived confirmation row that posted it, and issued
+    /// as that row would have been. The inbox errors are not used, so the workflow can be
+    /// created with an empty inbox.
+    pub fn write_cogi_issue(&mut self, cogi_file: &Path, progress: &mut impl Progress) -> anyhow::Result<Generated> {
+        self.params.kind = RunKind::Cogi;
+
+        let mut errors = Vec::new();
+        let mut corrections = Vec::new();
+        for result in read_cogi(cogi_file)? {
+            match result {
+                Ok(error) => corrections.push( Correction::new(error) ),
+                Err(e) => errors.push( format!("COGI export: {}", e) ),
+            }
+        }
+        self.files.push( RunFile::input(cogi_file) );
+
+        match &self.archive {
+            ArchiveSource::SapArchive(n) => {
+                self.params.files_searched = *n;
+                cogi::match_archive_in(&mut corrections, *paths::SAP_ARCHIVE, *n, progress)?;
+            },
+            ArchiveSource::Folder(folder, n) => {
+                self.params.files_searched = *n;
+                cogi::match_archive_in(&mut corrections, folder, *n, progress)?;
+            },
+            ArchiveSource::Rows(rows) => {
+                for (row, file) in rows {
+                    corrections.iter_mut().for_each(|c| c.match_row(row, file));
+                }
+            },
+        }
+
+        let uoms = UomRegistry::load_default()?;
+        let mut records: Vec<IssueFileRow> = Vec::new();
+        for c in corrections.iter_mut() {
+            c.generate();
+            c.issue = c.issue.clone().and_then(|row| row.into_stock_uom(&uoms));
+
+            match &c.issue {
+                Ok(row) => records.push(row.clone()),
+                Err(e) => errors.push( format!("{}: {}", c.error.describe(), e) ),
+            }
+        }
+
+        let (batches, merged) = self.options.consolidation.apply(records);
+        let report = RunReport { params: self.params.clone(), issue: batches.concat(), cogi: corrections, merged, ..Default::default() };
+        let (files, report_file) = self.write_ready("Issue", batches, Local::now(), &report)?;
+
+        Ok( Generated { files, new_inbox: Vec::new(), new_inbox_file: None, report_file, report, errors, findings: Findings::default() } )
+    }
+
+    /// Validate confirmation rows against the default rules, configuration tables and archived area per part
+    pub fn validate(&self, rows: &[CnfFileRow]) -> io::Result<Findings> {
+        let validator = Validator::with_tables(
+            UomRegistry::load_default()?,
+            StorageLocations::load_default()?,
+            self.area_history.clone()
+        );
+
+        Ok( validator.validate(rows) )
+    }
+
+    /// Move the `.ready` files written by this workflow to SAP outbound, returning their destinations
+    pub fn move_generated(&mut self) -> io::Result<Vec<PathBuf>> {
+        let mut moved = Vec::new();
+        for file in std::mem::take(&mut self.generated) {
+            let to = move_to_outbound(&file)?;
+            self.files.push( RunFile::moved(&to) );
+
+            moved.push(to);
+        }
+
+        Ok(moved)
+    }
+
+    /// Record the run in the history database, if one is set, returning the run id
+    #[cfg(feature = "history")]
+    pub fn record_history(&self) -> anyhow::Result<Option<i64>> {
+        let path = match &self.options.history {
+            Some(path) => path,
+            None => return Ok(None)
+        };
+
+        let report = match &self.report {
+            Some(report) => report.clone(),
+            None => RunReport { params: self.params.clone(), failures: self.failures.clone(), ..Default::default() }
+        };
+
+        let mut history = History::open(path)?;
+
+        Ok( Some(history.record_run(&report, &self.files)?) )
+    }
+
+    fn output_file(&self, prefix: &str, ext: &str) -> PathBuf {
+        self.options.output_dir.join( timestamped_file(prefix, ext) )
+    }
+
+    /// Write a `.ready` file for each non-empty batch of rows
+    ///
+    /// each file after the first is timestamped a second after `time`, so the names stay unique and in order
+    fn write_batches<T: serde::Serialize>(&mut self, prefix: &str, batches: Vec<Vec<T>>, time: DateTime<Local>) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for (i, records) in batches.into_iter().filter(|b| !b.is_empty()).enumerate() {
+            let ready = self.options.output_dir.join( timestamped_file_at(prefix, "ready", time + Duration::seconds(i as i64)) );
+            write_file(records, ready.clone())?;
+
+            self.files.push( RunFile::output(&ready) );
+            self.generated.push(ready.clone());
+            files.push(ready);
+        }
+
+        Ok(files)
+    }
+
+    /// Write the batches and the HTML report, named after the first `.ready` file
+    fn write_ready<T: serde::Serialize>(&mut self, prefix: &str, batches: Vec<Vec<T>>, time: DateTime<Local>, report: &RunReport) -> anyhow::Result<(Vec<PathBuf>, PathBuf)> {
+        let report_file = report_path( &self.options.output_dir.join(timestamped_file_at(prefix, "ready", time)) );
+        let files = self.write_batches(prefix, batches, time)?;
+
+        report.write_html(&report_file)?;
+        self.files.push( RunFile::output(&report_file) );
+        self.report = Some(report.clone());
+
+        Ok( (files, report_file) )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Plant;
+    use crate::inbox::cnf_files::{deserialize_record, HEADERS};
+
+    #[test]
+    fn comparison_steps() {
+        let output_dir = std::env::temp_dir().join( format!("sap-error-utils-workflow-{}", std::process::id()) );
+        fs::create_dir_all(&output_dir).unwrap();
+
+        let mut workflow = InboxWorkflow::new("\
+            Planned order not found for 1210123A-X1A, D-1210123-10004, 3.000, Sigmanest Program:54091\n\
+            \n\
+            not an inbox error\n\
+        ");
+
+        let record = csv::StringRecord::from(
+            "1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t3\tEA\t50W-0008\t\t30.000\tIN2\tK2\tHS01\t54091".split('\t').collect::<Vec<_>>()
+        );
+        let row: CnfFileRow = deserialize_record(&record, &HEADERS).unwrap();
+        let order = Order::PlannedOrder(OrderData {
+            id: 1234567,
+            mark: "1210123A-X1A".into(),
+            qty: 3,
+            wbs: "D-1210123-10004".try_into().unwrap(),
+            plant: Plant::Lancaster,
+        });
+
+        workflow = workflow
+            .archive(ArchiveSource::Rows(vec![ (row, "Production_20230105083000.ready".into()) ]))
+            .cohv(CohvSource::Orders(vec![order]))
+            .options(WorkflowOptions { output_dir: output_dir.clone(), ..Default::default() });
+
+        let parsed = workflow.parse().unwrap();
+        assert_eq!((parsed.failures, parsed.errors.len(), parsed.warnings.len()), (1, 1, 0));
+        assert_eq!(workflow.parts_list(), vec!["1210123A-X1A"]);
+
+        assert_eq!(workflow.match_confirmations(&mut ()).unwrap().matched, 1);
+        assert_eq!(workflow.load_orders().unwrap().orders, 1);
+
+        let applied = workflow.apply_orders(&mut ()).unwrap();
+        assert_eq!((applied.matched, applied.not_applied), (1, 0));
+
+        let generated = workflow.write_production().unwrap();
+        assert!(generated.files.len() == 1 && generated.files[0].exists());
+        assert!(generated.report_file.exists());
+        assert!(generated.new_inbox.is_empty());
+        assert_eq!(generated.report.production.len(), 1);
+
+        // manual edits: the failure is excluded, so it goes back to the inbox
+        let mut failures = workflow.failures().to_vec();
+        failures[0].exclude = true;
+        workflow.edit(failures, Vec::new());
+        assert!(workflow.files.is_empty() && workflow.generated.is_empty());
+
+        let generated = workflow.write_production().unwrap();
+        assert!(generated.files.is_empty());
+        assert_eq!(generated.new_inbox.len(), 1);
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn blocked_output_keeps_failures_in_inbox() {
+        let output_dir = std::env::temp_dir().join( format!("sap-error-utils-blocked-{}", std::process::id()) );
+        fs::create_dir_all(&output_dir).unwrap();
+
+        // part location is not PROD, an error
+        let record = csv::StringRecord::from(
+            "1210123A-X1A\tS-1210123\tD-1210123-10004\tK1\t3\tEA\t50W-0008\t\t30.000\tIN2\tK2\tHS01\t54091".split('\t').collect::<Vec<_>>()
+        );
+        let row: CnfFileRow = deserialize_record(&record, &HEADERS).unwrap();
+        let order = Order::PlannedOrder(OrderData {
+            id: 1234567,
+            mark: "1210123A-X1A".into(),
+            qty: 3,
+            wbs: "D-1210123-10004".try_into().unwrap(),
+            plant: Plant::Lancaster,
+        });
+
+        let mut workflow = InboxWorkflow::new("Planned order not found for 1210123A-X1A, D-1210123-10004, 3.000, Sigmanest Program:54091")
+            .archive(ArchiveSource::Rows(vec![ (row, "Production_20230105083000.ready".into()) ]))
+            .cohv(CohvSource::Orders(vec![order]))
+            .options(WorkflowOptions { output_dir: output_dir.clone(), ..Default::default() });
+
+        workflow.parse().unwrap();
+        workflow.match_confirmations(&mut ()).unwrap();
+        workflow.load_orders().unwrap();
+        assert_eq!(workflow.apply_orders(&mut ()).unwrap().matched, 1);
+
+        let generated = workflow.write_production().unwrap();
+        assert!(generated.findings.has_errors());
+        assert!(generated.files.is_empty());
+        assert_eq!(generated.new_inbox, vec!["Planned order not found for 1210123A-X1A, D-1210123-10004, 3.000, Sigmanest Program:54091"]);
+        assert!(generated.new_inbox_file.unwrap().exists());
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[cfg(feature = "excel")]
+    #[test]
+    fn cogi_issue() {
+        let output_dir = std::env::temp_dir().join( format!("sap-error-utils-cogi-{}", std::process::id()) );
+        fs::create_dir_all(&output_dir).unwrap();
+
+        let cogi_file = output_dir.join("cogi.txt");
+        fs::write(&cogi_file, "\
+Order\tMaterial\tPlant\tStorage Location\tQuantity\tUnit of Entry\tWBS Element\tMessage Text
+1234567\t50W-0008\tHS01\tK2\t30.000\tIN2\tD-1210123-10004\tDeficit of SL Unrestricted-use 30.000 IN2
+1234568\tA36-0004\tHS01\tK2\t5.000\tIN2\tD-1210123-10004\tDeficit of SL Unrestricted-use 5.000 IN2
+").unwrap();
+
+        let record = csv::StringRecord::from(
+            "1210123A-X1A\tS-1210123\tD-1210123-10004\tPROD\t3\tEA\t50W-0008\t\t30.000\tIN2\tK2\tHS01\t54091".split('\t').collect::<Vec<_>>()
+        );
+        let row: CnfFileRow = deserialize_record(&record, &HEADERS).unwrap();
+
+        let mut workflow = InboxWorkflow::new("")
+            .archive(ArchiveSource::Rows(vec![ (row, "Production_20230105083000.ready".into()) ]))
+            .options(WorkflowOptions { output_dir: output_dir.clone(), ..Default::default() });
+
+        let generated = workflow.write_cogi_issue(&cogi_file, &mut ()).unwrap();
+        assert!(generated.files.len() == 1 && generated.files[0].exists());
+        assert_eq!(generated.report.cogi.len(), 2);
+        assert_eq!(generated.report.issue.len(), 1);
+        assert_eq!(generated.report.issue[0].user1, "D-1210123");
+        assert_eq!(generated.report.issue[0].matl_qty, crate::api::Qty::from(30));
+        assert_eq!(generated.errors.len(), 1);
+
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
@@ -4,7 +4,11 @@
 #[macro_use] extern crate serde;
 
 pub mod api;
+#[cfg(any(feature = "gui", feature = "cli"))]
 pub mod apps;
+#[cfg(feature = "excel")]
 pub mod excel;
+pub mod history;
 pub mod inbox;
 pub mod paths;
+pub mod report;
@@ -3,7 +3,7 @@
 // TODO: refactor into paths module
 
 use regex::Regex;
-use std::io::Error;
+use std::io::{self, Error};
 use std::path::{Path, PathBuf};
 
 lazy_static! {
@@ -18,6 +18,21 @@ lazy_static! {
     /// SAP archive for confirmation, issue, stock, etc. files
     pub static ref SAP_ARCHIVE: &'static Path = Path::new(r"\\hiifileserv1\sigmanestprd\Archive");
 
+    /// COHV header alias table, in the working directory
+    pub static ref COHV_ALIASES: &'static Path = Path::new("cohv_aliases.txt");
+
+    /// Material density table, in the working directory
+    pub static ref MATERIAL_DENSITIES: &'static Path = Path::new("material_densities.txt");
+
+    /// Material stocking unit table, in the working directory
+    pub static ref MATERIAL_UOMS: &'static Path = Path::new("material_uoms.txt");
+
+    /// Material storage location plants, in the working directory
+    pub static ref STORAGE_LOCATIONS: &'static Path = Path::new("storage_locations.txt");
+
+    /// Run history database, in the working directory
+    pub static ref HISTORY_DB: &'static Path = Path::new("history.db");
+
     /// Production file pattern
     pub static ref PROD_FILE_NAME: Regex = Regex::new(r"Production_(\d{14}).(?:ready|outbound\.archive)").expect("failed to build regex");
 }
@@ -26,14 +41,21 @@ lazy_static! {
 /// 
 /// returns a formatted string `{prefix}_{year}{month}{day}{hour}{minute}{seconds}.{ext}`
 pub fn timestamped_file(prefix: &str, ext: &str) -> String {
-    let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S").to_string();
+    timestamped_file_at(prefix, ext, chrono::Local::now())
+}
+
+/// Create a filename with a naturally sortable timestamp of `time`
+///
+/// see [`timestamped_file`]
+pub fn timestamped_file_at(prefix: &str, ext: &str, time: chrono::DateTime<chrono::Local>) -> String {
+    let timestamp = time.format("%Y%m%d%H%M%S").to_string();
 
     format!("{}_{}.{}", prefix, timestamp, ext)
 }
 
 /// Get all confirmation files to be processed
 pub fn get_ready_files() -> Result<Vec<PathBuf>, Error> {
-    let files = std::fs::read_dir(&*CNF_FILES)?
+    let files = std::fs::read_dir(*CNF_FILES)?
         .filter_map(|f| f.ok())
         .filter(|f| PROD_FILE_NAME.is_match(f.file_name().to_str().unwrap_or("skip file")))
         .map(|f| f.path().to_path_buf())
@@ -42,6 +64,28 @@ pub fn get_ready_files() -> Result<Vec<PathBuf>, Error> {
     Ok(files)
 }
 
+/// Read a tab delimited table of `key<TAB>value` lines
+///
+/// `#` comment lines and lines without two columns are skipped
+pub fn read_pairs(path: &Path) -> io::Result<Vec<(String, String)>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .comment(Some(b'#'))
+        .flexible(true)
+        .from_path(path)?;
+
+    let mut pairs = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        if let (Some(key), Some(value)) = (record.get(0), record.get(1)) {
+            pairs.push( (key.to_string(), value.to_string()) );
+        }
+    }
+
+    Ok(pairs)
+}
+
 /// Confirmation file path functions to extend to [`std::path::PathBuf`]
 pub trait CnfFilePaths {
     /// Create a new production file name from current timestamp
@@ -49,13 +93,13 @@ pub trait CnfFilePaths {
     /// Create a new issue file name from current timestamp
     fn new_issue_file() -> Self;
     /// Create an archive file name from an existing file name
-    fn archive_file(self: &Self) -> Self;
+    fn archive_file(&self) -> Self;
     /// Create an backup file name from an existing file name
-    fn backup_file(self: &Self) -> Self;
+    fn backup_file(&self) -> Self;
     /// Create an issue file name from an existing file name
-    fn production_file(self: &Self) -> Self;
+    fn production_file(&self) -> Self;
     /// Create an issue file name from an existing file name
-    fn issue_file(self: &Self) -> Self;
+    fn issue_file(&self) -> Self;
 }
 
 impl CnfFilePaths for PathBuf {
@@ -69,7 +113,7 @@ impl CnfFilePaths for PathBuf {
         CNF_FILES.join( timestamped_file("Issue", "ready") )
     }
     
-    fn archive_file(self: &Self) -> Self {
+    fn archive_file(&self) -> Self {
         let mut path = CNF_FILES.join( "processed" );
 
         // safe to unwrap Option<&OsStr> here
@@ -80,7 +124,7 @@ impl CnfFilePaths for PathBuf {
         path
     }
     
-    fn backup_file(self: &Self) -> Self {
+    fn backup_file(&self) -> Self {
         let mut path = CNF_FILES.join( "original" );
 
         // safe to unwrap Option<&OsStr> here
@@ -91,7 +135,7 @@ impl CnfFilePaths for PathBuf {
         path
     }
 
-    fn production_file(self: &Self) -> Self {
+    fn production_file(&self) -> Self {
         let mut path = CNF_OUTBOX.to_path_buf();
 
         // safe to unwrap Option<&OsStr> and Option<&str> here
@@ -101,7 +145,7 @@ impl CnfFilePaths for PathBuf {
         path
     }
     
-    fn issue_file(self: &Self) -> Self {
+    fn issue_file(&self) -> Self {
         let mut path = CNF_OUTBOX.to_path_buf();
 
         // safe to unwrap Option<&OsStr> and Option<&str> here
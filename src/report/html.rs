@@ -0,0 +1,217 @@
+//! Self-contained HTML export of a [`RunReport`]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::inbox::FailureMatchStatus;
+use super::RunReport;
+
+const STYLE: &str = "
+body { font-family: Calibri, Arial, sans-serif; font-size: 10pt; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #bbb; padding: 2px 8px; text-align: left; vertical-align: top; }
+th { background: #eee; }
+td.num { text-align: right; }
+tr.matched td.status { color: #080; }
+tr.missing td.status { color: #c60; }
+tr.nocnf td.status, tr.excluded td.status { color: #c00; }
+table.orders { margin: 0; }
+p.error { color: #c00; font-weight: bold; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Path of the report for a generated `.ready` file, next to it
+pub fn report_path(ready_file: &Path) -> PathBuf {
+    let stem = ready_file.file_stem().and_then(|s| s.to_str()).unwrap_or("Run");
+
+    ready_file.with_file_name(format!("{}_report.html", stem))
+}
+
+impl RunReport {
+    pub fn to_html(&self) -> String {
+        let params = &self.params;
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{} run {}</title><style>{}</style></head><body>\n",
+            params.kind, params.started.format("%Y-%m-%d %H:%M"), STYLE
+        );
+
+        html.push_str(&format!("<h1>{} run {}</h1>\n", params.kind, params.started.format("%Y-%m-%d %H:%M:%S")));
+        html.push_str("<h2>Parameters</h2>\n<table>\n");
+        html.push_str(&format!("<tr><th>Archive files searched</th><td>{}</td></tr>\n", params.files_searched));
+        let cohv = params.cohv_file.as_ref().map_or("-".into(), |p| escape(&p.display().to_string()));
+        html.push_str(&format!("<tr><th>COHV export</th><td>{}</td></tr>\n", cohv));
+        html.push_str(&format!("<tr><th>Strategy</th><td>{}</td></tr>\n", escape(&params.strategy)));
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Summary</h2>\n<table>\n");
+        html.push_str(&format!("<tr><th>Failures</th><td class=\"num\">{}</td></tr>\n", self.failures.len()));
+        for (status, count) in self.status_counts() {
+            html.push_str(&format!("<tr><th>{}</th><td class=\"num\">{}</td></tr>\n", status, count));
+        }
+        html.push_str(&format!("<tr><th>Production rows</th><td class=\"num\">{}</td></tr>\n", self.production.len()));
+        html.push_str(&format!("<tr><th>Issue rows</th><td class=\"num\">{}</td></tr>\n", self.issue.len()));
+        html.push_str(&format!("<tr><th>Transfer rows</th><td class=\"num\">{}</td></tr>\n", self.transfers.len()));
+        html.push_str(&format!("<tr><th>Planned orders not applied</th><td class=\"num\">{}</td></tr>\n", self.unapplied.len()));
+        if !self.cogi.is_empty() {
+            let corrected = self.cogi.iter().filter(|c| c.issue.is_ok()).count();
+            html.push_str(&format!("<tr><th>COGI errors corrected</th><td class=\"num\">{}/{}</td></tr>\n", corrected, self.cogi.len()));
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Failures</h2>\n<table>\n");
+        html.push_str("<tr><th>Mark</th><th>WBS</th><th>Program</th><th>Qty</th><th>Status</th><th>Material</th><th>Archive file</th><th>Applied orders</th></tr>\n");
+        for f in &self.failures {
+            let (class, status) = match f.status() {
+                _ if f.exclude => ("excluded", "Excluded".to_string()),
+                status @ FailureMatchStatus::MatchComplete => ("matched", status.to_string()),
+                status @ FailureMatchStatus::NoConfirmationRow => ("nocnf", status.to_string()),
+                status => ("missing", status.to_string()),
+            };
+
+            let orders = match f.applied.is_empty() {
+                true => String::from("-"),
+                false => {
+                    let rows: String = f.applied
+                        .iter()
+                        .map(|o| format!("<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"num\">{}</td></tr>", o.id, escape(&o.wbs.to_string()), o.plant, o.qty))
+                        .collect();
+
+                    format!("<table class=\"orders\">{}</table>", rows)
+                }
+            };
+
+            html.push_str(&format!(
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td class=\"num\">{}</td><td class=\"status\">{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                class,
+                escape(&f.mark),
+                escape(&f.wbs.to_string()),
+                escape(&f.program),
+                f.qty,
+                escape(&status),
+                f.confirmation_row().map_or("-".into(), |r| escape(&r.matl.to_string())),
+                f.confirmation_file().map_or("-".into(), |p| escape(&p.display().to_string())),
+                orders,
+            ));
+        }
+        html.push_str("</table>\n");
+
+        if !self.cogi.is_empty() {
+            html.push_str("<h2>COGI errors</h2>\n<table>\n");
+            html.push_str("<tr><th>Order</th><th>Material</th><th>Qty</th><th>UoM</th><th>Plant</th><th>Message</th><th>Archive file</th><th>Correction</th></tr>\n");
+            for c in &self.cogi {
+                let e = &c.error;
+                let (class, correction) = match &c.issue {
+                    Ok(row) => ("matched", format!("{} {} {}", row.code, row.user1, row.user2)),
+                    Err(reason) => ("nocnf", reason.clone()),
+                };
+
+                html.push_str(&format!(
+                    "<tr class=\"{}\"><td>{}</td><td>{}</td><td class=\"num\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"status\">{}</td></tr>\n",
+                    class,
+                    e.order.map_or("-".into(), |o| o.to_string()),
+                    escape(&e.matl.to_string()),
+                    e.qty,
+                    e.uom,
+                    e.plant,
+                    escape(e.message.as_deref().unwrap_or("-")),
+                    c.archived.as_ref().map_or("-".into(), |(_, p)| escape(&p.display().to_string())),
+                    escape(&correction),
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.unapplied.is_empty() {
+            html.push_str("<h2>Planned orders not applied</h2>\n<table>\n<tr><th>Order</th><th>Mark</th><th>WBS</th><th>Plant</th><th>Qty</th></tr>\n");
+            for o in &self.unapplied {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"num\">{}</td></tr>\n",
+                    o.id, escape(&o.mark), escape(&o.wbs.to_string()), o.plant, o.qty
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.transfers.is_empty() {
+            html.push_str("<h2>Plant to plant transfers</h2>\n<p>Post the transfer file before the production file.</p>\n<table>\n");
+            html.push_str("<tr><th>Material</th><th>WBS</th><th>Qty</th><th>UoM</th><th>From</th><th>To</th><th>Program</th></tr>\n");
+            for t in &self.transfers {
+                let from = format!("{} {}", t.from_plant, t.from_loc.as_deref().unwrap_or(""));
+                let to = format!("{} {}", t.to_plant, t.to_loc.as_deref().unwrap_or(""));
+
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td class=\"num\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape(&t.matl.to_string()), escape(&t.matl_wbs.to_string()), t.matl_qty, t.matl_uom, escape(from.trim()), escape(to.trim()), escape(&t.program)
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.findings.is_empty() {
+            html.push_str("<h2>Validation</h2>\n");
+            if self.findings.has_errors() {
+                html.push_str("<p class=\"error\">Production and transfer files not written: rows have validation errors</p>\n");
+            }
+
+            html.push_str("<table>\n<tr><th>Severity</th><th>Rule</th><th>Mark</th><th>WBS</th><th>Finding</th></tr>\n");
+            for (severity, findings) in self.findings.by_severity() {
+                for f in findings {
+                    html.push_str(&format!(
+                        "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                        severity, f.rule, escape(&f.mark), escape(&f.wbs.to_string()), escape(&f.message)
+                    ));
+                }
+            }
+            html.push_str("</table>\n");
+        }
+
+        if !self.merged.is_empty() {
+            html.push_str("<h2>Merged rows</h2>\n<table>\n<tr><th>Row</th><th>Merged from</th></tr>\n");
+            for m in &self.merged {
+                let sources = m.sources
+                    .iter()
+                    .map(|s| escape(s))
+                    .collect::<Vec<_>>()
+                    .join("<br>");
+
+                html.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n", escape(&m.row), sources));
+            }
+            html.push_str("</table>\n");
+        }
+
+        html.push_str("</body></html>\n");
+
+        html
+    }
+
+    pub fn write_html(&self, path: &Path) -> std::io::Result<()> {
+        fs::write(path, self.to_html())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::inbox::Failure;
+    use super::{report_path, RunReport};
+
+    #[test]
+    fn html_report() {
+        let failure = Failure::try_from(
+            String::from("Planned order not found for 1200248A-X1A, D-1200248-10001, 2.000, Sigmanest Program:50123")
+        ).unwrap();
+
+        let html = RunReport::from_failures(vec![failure], Vec::new()).to_html();
+        assert!(html.contains("<tr><th>No confirmation row</th><td class=\"num\">1</td></tr>"));
+        assert!(html.contains("<td>1200248A-X1A</td>"));
+
+        assert_eq!(report_path(Path::new("out/Production_20230814120000.ready")), Path::new("out/Production_20230814120000_report.html"));
+    }
+}
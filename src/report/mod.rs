@@ -0,0 +1,134 @@
+//! Reports of comparison and issue runs, for sharing outside the app
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local};
+
+use crate::api::{CnfFileRow, IssueFileRow, OrderData, TransferFileRow};
+use crate::inbox::{Failure, FailureMatchStatus};
+use crate::inbox::cogi::Correction;
+use crate::inbox::compare::ORDER_ALLOCATION;
+use crate::inbox::consolidate::Merged;
+use crate::inbox::validation::Findings;
+
+mod html;
+pub use html::report_path;
+#[cfg(feature = "excel")]
+mod xlsx;
+
+/// Type of run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunKind {
+    Comparison,
+    Issue,
+    /// Issue correcting a COGI export
+    Cogi,
+}
+
+impl std::fmt::Display for RunKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Comparison => write!(f, "Comparison"),
+            Self::Issue      => write!(f, "Issue"),
+            Self::Cogi       => write!(f, "COGI"),
+        }
+    }
+}
+
+impl TryFrom<&str> for RunKind {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "Comparison" => Ok(Self::Comparison),
+            "Issue"      => Ok(Self::Issue),
+            "COGI"       => Ok(Self::Cogi),
+            _ => Err( anyhow!("Unknown run kind `{}`", value) )
+        }
+    }
+}
+
+/// Parameters a run was made with
+#[derive(Debug, Clone)]
+pub struct RunParams {
+    pub kind: RunKind,
+    pub started: DateTime<Local>,
+    /// Archived confirmation files searched
+    pub files_searched: usize,
+    /// COHV export orders were read from
+    pub cohv_file: Option<PathBuf>,
+    /// Order allocation strategy
+    pub strategy: String,
+}
+
+impl Default for RunParams {
+    fn default() -> Self {
+        Self {
+            kind: RunKind::Comparison,
+            started: Local::now(),
+            files_searched: 0,
+            cohv_file: None,
+            strategy: ORDER_ALLOCATION.into(),
+        }
+    }
+}
+
+/// Results of a comparison or issue run
+#[derive(Debug, Clone, Default)]
+pub struct RunReport {
+    pub params: RunParams,
+    /// Failures, with their matched confirmation rows and applied orders
+    pub failures: Vec<Failure>,
+    /// Planned orders (or remainder of) not applied to any failure
+    pub unapplied: Vec<OrderData>,
+    /// Generated Production file rows
+    pub production: Vec<CnfFileRow>,
+    /// Generated Issue file rows
+    pub issue: Vec<IssueFileRow>,
+    /// Generated plant to plant transfer rows
+    pub transfers: Vec<TransferFileRow>,
+    /// COGI errors, with their corrective issue rows
+    pub cogi: Vec<Correction>,
+    /// Generated rows that were merged, if consolidated
+    pub merged: Vec<Merged>,
+    /// Validation of the generated production rows
+    pub findings: Findings,
+}
+
+impl RunReport {
+    /// Report of a comparison, generating the production rows from the failures
+    pub fn from_failures(failures: Vec<Failure>, unapplied: Vec<OrderData>) -> Self {
+        let production = failures
+            .iter()
+            .filter_map(|f| f.generate_output().ok())
+            .flatten()
+            .collect();
+
+        Self { failures, unapplied, production, ..Default::default() }
+    }
+
+    /// Number of failures per status: matched, no confirmation row, missing orders and excluded
+    pub fn status_counts(&self) -> [(&'static str, usize); 4] {
+        let mut counts = [("Matched", 0), ("No confirmation row", 0), ("Missing orders", 0), ("Excluded", 0)];
+
+        for f in &self.failures {
+            let i = match f.status() {
+                _ if f.exclude => 3,
+                FailureMatchStatus::MatchComplete => 0,
+                FailureMatchStatus::NoConfirmationRow => 1,
+                FailureMatchStatus::NotEnoughOrdersApplied(_) => 2,
+            };
+
+            counts[i].1 += 1;
+        }
+
+        counts
+    }
+
+    /// Failures not fully matched, or excluded
+    pub fn unmatched(&self) -> impl Iterator<Item = &Failure> {
+        self.failures
+            .iter()
+            .filter(|f| f.exclude || f.status() != FailureMatchStatus::MatchComplete)
+    }
+}
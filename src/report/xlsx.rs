@@ -0,0 +1,170 @@
+//! Excel export of a [`RunReport`]
+
+use std::path::Path;
+
+use crate::api::DensityTable;
+use crate::excel::writer::{Cell, Workbook};
+use crate::inbox::cnf_files::{HEADERS, ISSUE_HEADERS};
+use super::RunReport;
+
+impl RunReport {
+    /// Write the report as a workbook, one sheet per result type
+    ///
+    /// plate weights use the densities in [`DensityTable::load_default`]
+    pub fn write_xlsx(&self, path: &Path) -> anyhow::Result<()> {
+        let densities = DensityTable::load_default()?;
+        let weight = |w: Option<f64>| w.map_or(Cell::Empty, Cell::Area);
+        let mut wb = Workbook::new();
+
+        let sheet = wb.add_sheet("Failures", &["Mark", "WBS", "Program", "Qty", "Status", "Excluded", "Material", "Area/EA", "Archive file"]);
+        for f in &self.failures {
+            let row = f.confirmation_row();
+
+            sheet.push_row(vec![
+                f.mark.as_str().into(),
+                f.wbs.to_string().into(),
+                f.program.as_str().into(),
+                f.qty.into(),
+                f.status().to_string().into(),
+                if f.exclude { "yes".into() } else { Cell::Empty },
+                row.map(|r| r.matl.to_string()).into(),
+                row.map_or(Cell::Empty, |r| Cell::Area(r.area_per_ea())),
+                f.confirmation_file().map(|p| p.display().to_string()).into(),
+            ]);
+        }
+
+        let sheet = wb.add_sheet("Applied Orders", &["Mark", "Failure WBS", "Program", "Order", "Order WBS", "Plant", "Qty", "Material Qty"]);
+        for f in &self.failures {
+            let area = f.confirmation_row().map(|r| r.area_per_ea());
+
+            for order in &f.applied {
+                sheet.push_row(vec![
+                    f.mark.as_str().into(),
+                    f.wbs.to_string().into(),
+                    f.program.as_str().into(),
+                    order.id.into(),
+                    order.wbs.to_string().into(),
+                    order.plant.to_string().into(),
+                    order.qty.into(),
+                    area.map_or(Cell::Empty, |a| Cell::Area(a * order.qty as f64)),
+                ]);
+            }
+        }
+
+        let mut header = HEADERS.to_vec();
+        header.extend(["Area/EA", "Weight (lb)"]);
+        let sheet = wb.add_sheet("Production", &header);
+        for r in &self.production {
+            sheet.push_row(vec![
+                r.mark.as_str().into(),
+                r.id.as_str().into(),
+                r.part_wbs.to_string().into(),
+                r.part_loc.as_str().into(),
+                r.part_qty.into(),
+                r.part_uom.to_string().into(),
+                r.matl.to_string().into(),
+                r.matl_wbs.to_string().into(),
+                Cell::Qty(r.matl_qty.into()),
+                r.matl_uom.to_string().into(),
+                r.matl_loc.as_deref().into(),
+                r.plant.to_string().into(),
+                r.program.as_str().into(),
+                Cell::Area(r.area_per_ea()),
+                weight(r.weight(&densities)),
+            ]);
+        }
+
+        let mut header = ISSUE_HEADERS.to_vec();
+        header.push("Weight (lb)");
+        let sheet = wb.add_sheet("Issue", &header);
+        for r in &self.issue {
+            sheet.push_row(vec![
+                r.code.to_string().into(),
+                r.user1.as_str().into(),
+                r.user2.as_str().into(),
+                r.matl.to_string().into(),
+                r.matl_wbs.to_string().into(),
+                Cell::Qty(r.matl_qty.into()),
+                r.matl_uom.to_string().into(),
+                r.matl_loc.as_deref().into(),
+                r.plant.to_string().into(),
+                r.program.as_str().into(),
+                weight(r.weight(&densities)),
+            ]);
+        }
+
+        let sheet = wb.add_sheet("Unmatched", &["Type", "Mark", "WBS", "Program", "Qty", "Detail"]);
+        for f in self.unmatched() {
+            let (qty, detail) = match f.status() {
+                _ if f.exclude => (f.qty, "Excluded".to_string()),
+                status => (f.qty(), status.to_string()),
+            };
+
+            sheet.push_row(vec![
+                "Failure".into(),
+                f.mark.as_str().into(),
+                f.wbs.to_string().into(),
+                f.program.as_str().into(),
+                qty.into(),
+                detail.into(),
+            ]);
+        }
+        for order in self.unapplied.iter().filter(|o| o.qty > 0) {
+            sheet.push_row(vec![
+                "Planned order".into(),
+                order.mark.as_str().into(),
+                order.wbs.to_string().into(),
+                Cell::Empty,
+                order.qty.into(),
+                format!("Order {} not applied ({})", order.id, order.plant).into(),
+            ]);
+        }
+
+        for c in &self.cogi {
+            if let Err(reason) = &c.issue {
+                let e = &c.error;
+                let order = e.order.map_or(String::new(), |o| format!("Order {}: ", o));
+
+                sheet.push_row(vec![
+                    "COGI error".into(),
+                    e.mark.as_deref().into(),
+                    e.wbs.as_ref().map(|w| w.to_string()).into(),
+                    e.program.as_deref().into(),
+                    Cell::Qty(e.qty.into()),
+                    format!("{}{} {} in {}: {}", order, e.matl, e.uom, e.plant, reason).into(),
+                ]);
+            }
+        }
+
+        wb.save(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use calamine::{open_workbook, DataType, Reader, Xlsx};
+
+    use crate::api::{OrderData, Plant, Wbs};
+    use crate::inbox::Failure;
+    use super::RunReport;
+
+    #[test]
+    fn report_sheets() {
+        let failure = Failure::try_from(
+            String::from("Planned order not found for 1200248A-X1A, D-1200248-10001, 2.000, Sigmanest Program:50123")
+        ).unwrap();
+        let order = OrderData { id: 1234567, mark: "1200248A-X1B".into(), qty: 3, wbs: Wbs::try_from("D-1200248-10002").unwrap(), plant: Plant::Lancaster };
+
+        let path = std::env::temp_dir().join("sap-error-utils-report.xlsx");
+        RunReport::from_failures(vec![failure], vec![order]).write_xlsx(&path).unwrap();
+
+        let mut wb: Xlsx<_> = open_workbook(&path).unwrap();
+        let names = wb.sheet_names().to_vec();
+        let unmatched = wb.worksheet_range("Unmatched").unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(names, ["Failures", "Applied Orders", "Production", "Issue", "Unmatched"]);
+        assert_eq!(unmatched.height(), 3);
+        assert_eq!(unmatched.get_value((1, 5)), Some(&DataType::String("No confirmation row".into())));
+    }
+}